@@ -0,0 +1,396 @@
+//! Derive macros for the `qemu_api` crate.
+//!
+//! These are re-exported from `qemu_api::args`, see the documentation
+//! of [`QemuArgument`](macro@QemuArgument) and [`QemuValue`](macro@QemuValue).
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Attribute, Data, DeriveInput, Error, Fields, LitStr, Result, Type};
+
+/// Derives `QemuArgument` for an option struct.
+///
+/// The option is configured with `#[qemu(...)]` attributes on the type:
+///
+/// - `flag = "-machine"`: the flag of the option, this is required.
+/// - `implied = "type"`: the key whose value is rendered first and
+///   without its key, like the machine type in `-machine q35,smm=on`.
+/// - `value = "virtio-rng-pci"`: a constant value for the implied key.
+/// - `json`: the option supports the JSON syntax, such as `-device`.
+///
+/// Named fields are rendered as properties, with their name in
+/// kebab-case as the key. Fields that are `None` are skipped, and the
+/// option is omitted entirely if no property is left. Fields can be
+/// configured with:
+///
+/// - `rename = "key"`: uses the given key instead.
+/// - `skip`: the field isn't rendered.
+///
+/// Unit structs render only the flag, and newtype structs render the
+/// flag followed by their value. Fieldless enums that also derive
+/// `QemuValue` render the flag followed by the value of the variant.
+///
+/// This also derives `FromArg`, which parses the value of the option.
+#[proc_macro_derive(QemuArgument, attributes(qemu))]
+pub fn derive_qemu_argument(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    qemu_argument(&input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+/// Derives `ToPropValue` and `FromProp` for a fieldless enum, so it can
+/// be used as the value of a property.
+///
+/// Variants are rendered as their name in kebab-case, for example
+/// `ExitFailure` as `exit-failure`. This can be changed with
+/// `#[qemu(rename = "...")]` on the variant.
+#[proc_macro_derive(QemuValue, attributes(qemu))]
+pub fn derive_qemu_value(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    qemu_value(&input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+#[derive(Default)]
+struct OptionAttrs {
+    flag: Option<LitStr>,
+    implied: Option<LitStr>,
+    value: Option<LitStr>,
+    json: bool,
+}
+
+impl OptionAttrs {
+    fn parse(attrs: &[Attribute]) -> Result<Self> {
+        let mut res = Self::default();
+        for attr in attrs.iter().filter(|a| a.path().is_ident("qemu")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("flag") {
+                    res.flag = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("implied") {
+                    res.implied = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("value") {
+                    res.value = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("json") {
+                    res.json = true;
+                } else {
+                    return Err(meta.error("unknown qemu option attribute"));
+                }
+                Ok(())
+            })?;
+        }
+        Ok(res)
+    }
+}
+
+#[derive(Default)]
+struct FieldAttrs {
+    rename: Option<LitStr>,
+    skip: bool,
+}
+
+impl FieldAttrs {
+    fn parse(attrs: &[Attribute]) -> Result<Self> {
+        let mut res = Self::default();
+        for attr in attrs.iter().filter(|a| a.path().is_ident("qemu")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    res.rename = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("skip") {
+                    res.skip = true;
+                } else {
+                    return Err(meta.error("unknown qemu field attribute"));
+                }
+                Ok(())
+            })?;
+        }
+        Ok(res)
+    }
+}
+
+fn qemu_argument(input: &DeriveInput) -> Result<TokenStream2> {
+    let attrs = OptionAttrs::parse(&input.attrs)?;
+    let Some(flag) = &attrs.flag else {
+        return Err(Error::new_spanned(
+            input,
+            "missing `#[qemu(flag = \"...\")]` attribute",
+        ));
+    };
+    if attrs.value.is_some() && attrs.implied.is_none() {
+        return Err(Error::new_spanned(
+            input,
+            "`value` requires an `implied` key",
+        ));
+    }
+
+    let (args, parse) = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => {
+                let mut pushes = Vec::new();
+                let mut takes = Vec::new();
+                if let (Some(implied), Some(value)) = (&attrs.implied, &attrs.value) {
+                    pushes.push(quote! { props.push(#implied, #value); });
+                    takes.push(quote! { kv.expect(#implied, #value)?; });
+                }
+                let mut inits = Vec::new();
+                for field in &fields.named {
+                    let field_attrs = FieldAttrs::parse(&field.attrs)?;
+                    let ident = field.ident.as_ref().expect("named field");
+                    if field_attrs.skip {
+                        inits.push(quote! { #ident: ::std::default::Default::default() });
+                        continue;
+                    }
+                    let key = field_attrs.rename.map(|r| r.value()).unwrap_or_else(|| {
+                        ident.to_string().trim_start_matches("r#").replace('_', "-")
+                    });
+                    let take = if is_type(&field.ty, "Option") {
+                        quote! { kv.take(#key)? }
+                    } else if is_type(&field.ty, "Vec") {
+                        quote! { kv.take_all(#key)? }
+                    } else {
+                        quote! { kv.required(#key)? }
+                    };
+                    takes.push(quote! { let #ident = #take; });
+                    inits.push(quote! { #ident });
+                    pushes.push(if is_type(&field.ty, "Option") {
+                        quote! {
+                            props.push_opt(
+                                #key,
+                                self.#ident.as_ref().map(::qemu_api::args::ToPropValue::to_prop_value),
+                            );
+                        }
+                    } else {
+                        quote! {
+                            props.push(#key, ::qemu_api::args::ToPropValue::to_prop_value(&self.#ident));
+                        }
+                    });
+                }
+                let syntax = if attrs.json {
+                    quote! { syntax }
+                } else {
+                    quote! { ::qemu_api::args::ArgSyntax::KeyValue }
+                };
+                let implied = match &attrs.implied {
+                    Some(implied) => quote! { ::std::option::Option::Some(#implied) },
+                    None => quote! { ::std::option::Option::None },
+                };
+                (
+                    quote! {
+                        let mut props = ::qemu_api::args::Props::new();
+                        #(#pushes)*
+                        if props.is_empty() {
+                            ::std::vec::Vec::new()
+                        } else {
+                            ::std::vec![#flag.into(), props.render(#syntax, #implied)?]
+                        }
+                    },
+                    quote! {
+                        let value = ::qemu_api::args::required_value(#flag, value)?;
+                        let mut kv = ::qemu_api::args::KeyValues::parse(#flag, value, #implied)?;
+                        #(#takes)*
+                        kv.finish()?;
+                        Self { #(#inits),* }
+                    },
+                )
+            }
+            Fields::Unnamed(fields) if fields.unnamed.len() == 1 => (
+                quote! {
+                    ::std::vec![
+                        #flag.into(),
+                        ::qemu_api::args::ToPropValue::to_prop_value(&self.0).render_arg()?,
+                    ]
+                },
+                parse_value(flag, quote! { Self }),
+            ),
+            Fields::Unnamed(fields) => {
+                return Err(Error::new_spanned(
+                    fields,
+                    "only newtype structs are supported",
+                ))
+            }
+            Fields::Unit => (
+                quote! { ::std::vec![#flag.into()] },
+                quote! {
+                    if value.is_some() {
+                        return ::std::result::Result::Err(
+                            ::qemu_api::args::ParseError::invalid(#flag, "unexpected value"),
+                        );
+                    }
+                    Self
+                },
+            ),
+        },
+        Data::Enum(_) => (
+            quote! {
+                ::std::vec![
+                    #flag.into(),
+                    ::qemu_api::args::ToPropValue::to_prop_value(self).render_arg()?,
+                ]
+            },
+            parse_value(flag, quote! { ::std::convert::identity }),
+        ),
+        Data::Union(_) => return Err(Error::new_spanned(input, "unions are not supported")),
+    };
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::qemu_api::args::QemuArgument for #name #ty_generics #where_clause {
+            fn format(
+                &self,
+            ) -> ::std::result::Result<
+                ::std::vec::Vec<::std::ffi::OsString>,
+                ::qemu_api::args::InvalidValue,
+            > {
+                self.format_with(::qemu_api::args::ArgSyntax::KeyValue)
+            }
+
+            #[allow(unused_variables)]
+            fn format_with(
+                &self,
+                syntax: ::qemu_api::args::ArgSyntax,
+            ) -> ::std::result::Result<
+                ::std::vec::Vec<::std::ffi::OsString>,
+                ::qemu_api::args::InvalidValue,
+            > {
+                ::std::result::Result::Ok({ #args })
+            }
+        }
+
+        impl #impl_generics ::qemu_api::args::FromArg for #name #ty_generics #where_clause {
+            fn from_arg(
+                value: ::std::option::Option<&::std::ffi::OsStr>,
+            ) -> ::std::result::Result<Self, ::qemu_api::args::ParseError> {
+                ::std::result::Result::Ok({ #parse })
+            }
+        }
+    })
+}
+
+/// Parses a single value with `FromProp`, and wraps it with `wrap`.
+fn parse_value(flag: &LitStr, wrap: TokenStream2) -> TokenStream2 {
+    quote! {
+        let value = ::qemu_api::args::required_value(#flag, value)?;
+        match ::qemu_api::args::FromProp::from_prop(value) {
+            ::std::option::Option::Some(value) => #wrap(value),
+            ::std::option::Option::None => {
+                return ::std::result::Result::Err(::qemu_api::args::ParseError::invalid(
+                    #flag,
+                    ::std::format!("invalid value {:?}", value),
+                ))
+            }
+        }
+    }
+}
+
+fn qemu_value(input: &DeriveInput) -> Result<TokenStream2> {
+    let Data::Enum(data) = &input.data else {
+        return Err(Error::new_spanned(input, "only enums are supported"));
+    };
+    let mut arms = Vec::new();
+    let mut parse_arms = Vec::new();
+    for variant in &data.variants {
+        if !matches!(variant.fields, Fields::Unit) {
+            return Err(Error::new_spanned(
+                variant,
+                "only fieldless variants are supported",
+            ));
+        }
+        let attrs = FieldAttrs::parse(&variant.attrs)?;
+        if attrs.skip {
+            return Err(Error::new_spanned(variant, "variants can't be skipped"));
+        }
+        let ident = &variant.ident;
+        let value = attrs
+            .rename
+            .map(|r| r.value())
+            .unwrap_or_else(|| kebab_case(&ident.to_string()));
+        arms.push(quote! { Self::#ident => #value });
+        parse_arms.push(quote! { #value => Self::#ident });
+    }
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::qemu_api::args::ToPropValue for #name #ty_generics #where_clause {
+            fn to_prop_value(&self) -> ::qemu_api::args::PropValue {
+                let value: &'static str = match self {
+                    #(#arms,)*
+                };
+                ::qemu_api::args::PropValue::from(value)
+            }
+        }
+
+        impl #impl_generics ::qemu_api::args::FromProp for #name #ty_generics #where_clause {
+            fn from_prop(value: &::std::ffi::OsStr) -> ::std::option::Option<Self> {
+                ::std::option::Option::Some(match value.to_str()? {
+                    #(#parse_arms,)*
+                    _ => return ::std::option::Option::None,
+                })
+            }
+        }
+    })
+}
+
+/// Whether `ty` is the type with the given name, such as `Option<T>`.
+fn is_type(ty: &Type, name: &str) -> bool {
+    let Type::Path(path) = ty else {
+        return false;
+    };
+    path.qself.is_none()
+        && path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == name)
+}
+
+/// Converts a variant name such as `InjectNmi` to `inject-nmi`.
+fn kebab_case(name: &str) -> String {
+    let mut res = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_ascii_uppercase() {
+            if i > 0 {
+                res.push('-');
+            }
+            res.push(c.to_ascii_lowercase());
+        } else {
+            res.push(c);
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use qemu_api::args::QemuArgument;
+    use std::ffi::OsString;
+
+    #[derive(Default, QemuArgument)]
+    #[qemu(flag = "-test")]
+    struct TestOption {
+        first: Option<u32>,
+        second_value: Option<bool>,
+    }
+
+    #[test]
+    fn test_named_fields() {
+        let option = TestOption {
+            first: Some(1),
+            second_value: Some(true),
+        };
+        assert_eq!(
+            vec![OsString::from("-test"), "first=1,second-value=on".into()],
+            option.format().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_named_fields_empty() {
+        assert_eq!(
+            Vec::<OsString>::new(),
+            TestOption::default().format().unwrap()
+        );
+    }
+}
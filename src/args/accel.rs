@@ -1,6 +1,15 @@
-use crate::args::QemuArgument;
+use crate::args::{
+    required_value, ArgSyntax, FromArg, InvalidValue, KeyValues, ParseError, Props, QemuArgument,
+    QemuValue, ToPropValue,
+};
+use std::ffi::{OsStr, OsString};
 
 #[derive(Default, Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default, rename_all = "kebab-case")
+)]
 pub struct Accel {
     pub accelerator: Accelerator,
     pub idg_passthru: IdgPassthru,
@@ -14,37 +23,96 @@ pub struct Accel {
 }
 
 impl QemuArgument for Accel {
-    fn format(&self) -> Vec<String> {
-        let mut res = format!(
-            "accel={},idg-passthru={},kernel-irqchip={}",
-            self.accelerator.as_str(),
-            self.idg_passthru.as_str(),
-            self.kernel_irqchip.as_str(),
-        );
-        if let Some(split_wx) = self.split_wx {
-            res.push_str(&format!(",split-wx={}", split_wx.as_str()));
-        }
-        if let Some(thread) = self.thread {
-            res.push_str(&format!(",thread={}", thread.as_str()));
-        }
-        if let Some(kvm_shadow_mem) = self.kvm_shadow_mem {
-            res.push_str(&format!(",kvm-shadow-mem={}", kvm_shadow_mem));
-        }
-        if let Some(tb_size) = self.tb_size {
-            res.push_str(&format!(",tb-size={}", tb_size));
-        }
-        if let Some(dirty_ring_size) = self.dirty_ring_size {
-            res.push_str(&format!(",dirty-ring-size={}", dirty_ring_size));
-        }
+    fn format(&self) -> Result<Vec<OsString>, InvalidValue> {
+        let mut props = Props::new();
+        props
+            .push("accel", self.accelerator.to_prop_value())
+            .push("idg-passthru", self.idg_passthru.to_prop_value())
+            .push("kernel-irqchip", self.kernel_irqchip.to_prop_value())
+            .push_opt("split-wx", self.split_wx.map(|s| s.to_prop_value()))
+            .push_opt("thread", self.thread.map(|t| t.to_prop_value()))
+            .push_opt("kvm-shadow-mem", self.kvm_shadow_mem)
+            .push_opt("tb-size", self.tb_size)
+            .push_opt("dirty-ring-size", self.dirty_ring_size);
         if let Some(notify_vmexit) = self.notify_vmexit {
-            res.push_str(&format!(",notify-vmexit={}", notify_vmexit.format()));
+            notify_vmexit.push_props(&mut props);
         }
 
-        vec!["-accel".to_string(), res]
+        Ok(vec![
+            "-accel".into(),
+            props.render(ArgSyntax::KeyValue, None)?,
+        ])
     }
 }
 
-#[derive(Default, Debug, Copy, Clone, Eq, PartialEq, Hash)]
+impl Accel {
+    /// The properties that are set but have no effect with the selected
+    /// accelerator, as they only apply to KVM or TCG.
+    pub fn irrelevant_properties(&self) -> Vec<&'static str> {
+        let kvm_only = [
+            ("kvm-shadow-mem", self.kvm_shadow_mem.is_some()),
+            ("dirty-ring-size", self.dirty_ring_size.is_some()),
+            ("notify-vmexit", self.notify_vmexit.is_some()),
+        ];
+        let tcg_only = [
+            ("tb-size", self.tb_size.is_some()),
+            ("split-wx", self.split_wx.is_some()),
+            ("thread", self.thread.is_some()),
+        ];
+        let irrelevant: &[_] = match self.accelerator {
+            Accelerator::Kvm => &tcg_only,
+            Accelerator::Tcg => &kvm_only,
+            Accelerator::Help => &[],
+            _ => &[kvm_only, tcg_only].concat(),
+        };
+        irrelevant
+            .iter()
+            .filter(|(_, set)| *set)
+            .map(|(name, _)| *name)
+            .collect()
+    }
+}
+
+impl FromArg for Accel {
+    fn from_arg(value: Option<&OsStr>) -> Result<Self, ParseError> {
+        let value = required_value("-accel", value)?;
+        let mut kv = KeyValues::parse("-accel", value, Some("accel"))?;
+        let notify_vmexit = match kv.take::<String>("notify-vmexit")?.as_deref() {
+            None => None,
+            Some("run") => Some(NotifyVmexit::Run),
+            Some("internal-error") => Some(NotifyVmexit::InternalError),
+            Some("disable") => Some(NotifyVmexit::DisableAndNotifyWindow(
+                kv.required("notify-window")?,
+            )),
+            Some(other) => {
+                return Err(ParseError::invalid(
+                    "-accel",
+                    format!("invalid value {:?} for notify-vmexit", other),
+                ))
+            }
+        };
+        let accel = Self {
+            accelerator: kv.required("accel")?,
+            idg_passthru: kv.take("idg-passthru")?.unwrap_or_default(),
+            kernel_irqchip: kv.take("kernel-irqchip")?.unwrap_or_default(),
+            split_wx: kv.take("split-wx")?,
+            thread: kv.take("thread")?,
+            kvm_shadow_mem: kv.take("kvm-shadow-mem")?,
+            tb_size: kv.take("tb-size")?,
+            dirty_ring_size: kv.take("dirty-ring-size")?,
+            notify_vmexit,
+        };
+        kv.finish()?;
+        Ok(accel)
+    }
+}
+
+#[derive(Default, Debug, Copy, Clone, Eq, PartialEq, Hash, QemuValue)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
 pub enum Accelerator {
     Kvm,
     Xen,
@@ -57,38 +125,24 @@ pub enum Accelerator {
     Help,
 }
 
-impl Accelerator {
-    fn as_str(&self) -> &'static str {
-        match self {
-            Self::Kvm => "kvm",
-            Self::Xen => "xen",
-            Self::Hax => "hax",
-            Self::Hvf => "hvf",
-            Self::Nvmm => "nvmm",
-            Self::Whpx => "whpx",
-            Self::Tcg => "tcg",
-            Self::Help => "help",
-        }
-    }
-}
-
-#[derive(Default, Copy, Clone, Debug, Eq, PartialEq, Hash)]
+#[derive(Default, Copy, Clone, Debug, Eq, PartialEq, Hash, QemuValue)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
 pub enum IdgPassthru {
     On,
     #[default]
     Off,
 }
 
-impl IdgPassthru {
-    fn as_str(&self) -> &'static str {
-        match self {
-            Self::On => "on",
-            Self::Off => "off",
-        }
-    }
-}
-
-#[derive(Default, Copy, Clone, Debug, Eq, PartialEq, Hash)]
+#[derive(Default, Copy, Clone, Debug, Eq, PartialEq, Hash, QemuValue)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
 pub enum KernelIrqchip {
     #[default]
     On,
@@ -96,47 +150,34 @@ pub enum KernelIrqchip {
     Split,
 }
 
-impl KernelIrqchip {
-    fn as_str(&self) -> &'static str {
-        match self {
-            Self::On => "on",
-            Self::Off => "off",
-            Self::Split => "split",
-        }
-    }
-}
-
-#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, QemuValue)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
 pub enum SplitWx {
     On,
     Off,
 }
 
-impl SplitWx {
-    fn as_str(&self) -> &'static str {
-        match self {
-            Self::On => "on",
-            Self::Off => "off",
-        }
-    }
-}
-
-#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, QemuValue)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
 pub enum Thread {
     Single,
     Multi,
 }
 
-impl Thread {
-    fn as_str(&self) -> &'static str {
-        match self {
-            Self::Single => "single",
-            Self::Multi => "multi",
-        }
-    }
-}
-
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
 pub enum NotifyVmexit {
     Run,
     InternalError,
@@ -144,14 +185,14 @@ pub enum NotifyVmexit {
 }
 
 impl NotifyVmexit {
-    fn format(&self) -> String {
+    fn push_props(&self, props: &mut Props) {
         match self {
-            Self::Run => "run".to_string(),
-            Self::InternalError => "internal-error".to_string(),
-            Self::DisableAndNotifyWindow(window) => {
-                format!("disable,notify-window={}", window)
-            }
-        }
+            Self::Run => props.push("notify-vmexit", "run"),
+            Self::InternalError => props.push("notify-vmexit", "internal-error"),
+            Self::DisableAndNotifyWindow(window) => props
+                .push("notify-vmexit", "disable")
+                .push("notify-window", *window),
+        };
     }
 }
 
@@ -169,7 +210,7 @@ mod tests {
             ..Default::default()
         });
 
-        let args = qemu.args();
+        let args = qemu.args().unwrap();
         assert_eq!(
             &["-accel", "accel=kvm,idg-passthru=off,kernel-irqchip=on"],
             args.as_slice()
@@ -185,7 +226,7 @@ mod tests {
             ..Default::default()
         });
 
-        let args = qemu.args();
+        let args = qemu.args().unwrap();
         assert_eq!(
             &["-accel", "accel=kvm,idg-passthru=on,kernel-irqchip=on"],
             args.as_slice()
@@ -201,7 +242,7 @@ mod tests {
             ..Default::default()
         });
 
-        let args = qemu.args();
+        let args = qemu.args().unwrap();
         assert_eq!(
             &["-accel", "accel=kvm,idg-passthru=off,kernel-irqchip=off"],
             args.as_slice()
@@ -217,7 +258,7 @@ mod tests {
             ..Default::default()
         });
 
-        let args = qemu.args();
+        let args = qemu.args().unwrap();
         assert_eq!(
             &[
                 "-accel",
@@ -236,7 +277,7 @@ mod tests {
             ..Default::default()
         });
 
-        let args = qemu.args();
+        let args = qemu.args().unwrap();
         assert_eq!(
             &[
                 "-accel",
@@ -255,7 +296,7 @@ mod tests {
             ..Default::default()
         });
 
-        let args = qemu.args();
+        let args = qemu.args().unwrap();
         assert_eq!(
             &[
                 "-accel",
@@ -274,7 +315,7 @@ mod tests {
             ..Default::default()
         });
 
-        let args = qemu.args();
+        let args = qemu.args().unwrap();
         assert_eq!(
             &[
                 "-accel",
@@ -293,7 +334,7 @@ mod tests {
             ..Default::default()
         });
 
-        let args = qemu.args();
+        let args = qemu.args().unwrap();
         assert_eq!(
             &[
                 "-accel",
@@ -312,7 +353,7 @@ mod tests {
             ..Default::default()
         });
 
-        let args = qemu.args();
+        let args = qemu.args().unwrap();
         assert_eq!(
             &[
                 "-accel",
@@ -331,7 +372,7 @@ mod tests {
             ..Default::default()
         });
 
-        let args = qemu.args();
+        let args = qemu.args().unwrap();
         assert_eq!(
             &[
                 "-accel",
@@ -350,7 +391,7 @@ mod tests {
             ..Default::default()
         });
 
-        let args = qemu.args();
+        let args = qemu.args().unwrap();
         assert_eq!(
             &[
                 "-accel",
@@ -369,7 +410,7 @@ mod tests {
             ..Default::default()
         });
 
-        let args = qemu.args();
+        let args = qemu.args().unwrap();
         assert_eq!(
             &[
                 "-accel",
@@ -388,7 +429,7 @@ mod tests {
             ..Default::default()
         });
 
-        let args = qemu.args();
+        let args = qemu.args().unwrap();
         assert_eq!(
             &[
                 "-accel",
@@ -0,0 +1,175 @@
+use crate::args::{
+    required_value, ArgSyntax, FromArg, InvalidValue, KeyValues, ParseError, Props, QemuArgument,
+    QemuValue, ToPropValue,
+};
+use std::ffi::{OsStr, OsString};
+
+/// Configures how QEMU reacts to lifecycle events of the guest.
+///
+/// Every field that is `None` keeps QEMU's default behavior.
+#[derive(Default, Debug, Copy, Clone, Eq, PartialEq, Hash, QemuArgument)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default, rename_all = "kebab-case")
+)]
+#[qemu(flag = "-action")]
+pub struct Action {
+    pub reboot: Option<RebootAction>,
+    pub shutdown: Option<ShutdownAction>,
+    pub panic: Option<PanicAction>,
+    pub watchdog: Option<WatchdogAction>,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, QemuValue)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum RebootAction {
+    Reset,
+    Shutdown,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, QemuValue)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum ShutdownAction {
+    Poweroff,
+    Pause,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, QemuValue)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum PanicAction {
+    Pause,
+    Shutdown,
+    /// Makes QEMU exit with a non-zero exit code, which allows to
+    /// distinguish a guest panic from a regular shutdown.
+    ExitFailure,
+    None,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, QemuValue, QemuArgument)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+#[qemu(flag = "-watchdog-action")]
+pub enum WatchdogAction {
+    Reset,
+    Shutdown,
+    Poweroff,
+    InjectNmi,
+    Pause,
+    Debug,
+    None,
+}
+
+/// An emulated hardware watchdog, added via the `-device` option.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, QemuValue)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum Watchdog {
+    /// Intel 6300ESB, a PCI device.
+    I6300esb,
+    /// iBase 700, an ISA device.
+    Ib700,
+}
+
+impl QemuArgument for Watchdog {
+    fn format(&self) -> Result<Vec<OsString>, InvalidValue> {
+        self.format_with(ArgSyntax::KeyValue)
+    }
+
+    fn format_with(&self, syntax: ArgSyntax) -> Result<Vec<OsString>, InvalidValue> {
+        let mut props = Props::new();
+        props.push("driver", self.to_prop_value());
+        Ok(vec![
+            "-device".into(),
+            props.render(syntax, Some("driver"))?,
+        ])
+    }
+}
+
+impl FromArg for Watchdog {
+    fn from_arg(value: Option<&OsStr>) -> Result<Self, ParseError> {
+        let value = required_value("-device", value)?;
+        let mut props = KeyValues::parse("-device", value, Some("driver"))?;
+        let watchdog = props.required("driver")?;
+        props.finish()?;
+        Ok(watchdog)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Generic;
+    use crate::Qemu;
+
+    #[test]
+    fn test_action_single() {
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.action(Action {
+            panic: Some(PanicAction::ExitFailure),
+            ..Default::default()
+        });
+
+        let args = qemu.args().unwrap();
+        assert_eq!(&["-action", "panic=exit-failure"], args.as_slice());
+    }
+
+    #[test]
+    fn test_action_all() {
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.action(Action {
+            reboot: Some(RebootAction::Shutdown),
+            shutdown: Some(ShutdownAction::Poweroff),
+            panic: Some(PanicAction::None),
+            watchdog: Some(WatchdogAction::InjectNmi),
+        });
+
+        let args = qemu.args().unwrap();
+        assert_eq!(
+            &[
+                "-action",
+                "reboot=shutdown,shutdown=poweroff,panic=none,watchdog=inject-nmi"
+            ],
+            args.as_slice()
+        );
+    }
+
+    #[test]
+    fn test_watchdog() {
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.watchdog(Watchdog::I6300esb);
+        qemu.watchdog_action(WatchdogAction::Poweroff);
+
+        let args = qemu.args().unwrap();
+        assert_eq!(
+            &["-device", "i6300esb", "-watchdog-action", "poweroff"],
+            args.as_slice()
+        );
+    }
+
+    #[test]
+    fn test_action_empty() {
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.action(Action::default());
+
+        assert!(qemu.args().unwrap().is_empty());
+    }
+}
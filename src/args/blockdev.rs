@@ -0,0 +1,355 @@
+use crate::args::{
+    required_value, ArgSyntax, FromArg, InvalidValue, KeyValues, ObjectId, ParseError, PropValue,
+    Props, QemuArgument, Secret, ThrottleGroup,
+};
+use std::ffi::{OsStr, OsString};
+use std::path::PathBuf;
+
+/// A node in the block graph, added via the `-blockdev` option.
+///
+/// Child nodes can either reference another top-level node by its node
+/// name, or be defined inline, see [`BlockdevRef`].
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub struct Blockdev {
+    pub node_name: Option<String>,
+    pub driver: BlockdevDriver,
+    pub read_only: Option<bool>,
+    pub cache: Option<BlockdevCache>,
+}
+
+impl Blockdev {
+    pub fn new(node_name: impl ToString, driver: BlockdevDriver) -> Self {
+        Self {
+            node_name: Some(node_name.to_string()),
+            driver,
+            read_only: None,
+            cache: None,
+        }
+    }
+
+    /// Creates a node without a node name, which is only useful as
+    /// an inline child of another node.
+    pub fn inline(driver: BlockdevDriver) -> Self {
+        Self {
+            node_name: None,
+            driver,
+            read_only: None,
+            cache: None,
+        }
+    }
+
+    fn props(&self) -> Props {
+        let mut props = Props::new();
+        props
+            .push("driver", self.driver.as_str())
+            .push_opt("node-name", self.node_name.as_ref())
+            .push_opt("read-only", self.read_only);
+        if let Some(cache) = self.cache {
+            let mut cache_props = Props::new();
+            cache_props
+                .push_opt("direct", cache.direct)
+                .push_opt("no-flush", cache.no_flush);
+            props.push("cache", cache_props);
+        }
+        match &self.driver {
+            BlockdevDriver::File { filename } | BlockdevDriver::HostDevice { filename } => {
+                props.push("filename", filename);
+            }
+            BlockdevDriver::Raw { file } => {
+                props.push("file", file);
+            }
+            BlockdevDriver::Qcow2 { file, backing } => {
+                props
+                    .push("file", file)
+                    .push_opt("backing", backing.as_ref());
+            }
+            BlockdevDriver::Luks { file, key_secret } => {
+                props.push("file", file).push("key-secret", key_secret);
+            }
+            BlockdevDriver::Throttle {
+                throttle_group,
+                file,
+            } => {
+                props
+                    .push("throttle-group", throttle_group)
+                    .push("file", file);
+            }
+        }
+        props
+    }
+
+    /// Parses the properties of [`Self::props`].
+    fn from_props(props: &mut KeyValues) -> Result<Self, ParseError> {
+        let driver: String = props.required("driver")?;
+        let node_name = props.take("node-name")?;
+        let read_only = props.take("read-only")?;
+        let mut cache_props = props.take_prefixed("cache");
+        let cache = if cache_props.is_empty() {
+            None
+        } else {
+            Some(BlockdevCache {
+                direct: cache_props.take("direct")?,
+                no_flush: cache_props.take("no-flush")?,
+            })
+        };
+        cache_props.finish()?;
+        let driver = match driver.as_str() {
+            "file" => BlockdevDriver::File {
+                filename: props.required("filename")?,
+            },
+            "host_device" => BlockdevDriver::HostDevice {
+                filename: props.required("filename")?,
+            },
+            "raw" => BlockdevDriver::Raw {
+                file: BlockdevRef::required(props, "file")?,
+            },
+            "qcow2" => BlockdevDriver::Qcow2 {
+                file: BlockdevRef::required(props, "file")?,
+                backing: BlockdevRef::take(props, "backing")?,
+            },
+            "luks" => BlockdevDriver::Luks {
+                file: BlockdevRef::required(props, "file")?,
+                key_secret: props.required("key-secret")?,
+            },
+            "throttle" => BlockdevDriver::Throttle {
+                throttle_group: props.required("throttle-group")?,
+                file: BlockdevRef::required(props, "file")?,
+            },
+            _ => {
+                return Err(ParseError::unsupported(
+                    "-blockdev",
+                    format!("unknown driver {}", driver),
+                ))
+            }
+        };
+        Ok(Self {
+            node_name,
+            driver,
+            read_only,
+            cache,
+        })
+    }
+}
+
+impl QemuArgument for Blockdev {
+    fn format(&self) -> Result<Vec<OsString>, InvalidValue> {
+        self.format_with(ArgSyntax::KeyValue)
+    }
+
+    fn format_with(&self, syntax: ArgSyntax) -> Result<Vec<OsString>, InvalidValue> {
+        Ok(vec!["-blockdev".into(), self.props().render(syntax, None)?])
+    }
+}
+
+impl FromArg for Blockdev {
+    fn from_arg(value: Option<&OsStr>) -> Result<Self, ParseError> {
+        let value = required_value("-blockdev", value)?;
+        let mut props = KeyValues::parse("-blockdev", value, None)?;
+        let blockdev = Self::from_props(&mut props)?;
+        props.finish()?;
+        Ok(blockdev)
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum BlockdevDriver {
+    File {
+        filename: PathBuf,
+    },
+    HostDevice {
+        filename: PathBuf,
+    },
+    Raw {
+        file: BlockdevRef,
+    },
+    Qcow2 {
+        file: BlockdevRef,
+        backing: Option<BlockdevRef>,
+    },
+    Luks {
+        file: BlockdevRef,
+        key_secret: ObjectId<Secret>,
+    },
+    /// Applies the limits of a throttle group to all I/O to `file`.
+    Throttle {
+        throttle_group: ObjectId<ThrottleGroup>,
+        file: BlockdevRef,
+    },
+}
+
+impl BlockdevDriver {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::File { .. } => "file",
+            Self::HostDevice { .. } => "host_device",
+            Self::Raw { .. } => "raw",
+            Self::Qcow2 { .. } => "qcow2",
+            Self::Luks { .. } => "luks",
+            Self::Throttle { .. } => "throttle",
+        }
+    }
+}
+
+/// A reference to a child node.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum BlockdevRef {
+    /// References a node by its node name.
+    Node(String),
+    /// Defines the child node inline.
+    Inline(Box<Blockdev>),
+}
+
+impl BlockdevRef {
+    /// Removes the reference with the given key, which is either a node
+    /// name or the dotted properties of an inline node.
+    fn take(props: &mut KeyValues, key: &str) -> Result<Option<Self>, ParseError> {
+        if let Some(node) = props.take(key)? {
+            return Ok(Some(Self::Node(node)));
+        }
+        let mut inline = props.take_prefixed(key);
+        if inline.is_empty() {
+            return Ok(None);
+        }
+        let blockdev = Blockdev::from_props(&mut inline)?;
+        inline.finish()?;
+        Ok(Some(Self::Inline(Box::new(blockdev))))
+    }
+
+    fn required(props: &mut KeyValues, key: &str) -> Result<Self, ParseError> {
+        Self::take(props, key)?
+            .ok_or_else(|| ParseError::unsupported("-blockdev", format!("missing {}", key)))
+    }
+}
+
+impl From<&BlockdevRef> for PropValue {
+    fn from(value: &BlockdevRef) -> Self {
+        match value {
+            BlockdevRef::Node(name) => name.into(),
+            BlockdevRef::Inline(blockdev) => blockdev.props().into(),
+        }
+    }
+}
+
+#[derive(Default, Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default, rename_all = "kebab-case")
+)]
+pub struct BlockdevCache {
+    pub direct: Option<bool>,
+    pub no_flush: Option<bool>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Generic;
+    use crate::Qemu;
+
+    fn qcow2() -> Blockdev {
+        Blockdev {
+            read_only: Some(true),
+            ..Blockdev::new(
+                "disk0",
+                BlockdevDriver::Qcow2 {
+                    file: BlockdevRef::Inline(Box::new(Blockdev {
+                        cache: Some(BlockdevCache {
+                            direct: Some(true),
+                            ..Default::default()
+                        }),
+                        ..Blockdev::inline(BlockdevDriver::File {
+                            filename: PathBuf::from("disk.qcow2"),
+                        })
+                    })),
+                    backing: Some(BlockdevRef::Node("base0".to_string())),
+                },
+            )
+        }
+    }
+
+    #[test]
+    fn test_blockdev_key_value() {
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.blockdev(qcow2());
+
+        let args = qemu.args().unwrap();
+        assert_eq!(
+            &[
+                "-blockdev",
+                "driver=qcow2,node-name=disk0,read-only=on,file.driver=file,file.cache.direct=on,file.filename=disk.qcow2,backing=base0"
+            ],
+            args.as_slice()
+        );
+    }
+
+    #[test]
+    fn test_blockdev_json() {
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.arg_syntax(ArgSyntax::Json);
+        qemu.blockdev(qcow2());
+
+        let args = qemu.args().unwrap();
+        assert_eq!(
+            &[
+                "-blockdev",
+                r#"{"driver":"qcow2","node-name":"disk0","read-only":true,"file":{"driver":"file","cache":{"direct":true},"filename":"disk.qcow2"},"backing":"base0"}"#
+            ],
+            args.as_slice()
+        );
+    }
+
+    #[test]
+    fn test_blockdev_throttle() {
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.blockdev(Blockdev::new(
+            "throttled0",
+            BlockdevDriver::Throttle {
+                throttle_group: ObjectId::new("limits0"),
+                file: BlockdevRef::Node("disk0".to_string()),
+            },
+        ));
+
+        let args = qemu.args().unwrap();
+        assert_eq!(
+            &[
+                "-blockdev",
+                "driver=throttle,node-name=throttled0,throttle-group=limits0,file=disk0"
+            ],
+            args.as_slice()
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_blockdev_json_non_utf8() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.arg_syntax(ArgSyntax::Json);
+        qemu.blockdev(Blockdev::new(
+            "disk0",
+            BlockdevDriver::File {
+                filename: PathBuf::from(OsStr::from_bytes(b"disk-\xff.img")),
+            },
+        ));
+
+        assert!(qemu.args().is_err());
+    }
+}
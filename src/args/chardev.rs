@@ -0,0 +1,258 @@
+use crate::args::{
+    required_value, ArgSyntax, AuthzSimple, FromArg, InvalidValue, KeyValues, ObjectId, ParseError,
+    Props, QemuArgument, TlsCreds,
+};
+use std::ffi::{OsStr, OsString};
+use std::path::PathBuf;
+
+/// A character device backend, added via the `-chardev` option.
+///
+/// Devices and objects such as a [`Filter`](crate::args::Filter)
+/// reference it by its id.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub struct Chardev {
+    pub id: ObjectId<Chardev>,
+    pub kind: ChardevKind,
+}
+
+impl Chardev {
+    pub fn new(id: impl ToString, kind: ChardevKind) -> Self {
+        Self {
+            id: ObjectId::new(id),
+            kind,
+        }
+    }
+
+    fn props(&self) -> Props {
+        let mut props = Props::new();
+        props
+            .push("backend", self.kind.as_str())
+            .push("id", &self.id);
+        match &self.kind {
+            ChardevKind::Null | ChardevKind::Pty | ChardevKind::Stdio => {}
+            ChardevKind::File { path } => {
+                props.push("path", path);
+            }
+            ChardevKind::Socket {
+                addr,
+                server,
+                wait,
+                tls_creds,
+                tls_authz,
+            } => {
+                match addr {
+                    ChardevAddr::Tcp { host, port } => {
+                        props.push("host", host).push("port", u32::from(*port));
+                    }
+                    ChardevAddr::Unix { path } => {
+                        props.push("path", path);
+                    }
+                }
+                props
+                    .push_opt("server", *server)
+                    .push_opt("wait", *wait)
+                    .push_opt("tls-creds", tls_creds.as_ref())
+                    .push_opt("tls-authz", tls_authz.as_ref());
+            }
+        }
+        props
+    }
+}
+
+impl QemuArgument for Chardev {
+    fn format(&self) -> Result<Vec<OsString>, InvalidValue> {
+        Ok(vec![
+            "-chardev".into(),
+            self.props().render(ArgSyntax::KeyValue, Some("backend"))?,
+        ])
+    }
+}
+
+impl FromArg for Chardev {
+    fn from_arg(value: Option<&OsStr>) -> Result<Self, ParseError> {
+        let value = required_value("-chardev", value)?;
+        let mut props = KeyValues::parse("-chardev", value, Some("backend"))?;
+        let backend: String = props.required("backend")?;
+        let id = props.required("id")?;
+        let kind = match backend.as_str() {
+            "null" => ChardevKind::Null,
+            "pty" => ChardevKind::Pty,
+            "stdio" => ChardevKind::Stdio,
+            "file" => ChardevKind::File {
+                path: props.required("path")?,
+            },
+            "socket" => {
+                let addr = match props.take("path")? {
+                    Some(path) => ChardevAddr::Unix { path },
+                    None => ChardevAddr::Tcp {
+                        host: props.required("host")?,
+                        port: props.required("port")?,
+                    },
+                };
+                ChardevKind::Socket {
+                    addr,
+                    server: props.take("server")?,
+                    wait: props.take("wait")?,
+                    tls_creds: props.take("tls-creds")?,
+                    tls_authz: props.take("tls-authz")?,
+                }
+            }
+            _ => {
+                return Err(ParseError::unsupported(
+                    "-chardev",
+                    format!("unknown backend {}", backend),
+                ))
+            }
+        };
+        props.finish()?;
+        Ok(Self { id, kind })
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum ChardevKind {
+    /// Discards all output and never provides input.
+    Null,
+    /// Allocates a new pseudo terminal.
+    Pty,
+    Stdio,
+    /// Writes the output to a file.
+    File {
+        path: PathBuf,
+    },
+    /// Connects to, or with `server` listens on, a socket.
+    Socket {
+        addr: ChardevAddr,
+        server: Option<bool>,
+        /// Whether a server waits for a client before the guest starts.
+        wait: Option<bool>,
+        /// Encrypts TCP connections with the given credentials.
+        tls_creds: Option<ObjectId<TlsCreds>>,
+        /// Restricts the clients of a TLS server to the authorized ones.
+        tls_authz: Option<ObjectId<AuthzSimple>>,
+    },
+}
+
+impl ChardevKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Null => "null",
+            Self::Pty => "pty",
+            Self::Stdio => "stdio",
+            Self::File { .. } => "file",
+            Self::Socket { .. } => "socket",
+        }
+    }
+}
+
+/// The address of a socket chardev.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum ChardevAddr {
+    Tcp { host: String, port: u16 },
+    Unix { path: PathBuf },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::args::{TlsCredsKind, TlsEndpoint};
+    use crate::Generic;
+    use crate::Qemu;
+
+    #[test]
+    fn test_chardev_file() {
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.chardev(Chardev::new(
+            "log0",
+            ChardevKind::File {
+                path: PathBuf::from("serial.log"),
+            },
+        ));
+
+        let args = qemu.args().unwrap();
+        assert_eq!(
+            &["-chardev", "file,id=log0,path=serial.log"],
+            args.as_slice()
+        );
+    }
+
+    #[test]
+    fn test_chardev_socket_tls() {
+        let creds = TlsCreds {
+            id: ObjectId::new("tls0"),
+            kind: TlsCredsKind::X509 {
+                verify_peer: Some(true),
+                passwordid: None,
+            },
+            endpoint: TlsEndpoint::Server,
+            dir: PathBuf::from("/etc/pki/qemu"),
+        };
+        let authz = AuthzSimple {
+            id: ObjectId::new("auth0"),
+            identity: "CN=client".to_string(),
+        };
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.object(creds.clone()).object(authz.clone());
+        qemu.chardev(Chardev::new(
+            "chr0",
+            ChardevKind::Socket {
+                addr: ChardevAddr::Tcp {
+                    host: "0.0.0.0".to_string(),
+                    port: 5000,
+                },
+                server: Some(true),
+                wait: Some(false),
+                tls_creds: Some(creds.id.clone()),
+                tls_authz: Some(authz.id.clone()),
+            },
+        ));
+
+        let args = qemu.args().unwrap();
+        assert_eq!(
+            &[
+                "-object",
+                "tls-creds-x509,id=tls0,endpoint=server,dir=/etc/pki/qemu,verify-peer=on",
+                "-object",
+                "authz-simple,id=auth0,identity=CN=client",
+                "-chardev",
+                "socket,id=chr0,host=0.0.0.0,port=5000,server=on,wait=off,tls-creds=tls0,tls-authz=auth0"
+            ],
+            args.as_slice()
+        );
+    }
+
+    #[test]
+    fn test_chardev_unix_socket() {
+        let chardev = Chardev::new(
+            "qga0",
+            ChardevKind::Socket {
+                addr: ChardevAddr::Unix {
+                    path: PathBuf::from("/run/qga.sock"),
+                },
+                server: Some(true),
+                wait: None,
+                tls_creds: None,
+                tls_authz: None,
+            },
+        );
+        assert_eq!(
+            vec!["-chardev", "socket,id=qga0,path=/run/qga.sock,server=on"],
+            chardev.format().unwrap()
+        );
+    }
+}
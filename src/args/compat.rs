@@ -0,0 +1,80 @@
+use crate::args::{QemuArgument, QemuValue};
+
+#[derive(Default, Debug, Copy, Clone, Eq, PartialEq, Hash, QemuArgument)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default, rename_all = "kebab-case")
+)]
+#[qemu(flag = "-compat")]
+pub struct Compat {
+    pub deprecated_input: Option<DeprecatedInput>,
+    pub deprecated_output: Option<DeprecatedOutput>,
+}
+
+/// Policy for deprecated input, i.e. deprecated commands and arguments.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, QemuValue)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum DeprecatedInput {
+    Accept,
+    Reject,
+    Crash,
+}
+
+/// Policy for deprecated output, i.e. deprecated fields in results and events.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, QemuValue)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum DeprecatedOutput {
+    Accept,
+    Hide,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Generic;
+    use crate::Qemu;
+
+    #[test]
+    fn test_compat_deprecated_input() {
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.compat(Compat {
+            deprecated_input: Some(DeprecatedInput::Reject),
+            ..Default::default()
+        });
+
+        let args = qemu.args().unwrap();
+        assert_eq!(&["-compat", "deprecated-input=reject"], args.as_slice());
+    }
+
+    #[test]
+    fn test_compat_both() {
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.compat(Compat {
+            deprecated_input: Some(DeprecatedInput::Crash),
+            deprecated_output: Some(DeprecatedOutput::Hide),
+        });
+
+        let args = qemu.args().unwrap();
+        assert_eq!(
+            &["-compat", "deprecated-input=crash,deprecated-output=hide"],
+            args.as_slice()
+        );
+    }
+
+    #[test]
+    fn test_compat_empty() {
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.compat(Compat::default());
+
+        assert!(qemu.args().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,170 @@
+use crate::args::{
+    required_value, ArgSyntax, FromArg, InvalidValue, Iothread, KeyValues, ObjectId, ParseError,
+    PropValue, Props, QemuArgument,
+};
+use std::ffi::{OsStr, OsString};
+
+/// A device, added via the `-device` option.
+///
+/// This is a generic device for drivers that don't have a dedicated type.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct Device {
+    props: Props,
+}
+
+impl Device {
+    pub fn new(driver: impl ToString) -> Self {
+        let mut props = Props::new();
+        props.push("driver", driver.to_string());
+        Self { props }
+    }
+
+    /// The driver of the device, such as `virtio-net-pci`.
+    pub fn driver(&self) -> Option<&str> {
+        match self.props.get("driver")? {
+            PropValue::String(driver) => Some(driver),
+            _ => None,
+        }
+    }
+
+    /// The `id` property of the device, if it is set to a string.
+    pub fn id(&self) -> Option<&str> {
+        match self.props.get("id")? {
+            PropValue::String(id) => Some(id),
+            _ => None,
+        }
+    }
+
+    /// Sets a property of the device.
+    pub fn prop(mut self, key: impl ToString, value: impl Into<PropValue>) -> Self {
+        self.props.push(key, value);
+        self
+    }
+
+    /// Runs the device in the given I/O thread instead of the main loop,
+    /// for drivers with an `iothread` property such as `virtio-blk-pci`.
+    pub fn iothread(self, iothread: &ObjectId<Iothread>) -> Self {
+        self.prop("iothread", iothread)
+    }
+}
+
+impl QemuArgument for Device {
+    fn format(&self) -> Result<Vec<OsString>, InvalidValue> {
+        self.format_with(ArgSyntax::KeyValue)
+    }
+
+    fn format_with(&self, syntax: ArgSyntax) -> Result<Vec<OsString>, InvalidValue> {
+        Ok(vec![
+            "-device".into(),
+            self.props.render(syntax, Some("driver"))?,
+        ])
+    }
+}
+
+/// Parses a device with all its properties as strings, since their
+/// types depend on the driver.
+impl FromArg for Device {
+    fn from_arg(value: Option<&OsStr>) -> Result<Self, ParseError> {
+        let value = required_value("-device", value)?;
+        let mut props = KeyValues::parse("-device", value, Some("driver"))?;
+        let mut device = Self::new(props.required::<String>("driver")?);
+        for (key, value) in props.into_entries() {
+            device = match value.into_string() {
+                Ok(value) => device.prop(key, value),
+                Err(value) => device.prop(key, value),
+            };
+        }
+        Ok(device)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Generic;
+    use crate::Qemu;
+
+    fn device() -> Device {
+        Device::new("virtio-net-pci")
+            .prop("netdev", "net0")
+            .prop("mac", "52:54:00:12:34:56")
+            .prop("disable-legacy", true)
+    }
+
+    #[test]
+    fn test_device_key_value() {
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.device(device());
+
+        let args = qemu.args().unwrap();
+        assert_eq!(
+            &[
+                "-device",
+                "virtio-net-pci,netdev=net0,mac=52:54:00:12:34:56,disable-legacy=on"
+            ],
+            args.as_slice()
+        );
+    }
+
+    #[test]
+    fn test_device_json() {
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.arg_syntax(ArgSyntax::Json);
+        qemu.device(device());
+
+        let args = qemu.args().unwrap();
+        assert_eq!(
+            &[
+                "-device",
+                r#"{"driver":"virtio-net-pci","netdev":"net0","mac":"52:54:00:12:34:56","disable-legacy":true}"#
+            ],
+            args.as_slice()
+        );
+    }
+
+    #[test]
+    fn test_device_iothread() {
+        let iothread = Iothread::new("io0");
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.device(
+            Device::new("virtio-blk-pci")
+                .prop("drive", "disk0")
+                .iothread(&iothread.id),
+        );
+        qemu.object(iothread);
+
+        let args = qemu.args().unwrap();
+        assert_eq!(
+            &[
+                "-device",
+                "virtio-blk-pci,drive=disk0,iothread=io0",
+                "-object",
+                "iothread,id=io0"
+            ],
+            args.as_slice()
+        );
+    }
+
+    #[test]
+    fn test_device_comma_escaping() {
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.device(Device::new("virtio-serial-pci").prop("id", "a,b"));
+        qemu.arg_syntax(ArgSyntax::KeyValue);
+        let args = qemu.args().unwrap();
+        assert_eq!(&["-device", "virtio-serial-pci,id=a,,b"], args.as_slice());
+
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.device(Device::new("virtio-serial-pci").prop("id", "a,b"));
+        qemu.arg_syntax(ArgSyntax::Json);
+        let args = qemu.args().unwrap();
+        assert_eq!(
+            &["-device", r#"{"driver":"virtio-serial-pci","id":"a,b"}"#],
+            args.as_slice()
+        );
+    }
+}
@@ -1,44 +1,43 @@
-use crate::args::QemuArgument;
+use crate::args::{ObjectId, QemuArgument, QemuValue, Secret, ThrottleGroup};
 use std::path::PathBuf;
 
-#[derive(Default, Debug, Clone, Eq, PartialEq, Hash)]
+#[derive(Default, Debug, Clone, Eq, PartialEq, Hash, QemuArgument)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default, rename_all = "kebab-case")
+)]
+#[qemu(flag = "-drive")]
 pub struct Drive {
     pub file: PathBuf,
     pub format: Option<Format>,
     pub cache: Option<Cache>,
     pub snapshot: Option<Snapshot>,
+    /// The secret that holds the passphrase of a [`Format::Luks`] image.
+    pub key_secret: Option<ObjectId<Secret>>,
+    #[qemu(rename = "throttling.group")]
+    pub throttle_group: Option<ObjectId<ThrottleGroup>>,
 }
 
-impl QemuArgument for Drive {
-    fn format(&self) -> Vec<String> {
-        let mut arg = format!("file={}", self.file.display());
-        if let Some(format) = self.format {
-            arg.push_str(&format!(",format={}", format.name()));
-        }
-        if let Some(cache) = self.cache {
-            arg.push_str(&format!(",cache={}", cache.name()));
-        }
-        if let Some(snapshot) = self.snapshot {
-            arg.push_str(&format!(",snapshot={}", snapshot.name()));
-        }
-        vec!["-drive".to_string(), arg]
-    }
-}
-
-#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[derive(Default, Debug, Copy, Clone, Eq, PartialEq, Hash, QemuValue)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
 pub enum Format {
+    #[default]
     Raw,
+    Qcow2,
+    Luks,
 }
 
-impl Format {
-    fn name(&self) -> &'static str {
-        match self {
-            Self::Raw => "raw",
-        }
-    }
-}
-
-#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, QemuValue)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
 pub enum Cache {
     Writethrough,
     Writeback,
@@ -47,29 +46,120 @@ pub enum Cache {
     Unsafe,
 }
 
-impl Cache {
-    fn name(&self) -> &'static str {
-        match self {
-            Self::Writethrough => "writethrough",
-            Self::Writeback => "writeback",
-            Self::None => "none",
-            Self::Directsync => "directsync",
-            Self::Unsafe => "unsafe",
-        }
-    }
-}
-
-#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, QemuValue)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
 pub enum Snapshot {
     On,
     Off,
 }
 
-impl Snapshot {
-    fn name(&self) -> &'static str {
-        match self {
-            Self::On => "on",
-            Self::Off => "off",
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::args::{SecretSource, ThrottleLimits};
+    use crate::Generic;
+    use crate::Qemu;
+
+    #[test]
+    fn test_drive() {
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.drive(Drive {
+            file: PathBuf::from("disk.img"),
+            format: Some(Format::Raw),
+            cache: Some(Cache::Writeback),
+            snapshot: Some(Snapshot::On),
+            ..Default::default()
+        });
+
+        let args = qemu.args().unwrap();
+        assert_eq!(
+            &[
+                "-drive",
+                "file=disk.img,format=raw,cache=writeback,snapshot=on"
+            ],
+            args.as_slice()
+        );
+    }
+
+    #[test]
+    fn test_luks_drive() {
+        let secret = Secret::new("sec0", SecretSource::File(PathBuf::from("pass")));
+        let group = ThrottleGroup {
+            id: ObjectId::new("limits0"),
+            limits: ThrottleLimits {
+                iops_total: Some(10),
+                ..Default::default()
+            },
+        };
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.drive(Drive {
+            file: PathBuf::from("disk.luks"),
+            format: Some(Format::Luks),
+            key_secret: Some(secret.id.clone()),
+            throttle_group: Some(group.id.clone()),
+            ..Default::default()
+        });
+
+        let args = qemu.args().unwrap();
+        assert_eq!(
+            &[
+                "-drive",
+                "file=disk.luks,format=luks,key-secret=sec0,throttling.group=limits0"
+            ],
+            args.as_slice()
+        );
+    }
+
+    #[test]
+    fn test_drive_path_with_comma() {
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.drive(Drive {
+            file: PathBuf::from("a,format=qcow2.img"),
+            format: Some(Format::Raw),
+            ..Default::default()
+        });
+
+        let args = qemu.args().unwrap();
+        assert_eq!(
+            &["-drive", "file=a,,format=qcow2.img,format=raw"],
+            args.as_slice()
+        );
+    }
+
+    #[test]
+    fn test_drive_path_with_nul() {
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.drive(Drive {
+            file: PathBuf::from("disk\0.img"),
+            ..Default::default()
+        });
+
+        assert!(qemu.args().is_err());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_drive_non_utf8_path() {
+        use std::ffi::{OsStr, OsString};
+        use std::os::unix::ffi::{OsStrExt, OsStringExt};
+
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.drive(Drive {
+            file: PathBuf::from(OsStr::from_bytes(b"disk-\xff,1.img")),
+            ..Default::default()
+        });
+
+        let args = qemu.args().unwrap();
+        assert_eq!(
+            &[
+                OsString::from("-drive"),
+                OsString::from_vec(b"file=disk-\xff,,1.img".to_vec())
+            ],
+            args.as_slice()
+        );
     }
 }
@@ -0,0 +1,181 @@
+use crate::args::{
+    unknown_qom_type, Chardev, KeyValues, Netdev, ObjectId, ParseError, Props, QemuValue,
+    ToPropValue,
+};
+use std::path::PathBuf;
+
+/// A network filter that is attached to a netdev.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub struct Filter {
+    pub id: ObjectId<Filter>,
+    pub kind: FilterKind,
+    /// The netdev this filter is attached to.
+    pub netdev: ObjectId<Netdev>,
+    pub queue: Option<FilterQueue>,
+}
+
+impl Filter {
+    pub(crate) fn props(&self) -> Props {
+        let mut props = Props::new();
+        props
+            .push("qom-type", self.kind.as_str())
+            .push("id", &self.id)
+            .push("netdev", &self.netdev);
+        match &self.kind {
+            FilterKind::Buffer { interval } => {
+                props.push("interval", *interval);
+            }
+            FilterKind::Mirror { outdev } => {
+                props.push("outdev", outdev);
+            }
+            FilterKind::Redirector { indev, outdev } => {
+                props
+                    .push_opt("indev", indev.as_ref())
+                    .push_opt("outdev", outdev.as_ref());
+            }
+            FilterKind::Dump { file } => {
+                props.push("file", file);
+            }
+            FilterKind::Rewriter | FilterKind::Replay => {}
+        }
+        props.push_opt("queue", self.queue.map(|q| q.to_prop_value()));
+        props
+    }
+
+    /// Parses the properties of [`Self::props`] after the `qom-type`.
+    pub(crate) fn from_props(qom_type: &str, props: &mut KeyValues) -> Result<Self, ParseError> {
+        let id = props.required("id")?;
+        let netdev = props.required("netdev")?;
+        let kind = match qom_type {
+            "filter-buffer" => FilterKind::Buffer {
+                interval: props.required("interval")?,
+            },
+            "filter-mirror" => FilterKind::Mirror {
+                outdev: props.required("outdev")?,
+            },
+            "filter-redirector" => FilterKind::Redirector {
+                indev: props.take("indev")?,
+                outdev: props.take("outdev")?,
+            },
+            "filter-dump" => FilterKind::Dump {
+                file: props.required("file")?,
+            },
+            "filter-rewriter" => FilterKind::Rewriter,
+            "filter-replay" => FilterKind::Replay,
+            _ => return Err(unknown_qom_type(qom_type)),
+        };
+        Ok(Self {
+            id,
+            kind,
+            netdev,
+            queue: props.take("queue")?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum FilterKind {
+    /// `filter-buffer`, releases packets every `interval` microseconds.
+    Buffer {
+        interval: u64,
+    },
+    /// `filter-mirror`, mirrors packets to the chardev `outdev`.
+    Mirror {
+        outdev: ObjectId<Chardev>,
+    },
+    /// `filter-redirector`, redirects packets from and to chardevs.
+    Redirector {
+        indev: Option<ObjectId<Chardev>>,
+        outdev: Option<ObjectId<Chardev>>,
+    },
+    /// `filter-dump`, dumps packets into a pcap file.
+    Dump {
+        file: PathBuf,
+    },
+    Rewriter,
+    Replay,
+}
+
+impl FilterKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Buffer { .. } => "filter-buffer",
+            Self::Mirror { .. } => "filter-mirror",
+            Self::Redirector { .. } => "filter-redirector",
+            Self::Dump { .. } => "filter-dump",
+            Self::Rewriter => "filter-rewriter",
+            Self::Replay => "filter-replay",
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, QemuValue)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum FilterQueue {
+    All,
+    Rx,
+    Tx,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Generic;
+    use crate::Qemu;
+
+    #[test]
+    fn test_filter_dump() {
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.object(Filter {
+            id: ObjectId::new("f0"),
+            kind: FilterKind::Dump {
+                file: PathBuf::from("net.pcap"),
+            },
+            netdev: ObjectId::new("net0"),
+            queue: Some(FilterQueue::Rx),
+        });
+
+        let args = qemu.args().unwrap();
+        assert_eq!(
+            &[
+                "-object",
+                "filter-dump,id=f0,netdev=net0,file=net.pcap,queue=rx"
+            ],
+            args.as_slice()
+        );
+    }
+
+    #[test]
+    fn test_filter_redirector() {
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.object(Filter {
+            id: ObjectId::new("f0"),
+            kind: FilterKind::Redirector {
+                indev: None,
+                outdev: Some(ObjectId::new("chr0")),
+            },
+            netdev: ObjectId::new("net0"),
+            queue: None,
+        });
+
+        let args = qemu.args().unwrap();
+        assert_eq!(
+            &["-object", "filter-redirector,id=f0,netdev=net0,outdev=chr0"],
+            args.as_slice()
+        );
+    }
+}
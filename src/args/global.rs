@@ -0,0 +1,124 @@
+use crate::args::{
+    os_arg, required_value, ArgSyntax, FromArg, InvalidValue, KeyValues, ParseError, Props,
+    QemuArgument,
+};
+use std::ffi::{OsStr, OsString};
+
+#[derive(Default, Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default, rename_all = "kebab-case")
+)]
+pub struct Global {
+    pub driver: String,
+    pub property: String,
+    pub value: String,
+}
+
+impl Global {
+    pub fn new(driver: impl ToString, property: impl ToString, value: impl ToString) -> Self {
+        Self {
+            driver: driver.to_string(),
+            property: property.to_string(),
+            value: value.to_string(),
+        }
+    }
+}
+
+impl QemuArgument for Global {
+    fn format(&self) -> Result<Vec<OsString>, InvalidValue> {
+        // The short form `driver.property=value` is split at the first '.'
+        // and takes the value verbatim, so it can't be used for drivers
+        // such as `cfi.pflash01`. The long form is escaped like any other option.
+        let short_form = !self.driver.contains(['.', '='])
+            && !self.property.contains('=')
+            && !self.driver.is_empty();
+        let arg = if short_form {
+            os_arg(format!("{}.{}={}", self.driver, self.property, self.value))?
+        } else {
+            let mut props = Props::new();
+            props
+                .push("driver", &self.driver)
+                .push("property", &self.property)
+                .push("value", &self.value);
+            props.render(ArgSyntax::KeyValue, None)?
+        };
+        Ok(vec!["-global".into(), arg])
+    }
+}
+
+impl FromArg for Global {
+    fn from_arg(value: Option<&OsStr>) -> Result<Self, ParseError> {
+        let value = required_value("-global", value)?;
+        let short = value.to_str().and_then(|value| {
+            let (driver, rest) = value.split_once('.')?;
+            let (property, value) = rest.split_once('=')?;
+            (!driver.is_empty() && !driver.contains('='))
+                .then(|| Global::new(driver, property, value))
+        });
+        if let Some(global) = short {
+            return Ok(global);
+        }
+        let mut kv = KeyValues::parse("-global", value, None)?;
+        let global = Self {
+            driver: kv.required("driver")?,
+            property: kv.required("property")?,
+            value: kv.required("value")?,
+        };
+        kv.finish()?;
+        Ok(global)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Generic;
+    use crate::Qemu;
+
+    #[test]
+    fn test_global() {
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.global(Global::new("isa-debugcon", "iobase", "0x402"));
+
+        let args = qemu.args().unwrap();
+        assert_eq!(&["-global", "isa-debugcon.iobase=0x402"], args.as_slice());
+    }
+
+    #[test]
+    fn test_global_repeatable() {
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.global(Global::new("isa-debugcon", "iobase", "0x402"));
+        qemu.global(Global::new("kvm-pit", "lost_tick_policy", "discard"));
+
+        let args = qemu.args().unwrap();
+        assert_eq!(
+            &[
+                "-global",
+                "isa-debugcon.iobase=0x402",
+                "-global",
+                "kvm-pit.lost_tick_policy=discard"
+            ],
+            args.as_slice()
+        );
+    }
+
+    #[test]
+    fn test_global_dotted_driver() {
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.global(Global::new("cfi.pflash01", "secure", "on"));
+        qemu.global(Global::new("isa-debugcon", "chardev", "a,b"));
+
+        let args = qemu.args().unwrap();
+        assert_eq!(
+            &[
+                "-global",
+                "driver=cfi.pflash01,property=secure,value=on",
+                "-global",
+                "isa-debugcon.chardev=a,b"
+            ],
+            args.as_slice()
+        );
+    }
+}
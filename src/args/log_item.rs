@@ -1,6 +1,12 @@
-use crate::args::QemuArgument;
+use crate::args::{check_arg, required_value, FromArg, InvalidValue, ParseError, QemuArgument};
+use std::ffi::{OsStr, OsString};
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
 pub enum LogItem {
     OutAsm,
     InAsm,
@@ -26,7 +32,7 @@ pub enum LogItem {
 }
 
 impl LogItem {
-    fn format(&self) -> String {
+    pub(crate) fn format(&self) -> String {
         match self {
             LogItem::OutAsm => "out_asm".to_string(),
             LogItem::InAsm => "in_asm".to_string(),
@@ -51,9 +57,40 @@ impl LogItem {
             LogItem::Help => "help".to_string(),
         }
     }
+
+    pub(crate) fn parse(item: &str) -> Option<Self> {
+        Some(match item {
+            "out_asm" => LogItem::OutAsm,
+            "in_asm" => LogItem::InAsm,
+            "op" => LogItem::Op,
+            "op_opt" => LogItem::OpOpt,
+            "op_ind" => LogItem::OpInd,
+            "int" => LogItem::Int,
+            "exec" => LogItem::Exec,
+            "cpu" => LogItem::Cpu,
+            "fpu" => LogItem::Fpu,
+            "mmu" => LogItem::Mmu,
+            "pcall" => LogItem::Pcall,
+            "cpu_reset" => LogItem::CpuReset,
+            "unimp" => LogItem::Unimp,
+            "guest_errors" => LogItem::GuestErrors,
+            "page" => LogItem::Page,
+            "nochain" => LogItem::Nochain,
+            "plugin" => LogItem::Plugin,
+            "strace" => LogItem::Strace,
+            "tid" => LogItem::Tid,
+            "help" => LogItem::Help,
+            _ => LogItem::Trace(item.strip_prefix("trace:")?.to_string()),
+        })
+    }
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
 pub(crate) struct LogItems {
     pub items: Vec<LogItem>,
 }
@@ -70,18 +107,39 @@ where
 }
 
 impl QemuArgument for LogItems {
-    fn format(&self) -> Vec<String> {
-        let mut arg = String::new();
+    fn format(&self) -> Result<Vec<OsString>, InvalidValue> {
+        let items = self
+            .items
+            .iter()
+            .map(|item| {
+                let item = item.format();
+                // QEMU splits the items at commas without any escaping
+                if item.contains(',') {
+                    return Err(InvalidValue::new(item, "log items must not contain ','"));
+                }
+                check_arg(&item)?;
+                Ok(item)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
 
-        if let Some(first) = self.items.first() {
-            arg.push_str(first.format().as_str())
-        }
-        self.items.iter().skip(1).for_each(|i| {
-            arg.push(',');
-            arg.push_str(i.format().as_str());
-        });
+        Ok(vec!["-d".into(), items.join(",").into()])
+    }
+}
 
-        vec!["-d".to_string(), arg]
+impl FromArg for LogItems {
+    fn from_arg(value: Option<&OsStr>) -> Result<Self, ParseError> {
+        let value = required_value("-d", value)?;
+        let value = value
+            .to_str()
+            .ok_or_else(|| ParseError::invalid("-d", "non-UTF-8 value"))?;
+        let items = value
+            .split(',')
+            .map(|item| {
+                LogItem::parse(item)
+                    .ok_or_else(|| ParseError::unsupported("-d", format!("unknown item {}", item)))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { items })
     }
 }
 
@@ -97,4 +155,10 @@ mod tests {
         assert!(inner.contains(&LogItem::Page));
         assert!(inner.contains(&LogItem::GuestErrors));
     }
+
+    #[test]
+    fn test_trace_with_comma() {
+        let items = LogItems::from([LogItem::Trace("virtio_*,cpu".to_string())]);
+        assert!(items.format().is_err());
+    }
 }
@@ -0,0 +1,58 @@
+use crate::args::{MemoryBackend, ObjectId, QemuArgument};
+
+#[derive(Default, Debug, Clone, Eq, PartialEq, Hash, QemuArgument)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default, rename_all = "kebab-case")
+)]
+#[qemu(flag = "-machine", implied = "type")]
+pub struct Machine {
+    /// The machine type, for example `q35` or `virt`. If this is `None`,
+    /// QEMU uses the default machine of the system.
+    #[qemu(rename = "type")]
+    pub kind: Option<String>,
+    /// The memory backend that is used as guest RAM.
+    pub memory_backend: Option<ObjectId<MemoryBackend>>,
+    /// Enables the ACPI Heterogeneous Memory Attribute Table, which is
+    /// required for HMAT entries in a [`NumaTopology`](crate::args::NumaTopology).
+    pub hmat: Option<bool>,
+    /// Enables System Management Mode, which is required by secure
+    /// boot builds of OVMF.
+    pub smm: Option<bool>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Generic;
+    use crate::Qemu;
+
+    #[test]
+    fn test_machine() {
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.machine(Machine {
+            kind: Some("q35".to_string()),
+            memory_backend: Some(ObjectId::new("mem0")),
+            hmat: Some(true),
+            smm: Some(false),
+        });
+
+        let args = qemu.args().unwrap();
+        assert_eq!(
+            &["-machine", "q35,memory-backend=mem0,hmat=on,smm=off"],
+            args.as_slice()
+        );
+    }
+
+    #[test]
+    fn test_machine_kind_with_equals() {
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.machine(Machine {
+            kind: Some("accel=kvm".to_string()),
+            ..Default::default()
+        });
+
+        assert!(qemu.args().is_err());
+    }
+}
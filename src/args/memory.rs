@@ -0,0 +1,336 @@
+use crate::args::{
+    unknown_qom_type, KeyValues, ObjectId, ParseError, Props, QemuArgument, QemuValue, ToPropValue,
+};
+use std::path::PathBuf;
+
+/// A memory backend object.
+///
+/// Backends can be used as guest RAM via [`Machine::memory_backend`](crate::args::Machine::memory_backend)
+/// or as memory of a NUMA node.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub struct MemoryBackend {
+    pub id: ObjectId<MemoryBackend>,
+    pub kind: MemoryBackendKind,
+    /// The size in bytes.
+    pub size: u64,
+    pub share: Option<bool>,
+    pub prealloc: Option<bool>,
+    pub host_nodes: Vec<u32>,
+    pub policy: Option<HostMemPolicy>,
+}
+
+impl MemoryBackend {
+    pub fn new(id: impl ToString, kind: MemoryBackendKind, size: u64) -> Self {
+        Self {
+            id: ObjectId::new(id),
+            kind,
+            size,
+            share: None,
+            prealloc: None,
+            host_nodes: Vec::new(),
+            policy: None,
+        }
+    }
+
+    /// Whether the memory of this backend can be shared with other
+    /// processes, which is required by vhost-user devices.
+    ///
+    /// `memory-backend-memfd` is shared by default, the other backends are not.
+    pub fn is_shared(&self) -> bool {
+        self.share
+            .unwrap_or(matches!(self.kind, MemoryBackendKind::Memfd { .. }))
+    }
+}
+
+impl MemoryBackend {
+    pub(crate) fn props(&self) -> Props {
+        let mut props = Props::new();
+        props
+            .push("qom-type", self.kind.as_str())
+            .push("id", &self.id)
+            .push("size", self.size);
+        match &self.kind {
+            MemoryBackendKind::Ram => {}
+            MemoryBackendKind::File { mem_path } => {
+                props.push("mem-path", mem_path);
+            }
+            MemoryBackendKind::Memfd {
+                hugetlb,
+                hugetlbsize,
+            } => {
+                props
+                    .push_opt("hugetlb", *hugetlb)
+                    .push_opt("hugetlbsize", *hugetlbsize);
+            }
+        }
+        props
+            .push_opt("share", self.share)
+            .push_opt("prealloc", self.prealloc);
+        if !self.host_nodes.is_empty() {
+            props.push("host-nodes", self.host_nodes.clone());
+        }
+        props.push_opt("policy", self.policy.map(|p| p.to_prop_value()));
+        props
+    }
+
+    /// Parses the properties of [`Self::props`] after the `qom-type`.
+    pub(crate) fn from_props(qom_type: &str, props: &mut KeyValues) -> Result<Self, ParseError> {
+        let id = props.required("id")?;
+        let size = props.required("size")?;
+        let kind = match qom_type {
+            "memory-backend-ram" => MemoryBackendKind::Ram,
+            "memory-backend-file" => MemoryBackendKind::File {
+                mem_path: props.required("mem-path")?,
+            },
+            "memory-backend-memfd" => MemoryBackendKind::Memfd {
+                hugetlb: props.take("hugetlb")?,
+                hugetlbsize: props.take("hugetlbsize")?,
+            },
+            _ => return Err(unknown_qom_type(qom_type)),
+        };
+        Ok(Self {
+            id,
+            kind,
+            size,
+            share: props.take("share")?,
+            prealloc: props.take("prealloc")?,
+            host_nodes: props.take_all("host-nodes")?,
+            policy: props.take("policy")?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum MemoryBackendKind {
+    /// `memory-backend-ram`, anonymous memory.
+    Ram,
+    /// `memory-backend-file`, memory backed by a file, for example
+    /// on a hugetlbfs mount.
+    File { mem_path: PathBuf },
+    /// `memory-backend-memfd`, anonymous memory that can be shared.
+    Memfd {
+        hugetlb: Option<bool>,
+        /// The huge page size in bytes.
+        hugetlbsize: Option<u64>,
+    },
+}
+
+impl MemoryBackendKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Ram => "memory-backend-ram",
+            Self::File { .. } => "memory-backend-file",
+            Self::Memfd { .. } => "memory-backend-memfd",
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, QemuValue)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum HostMemPolicy {
+    Default,
+    Preferred,
+    Bind,
+    Interleave,
+}
+
+#[derive(Default, Debug, Clone, Eq, PartialEq, Hash, QemuArgument)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[qemu(flag = "-mem-path")]
+pub struct MemPath(pub PathBuf);
+
+#[derive(Default, Debug, Clone, Eq, PartialEq, Hash, QemuArgument)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[qemu(flag = "-mem-prealloc")]
+pub struct MemPrealloc;
+
+#[derive(Default, Debug, Copy, Clone, Eq, PartialEq, Hash, QemuArgument)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default, rename_all = "kebab-case")
+)]
+#[qemu(flag = "-overcommit")]
+pub struct Overcommit {
+    pub mem_lock: Option<bool>,
+    pub cpu_pm: Option<bool>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::args::{Machine, NumaNode, NumaTopology};
+    use crate::Generic;
+    use crate::Qemu;
+
+    #[test]
+    fn test_memory_backend_ram() {
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.memory_backend(MemoryBackend::new("mem0", MemoryBackendKind::Ram, 1 << 30));
+
+        let args = qemu.args().unwrap();
+        assert_eq!(
+            &["-object", "memory-backend-ram,id=mem0,size=1073741824"],
+            args.as_slice()
+        );
+    }
+
+    #[test]
+    fn test_memory_backend_file() {
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.memory_backend(MemoryBackend {
+            share: Some(true),
+            prealloc: Some(true),
+            host_nodes: vec![0, 2],
+            policy: Some(HostMemPolicy::Bind),
+            ..MemoryBackend::new(
+                "mem0",
+                MemoryBackendKind::File {
+                    mem_path: PathBuf::from("/dev/hugepages"),
+                },
+                1 << 30,
+            )
+        });
+
+        let args = qemu.args().unwrap();
+        assert_eq!(
+            &[
+                "-object",
+                "memory-backend-file,id=mem0,size=1073741824,mem-path=/dev/hugepages,share=on,prealloc=on,host-nodes=0,host-nodes=2,policy=bind"
+            ],
+            args.as_slice()
+        );
+    }
+
+    #[test]
+    fn test_memory_backend_memfd() {
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.memory_backend(MemoryBackend::new(
+            "mem0",
+            MemoryBackendKind::Memfd {
+                hugetlb: Some(true),
+                hugetlbsize: Some(2 << 20),
+            },
+            1 << 30,
+        ));
+
+        let args = qemu.args().unwrap();
+        assert_eq!(
+            &[
+                "-object",
+                "memory-backend-memfd,id=mem0,size=1073741824,hugetlb=on,hugetlbsize=2097152"
+            ],
+            args.as_slice()
+        );
+    }
+
+    #[test]
+    fn test_shared_memory() {
+        let mut qemu = Qemu::<Generic>::new();
+        assert!(!qemu.has_shared_memory());
+
+        qemu.memory_backend(MemoryBackend::new("mem0", MemoryBackendKind::Ram, 1 << 30));
+        qemu.machine(Machine {
+            memory_backend: Some(ObjectId::new("mem0")),
+            ..Default::default()
+        });
+        assert!(!qemu.has_shared_memory());
+
+        qemu.memory_backend(MemoryBackend::new(
+            "mem1",
+            MemoryBackendKind::Memfd {
+                hugetlb: None,
+                hugetlbsize: None,
+            },
+            1 << 30,
+        ));
+        qemu.machine(Machine {
+            memory_backend: Some(ObjectId::new("mem1")),
+            ..Default::default()
+        });
+        assert!(qemu.has_shared_memory());
+    }
+
+    #[test]
+    fn test_shared_memory_numa() {
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.memory_backend(MemoryBackend::new("mem0", MemoryBackendKind::Ram, 1 << 30));
+        qemu.memory_backend(MemoryBackend {
+            share: Some(true),
+            ..MemoryBackend::new("mem1", MemoryBackendKind::Ram, 1 << 30)
+        });
+        let mut numa = NumaTopology::new();
+        numa.node(NumaNode {
+            memdev: Some(ObjectId::new("mem1")),
+            ..NumaNode::new(0)
+        });
+        qemu.numa(numa.clone());
+        assert!(qemu.has_shared_memory());
+
+        numa.node(NumaNode {
+            memdev: Some(ObjectId::new("mem0")),
+            ..NumaNode::new(1)
+        });
+        qemu.numa(numa);
+        assert!(!qemu.has_shared_memory());
+    }
+
+    #[test]
+    fn test_shared_memory_mem_path() {
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.mem_path(PathBuf::from("/dev/hugepages"));
+        assert!(!qemu.has_shared_memory());
+
+        qemu.memory_backend(MemoryBackend::new("mem0", MemoryBackendKind::Ram, 1 << 30));
+        qemu.machine(Machine {
+            memory_backend: Some(ObjectId::new("mem0")),
+            ..Default::default()
+        });
+        assert!(!qemu.has_shared_memory());
+    }
+
+    #[test]
+    fn test_mem_path_prealloc_overcommit() {
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.mem_path(PathBuf::from("/dev/hugepages"));
+        qemu.mem_prealloc();
+        qemu.overcommit(Overcommit {
+            mem_lock: Some(true),
+            cpu_pm: Some(false),
+        });
+
+        let args = qemu.args().unwrap();
+        assert_eq!(
+            &[
+                "-mem-path",
+                "/dev/hugepages",
+                "-mem-prealloc",
+                "-overcommit",
+                "mem-lock=on,cpu-pm=off"
+            ],
+            args.as_slice()
+        );
+    }
+
+    #[test]
+    fn test_overcommit_empty() {
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.overcommit(Overcommit::default());
+
+        assert!(qemu.args().unwrap().is_empty());
+    }
+}
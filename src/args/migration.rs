@@ -0,0 +1,123 @@
+use crate::args::{
+    leading_value, os_arg, AuthzSimple, FromArg, Global, InvalidValue, ObjectId, QemuArgument,
+    TlsCreds,
+};
+use std::ffi::OsString;
+
+/// Waits for an incoming migration, configured via the `-incoming` option.
+///
+/// QEMU has no command line option for the TLS parameters of a migration,
+/// so they are set as properties of the migration object with `-global`
+/// options, which come before the `-incoming` option.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub struct Incoming {
+    /// The URI to listen on, such as `tcp:0:4444`, `unix:/run/migrate.sock`,
+    /// or `defer` to wait for the `migrate-incoming` QMP command.
+    pub uri: String,
+    /// Encrypts the migration stream with the given credentials.
+    pub tls_creds: Option<ObjectId<TlsCreds>>,
+    /// Restricts the sources to the authorized x509 identities.
+    pub tls_authz: Option<ObjectId<AuthzSimple>>,
+}
+
+impl Incoming {
+    pub fn new(uri: impl ToString) -> Self {
+        Self {
+            uri: uri.to_string(),
+            tls_creds: None,
+            tls_authz: None,
+        }
+    }
+}
+
+impl QemuArgument for Incoming {
+    fn format(&self) -> Result<Vec<OsString>, InvalidValue> {
+        let mut args = Vec::new();
+        let params = [
+            ("tls-creds", self.tls_creds.as_ref().map(ObjectId::as_str)),
+            ("tls-authz", self.tls_authz.as_ref().map(ObjectId::as_str)),
+        ];
+        for (property, id) in params {
+            if let Some(id) = id {
+                args.extend(Global::new("migration", property, id).format()?);
+            }
+        }
+        args.extend(["-incoming".into(), os_arg(&self.uri)?]);
+        Ok(args)
+    }
+}
+
+impl Incoming {
+    /// Parses the options of [`Self::format`] at the start of `args`,
+    /// and returns the incoming migration with the number of arguments
+    /// it spans.
+    pub(crate) fn parse_leading(args: &[OsString]) -> Option<(Self, usize)> {
+        let mut tls_creds = None;
+        let mut tls_authz = None;
+        let mut skip = 0;
+        while let Some(global) = leading_value(&args[skip..], "-global") {
+            let global = Global::from_arg(Some(global)).ok()?;
+            match (global.driver.as_str(), global.property.as_str()) {
+                ("migration", "tls-creds") if tls_creds.is_none() && tls_authz.is_none() => {
+                    tls_creds = Some(ObjectId::new(global.value));
+                }
+                ("migration", "tls-authz") if tls_authz.is_none() => {
+                    tls_authz = Some(ObjectId::new(global.value));
+                }
+                _ => return None,
+            }
+            skip += 2;
+        }
+        let uri = leading_value(&args[skip..], "-incoming")?.to_str()?;
+        let incoming = Self {
+            uri: uri.to_string(),
+            tls_creds,
+            tls_authz,
+        };
+        Some((incoming, skip + 2))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Generic;
+    use crate::Qemu;
+
+    #[test]
+    fn test_incoming() {
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.incoming(Incoming::new("defer"));
+
+        let args = qemu.args().unwrap();
+        assert_eq!(&["-incoming", "defer"], args.as_slice());
+    }
+
+    #[test]
+    fn test_incoming_tls() {
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.incoming(Incoming {
+            tls_creds: Some(ObjectId::new("tls0")),
+            tls_authz: Some(ObjectId::new("auth0")),
+            ..Incoming::new("tcp:0:4444")
+        });
+
+        let args = qemu.args().unwrap();
+        assert_eq!(
+            &[
+                "-global",
+                "migration.tls-creds=tls0",
+                "-global",
+                "migration.tls-authz=auth0",
+                "-incoming",
+                "tcp:0:4444"
+            ],
+            args.as_slice()
+        );
+    }
+}
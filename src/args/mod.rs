@@ -1,13 +1,167 @@
 mod accel;
+mod action;
+mod blockdev;
+mod chardev;
+mod compat;
+mod device;
 mod drive;
+mod filter;
+mod global;
 mod log_item;
+mod machine;
+mod memory;
+mod migration;
+mod netdev;
+mod numa;
+mod object;
+mod parse;
+mod plugin;
+mod props;
+mod rng;
+mod secret;
 mod simple;
+mod smp;
+mod throttle;
+mod tls;
+mod tpm;
+mod uefi;
+mod vnc;
 
 pub use accel::*;
+pub use action::*;
+pub use blockdev::*;
+pub use chardev::*;
+pub use compat::*;
+pub use device::*;
 pub use drive::*;
+pub use filter::*;
+pub use global::*;
 pub use log_item::*;
+pub use machine::*;
+pub use memory::*;
+pub use migration::*;
+pub use netdev::*;
+pub use numa::*;
+pub use object::*;
+pub use parse::*;
+pub use plugin::*;
+pub use props::*;
+pub use rng::*;
+pub use secret::*;
 pub(crate) use simple::*;
+pub use smp::*;
+pub use throttle::*;
+pub use tls::*;
+pub use tpm::*;
+pub use uefi::*;
+pub use vnc::*;
+
+pub use qemu_api_derive::{QemuArgument, QemuValue};
+
+use std::ffi::OsString;
+use std::fmt::{Display, Formatter};
 
 pub trait QemuArgument {
-    fn format(&self) -> Vec<String>;
+    /// Formats the argument. Fails if a value can't be represented
+    /// on the command line.
+    fn format(&self) -> Result<Vec<OsString>, InvalidValue>;
+
+    /// Formats the argument in the given syntax. Only structured options
+    /// support [`ArgSyntax::Json`], all others ignore the syntax.
+    fn format_with(&self, syntax: ArgSyntax) -> Result<Vec<OsString>, InvalidValue> {
+        let _ = syntax;
+        self.format()
+    }
+}
+
+/// The arguments of a QEMU command line.
+///
+/// The arguments are passed to QEMU byte-exact. The [`Display`]
+/// implementation renders a shell-quoted form for logging, which
+/// replaces invalid UTF-8 lossily.
+#[derive(Default, Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct QemuArgs(
+    #[cfg_attr(feature = "serde", serde(with = "crate::profile::os_strings"))] Vec<OsString>,
+);
+
+impl QemuArgs {
+    pub fn as_slice(&self) -> &[OsString] {
+        &self.0
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, OsString> {
+        self.0.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl From<Vec<OsString>> for QemuArgs {
+    fn from(args: Vec<OsString>) -> Self {
+        Self(args)
+    }
+}
+
+impl From<QemuArgs> for Vec<OsString> {
+    fn from(args: QemuArgs) -> Self {
+        args.0
+    }
+}
+
+impl IntoIterator for QemuArgs {
+    type Item = OsString;
+    type IntoIter = std::vec::IntoIter<OsString>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a QemuArgs {
+    type Item = &'a OsString;
+    type IntoIter = std::slice::Iter<'a, OsString>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+impl Display for QemuArgs {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (i, arg) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            let arg = arg.to_string_lossy();
+            let plain = !arg.is_empty()
+                && arg
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "-_=,.:/@%+".contains(c));
+            if plain {
+                f.write_str(&arg)?;
+            } else {
+                write!(f, "'{}'", arg.replace('\'', r"'\''"))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn on_off(value: bool) -> &'static str {
+    if value {
+        "on"
+    } else {
+        "off"
+    }
 }
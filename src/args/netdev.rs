@@ -0,0 +1,176 @@
+use crate::args::{
+    required_value, ArgSyntax, FromArg, InvalidValue, KeyValues, ObjectId, ParseError, Props,
+    QemuArgument,
+};
+use std::ffi::{OsStr, OsString};
+use std::path::PathBuf;
+
+/// A network backend, added via the `-netdev` option.
+///
+/// It is connected to the guest by a network device that references
+/// its id, see [`Device`](crate::args::Device).
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub struct Netdev {
+    pub id: ObjectId<Netdev>,
+    pub kind: NetdevKind,
+}
+
+impl Netdev {
+    fn props(&self) -> Props {
+        let mut props = Props::new();
+        props.push("type", self.kind.as_str()).push("id", &self.id);
+        match &self.kind {
+            NetdevKind::User { net, restrict } => {
+                props
+                    .push_opt("net", net.as_ref())
+                    .push_opt("restrict", *restrict);
+            }
+            NetdevKind::Tap {
+                ifname,
+                script,
+                downscript,
+                vhost,
+            } => {
+                props
+                    .push_opt("ifname", ifname.as_ref())
+                    .push_opt("script", script.as_ref())
+                    .push_opt("downscript", downscript.as_ref())
+                    .push_opt("vhost", *vhost);
+            }
+            NetdevKind::Bridge { br } => {
+                props.push_opt("br", br.as_ref());
+            }
+        }
+        props
+    }
+}
+
+impl QemuArgument for Netdev {
+    fn format(&self) -> Result<Vec<OsString>, InvalidValue> {
+        self.format_with(ArgSyntax::KeyValue)
+    }
+
+    fn format_with(&self, syntax: ArgSyntax) -> Result<Vec<OsString>, InvalidValue> {
+        Ok(vec![
+            "-netdev".into(),
+            self.props().render(syntax, Some("type"))?,
+        ])
+    }
+}
+
+impl FromArg for Netdev {
+    fn from_arg(value: Option<&OsStr>) -> Result<Self, ParseError> {
+        let value = required_value("-netdev", value)?;
+        let mut props = KeyValues::parse("-netdev", value, Some("type"))?;
+        let kind: String = props.required("type")?;
+        let id = props.required("id")?;
+        let kind = match kind.as_str() {
+            "user" => NetdevKind::User {
+                net: props.take("net")?,
+                restrict: props.take("restrict")?,
+            },
+            "tap" => NetdevKind::Tap {
+                ifname: props.take("ifname")?,
+                script: props.take("script")?,
+                downscript: props.take("downscript")?,
+                vhost: props.take("vhost")?,
+            },
+            "bridge" => NetdevKind::Bridge {
+                br: props.take("br")?,
+            },
+            _ => {
+                return Err(ParseError::unsupported(
+                    "-netdev",
+                    format!("unknown type {}", kind),
+                ))
+            }
+        };
+        props.finish()?;
+        Ok(Self { id, kind })
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum NetdevKind {
+    /// User mode networking.
+    User {
+        /// The guest network, for example `10.0.2.0/24`.
+        net: Option<String>,
+        /// Isolates the guest from the host and the outside network.
+        restrict: Option<bool>,
+    },
+    Tap {
+        ifname: Option<String>,
+        script: Option<PathBuf>,
+        downscript: Option<PathBuf>,
+        vhost: Option<bool>,
+    },
+    Bridge {
+        br: Option<String>,
+    },
+}
+
+impl NetdevKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::User { .. } => "user",
+            Self::Tap { .. } => "tap",
+            Self::Bridge { .. } => "bridge",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Generic;
+    use crate::Qemu;
+
+    fn netdev() -> Netdev {
+        Netdev {
+            id: ObjectId::new("net0"),
+            kind: NetdevKind::User {
+                net: Some("10.0.2.0/24".to_string()),
+                restrict: Some(true),
+            },
+        }
+    }
+
+    #[test]
+    fn test_netdev_key_value() {
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.netdev(netdev());
+
+        let args = qemu.args().unwrap();
+        assert_eq!(
+            &["-netdev", "user,id=net0,net=10.0.2.0/24,restrict=on"],
+            args.as_slice()
+        );
+    }
+
+    #[test]
+    fn test_netdev_json() {
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.arg_syntax(ArgSyntax::Json);
+        qemu.netdev(netdev());
+
+        let args = qemu.args().unwrap();
+        assert_eq!(
+            &[
+                "-netdev",
+                r#"{"type":"user","id":"net0","net":"10.0.2.0/24","restrict":true}"#
+            ],
+            args.as_slice()
+        );
+    }
+}
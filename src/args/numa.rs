@@ -0,0 +1,751 @@
+use crate::args::{
+    required_value, ArgSyntax, InvalidValue, KeyValues, MemoryBackend, ObjectId, ParseError, Props,
+    QemuArgument, QemuValue, Smp, ToPropValue,
+};
+use std::collections::HashSet;
+use std::ffi::{OsStr, OsString};
+use std::fmt::{Display, Formatter};
+use std::ops::RangeInclusive;
+
+/// A NUMA topology, which expands to one `-numa` option per entry.
+///
+/// Use [`Self::validate`] to check the topology against the cpu
+/// topology and the memory backends.
+#[derive(Default, Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default, rename_all = "kebab-case")
+)]
+pub struct NumaTopology {
+    pub nodes: Vec<NumaNode>,
+    pub distances: Vec<NumaDist>,
+    pub cpus: Vec<NumaCpu>,
+    pub hmat_lb: Vec<HmatLb>,
+    pub hmat_cache: Vec<HmatCache>,
+}
+
+impl NumaTopology {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn node(&mut self, node: NumaNode) -> &mut Self {
+        self.nodes.push(node);
+        self
+    }
+
+    /// Sets the distance from `src` to `dst`. If no distance from `dst`
+    /// to `src` is given, QEMU assumes a symmetric distance.
+    pub fn dist(&mut self, src: u32, dst: u32, val: u8) -> &mut Self {
+        self.distances.push(NumaDist { src, dst, val });
+        self
+    }
+
+    pub fn cpu(&mut self, cpu: NumaCpu) -> &mut Self {
+        self.cpus.push(cpu);
+        self
+    }
+
+    /// Adds a HMAT latency or bandwidth entry. This requires
+    /// [`Machine::hmat`](crate::args::Machine::hmat) to be enabled.
+    pub fn hmat_lb(&mut self, hmat_lb: HmatLb) -> &mut Self {
+        self.hmat_lb.push(hmat_lb);
+        self
+    }
+
+    /// Adds a HMAT memory side cache entry. This requires
+    /// [`Machine::hmat`](crate::args::Machine::hmat) to be enabled.
+    pub fn hmat_cache(&mut self, hmat_cache: HmatCache) -> &mut Self {
+        self.hmat_cache.push(hmat_cache);
+        self
+    }
+
+    /// Checks that the topology is consistent in itself, and with
+    /// the given cpu topology and memory backends.
+    pub fn validate<'a>(
+        &self,
+        smp: &Smp,
+        backends: impl IntoIterator<Item = &'a MemoryBackend>,
+    ) -> Result<(), NumaError> {
+        let backend_ids: HashSet<_> = backends.into_iter().map(|b| &b.id).collect();
+        let mut node_ids = HashSet::new();
+        for node in &self.nodes {
+            if !node_ids.insert(node.nodeid) {
+                return Err(NumaError::DuplicateNode(node.nodeid));
+            }
+        }
+        let check_node = |id: u32| {
+            if node_ids.contains(&id) {
+                Ok(())
+            } else {
+                Err(NumaError::UnknownNode(id))
+            }
+        };
+
+        let max_cpus = smp.max_cpus().map_err(NumaError::Smp)?;
+        let mut assigned_cpus = HashSet::new();
+        for node in &self.nodes {
+            if let Some(memdev) = &node.memdev {
+                if !backend_ids.contains(memdev) {
+                    return Err(NumaError::UnknownMemdev(memdev.to_string()));
+                }
+            }
+            if let Some(initiator) = node.initiator {
+                check_node(initiator)?;
+            }
+            for cpu in node.cpus.iter().flat_map(|r| r.clone()) {
+                if cpu >= max_cpus {
+                    return Err(NumaError::CpuOutOfRange(cpu));
+                }
+                if !assigned_cpus.insert(cpu) {
+                    return Err(NumaError::CpuAssignedTwice(cpu));
+                }
+            }
+        }
+
+        for dist in &self.distances {
+            check_node(dist.src)?;
+            check_node(dist.dst)?;
+        }
+
+        // Counts that depend on the machine type aren't checked.
+        let derived = smp.derived();
+        for cpu in &self.cpus {
+            check_node(cpu.node_id)?;
+            let checks = [
+                (cpu.socket_id, derived.sockets, "socket-id"),
+                (cpu.die_id, derived.dies, "die-id"),
+                (cpu.core_id, derived.cores, "core-id"),
+                (cpu.thread_id, derived.threads, "thread-id"),
+            ];
+            for (id, count, name) in checks {
+                if let (Some(id), Some(count)) = (id, count) {
+                    if id >= count {
+                        return Err(NumaError::TopologyOutOfRange { name, id });
+                    }
+                }
+            }
+        }
+
+        for lb in &self.hmat_lb {
+            check_node(lb.initiator)?;
+            check_node(lb.target)?;
+        }
+        for cache in &self.hmat_cache {
+            check_node(cache.node_id)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl QemuArgument for NumaTopology {
+    fn format(&self) -> Result<Vec<OsString>, InvalidValue> {
+        let entries = self
+            .nodes
+            .iter()
+            .map(NumaNode::props)
+            .chain(self.distances.iter().map(NumaDist::props))
+            .chain(self.cpus.iter().map(NumaCpu::props))
+            .chain(self.hmat_lb.iter().map(HmatLb::props))
+            .chain(self.hmat_cache.iter().map(HmatCache::props));
+        let mut args = Vec::new();
+        for props in entries {
+            args.push("-numa".into());
+            args.push(props.render(ArgSyntax::KeyValue, Some("type"))?);
+        }
+        Ok(args)
+    }
+}
+
+impl NumaTopology {
+    /// Parses the value of a single `-numa` option, which is one of the
+    /// entries of [`Self::format`], and adds it to this topology.
+    pub(crate) fn parse_entry(&mut self, value: Option<&OsStr>) -> Result<(), ParseError> {
+        let value = required_value("-numa", value)?;
+        let mut props = KeyValues::parse("-numa", value, Some("type"))?;
+        let kind: String = props.required("type")?;
+        match kind.as_str() {
+            "node" => {
+                let node = NumaNode::from_props(&mut props)?;
+                props.finish()?;
+                self.nodes.push(node);
+            }
+            "dist" => {
+                let dist = NumaDist {
+                    src: props.required("src")?,
+                    dst: props.required("dst")?,
+                    val: props.required("val")?,
+                };
+                props.finish()?;
+                self.distances.push(dist);
+            }
+            "cpu" => {
+                let cpu = NumaCpu {
+                    node_id: props.required("node-id")?,
+                    socket_id: props.take("socket-id")?,
+                    die_id: props.take("die-id")?,
+                    core_id: props.take("core-id")?,
+                    thread_id: props.take("thread-id")?,
+                };
+                props.finish()?;
+                self.cpus.push(cpu);
+            }
+            "hmat-lb" => {
+                let hmat_lb = HmatLb::from_props(&mut props)?;
+                props.finish()?;
+                self.hmat_lb.push(hmat_lb);
+            }
+            "hmat-cache" => {
+                let hmat_cache = HmatCache {
+                    node_id: props.required("node-id")?,
+                    size: props.required("size")?,
+                    level: props.required("level")?,
+                    associativity: props.required("associativity")?,
+                    policy: props.required("policy")?,
+                    line: props.required("line")?,
+                };
+                props.finish()?;
+                self.hmat_cache.push(hmat_cache);
+            }
+            _ => {
+                return Err(ParseError::unsupported(
+                    "-numa",
+                    format!("unknown type {}", kind),
+                ))
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default, Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default, rename_all = "kebab-case")
+)]
+pub struct NumaNode {
+    pub nodeid: u32,
+    /// The memory backend that provides the memory of this node.
+    pub memdev: Option<ObjectId<MemoryBackend>>,
+    pub cpus: Vec<RangeInclusive<u32>>,
+    pub initiator: Option<u32>,
+}
+
+impl NumaNode {
+    pub fn new(nodeid: u32) -> Self {
+        Self {
+            nodeid,
+            ..Default::default()
+        }
+    }
+
+    fn props(&self) -> Props {
+        let cpus = self
+            .cpus
+            .iter()
+            .map(|cpus| {
+                if cpus.start() == cpus.end() {
+                    cpus.start().to_string()
+                } else {
+                    format!("{}-{}", cpus.start(), cpus.end())
+                }
+            })
+            .collect::<Vec<_>>();
+        let mut props = Props::new();
+        props
+            .push("type", "node")
+            .push("nodeid", self.nodeid)
+            .push("cpus", cpus)
+            .push_opt("memdev", self.memdev.as_ref())
+            .push_opt("initiator", self.initiator);
+        props
+    }
+
+    fn from_props(props: &mut KeyValues) -> Result<Self, ParseError> {
+        let nodeid = props.required("nodeid")?;
+        let cpus = props
+            .take_all::<String>("cpus")?
+            .iter()
+            .map(|cpus| {
+                let range = match cpus.split_once('-') {
+                    Some((start, end)) => start.parse().ok().zip(end.parse().ok()),
+                    None => cpus.parse().ok().map(|cpu| (cpu, cpu)),
+                };
+                range.map(|(start, end)| start..=end).ok_or_else(|| {
+                    ParseError::invalid("-numa", format!("invalid value {:?} for cpus", cpus))
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            nodeid,
+            memdev: props.take("memdev")?,
+            cpus,
+            initiator: props.take("initiator")?,
+        })
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub struct NumaDist {
+    pub src: u32,
+    pub dst: u32,
+    pub val: u8,
+}
+
+impl NumaDist {
+    fn props(&self) -> Props {
+        let mut props = Props::new();
+        props
+            .push("type", "dist")
+            .push("src", self.src)
+            .push("dst", self.dst)
+            .push("val", u32::from(self.val));
+        props
+    }
+}
+
+/// Assigns cpus, selected by their topology ids, to a node.
+#[derive(Default, Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default, rename_all = "kebab-case")
+)]
+pub struct NumaCpu {
+    pub node_id: u32,
+    pub socket_id: Option<u32>,
+    pub die_id: Option<u32>,
+    pub core_id: Option<u32>,
+    pub thread_id: Option<u32>,
+}
+
+impl NumaCpu {
+    fn props(&self) -> Props {
+        let mut props = Props::new();
+        props
+            .push("type", "cpu")
+            .push("node-id", self.node_id)
+            .push_opt("socket-id", self.socket_id)
+            .push_opt("die-id", self.die_id)
+            .push_opt("core-id", self.core_id)
+            .push_opt("thread-id", self.thread_id);
+        props
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub struct HmatLb {
+    pub initiator: u32,
+    pub target: u32,
+    pub hierarchy: HmatHierarchy,
+    pub data_type: HmatDataType,
+    pub value: HmatLbValue,
+}
+
+impl HmatLb {
+    fn props(&self) -> Props {
+        let (key, value) = match self.value {
+            HmatLbValue::Latency(ns) => ("latency", ns),
+            HmatLbValue::Bandwidth(bytes) => ("bandwidth", bytes),
+        };
+        let mut props = Props::new();
+        props
+            .push("type", "hmat-lb")
+            .push("initiator", self.initiator)
+            .push("target", self.target)
+            .push("hierarchy", self.hierarchy.to_prop_value())
+            .push("data-type", self.data_type.to_prop_value())
+            .push(key, value);
+        props
+    }
+
+    fn from_props(props: &mut KeyValues) -> Result<Self, ParseError> {
+        let initiator = props.required("initiator")?;
+        let target = props.required("target")?;
+        let hierarchy = props.required("hierarchy")?;
+        let data_type = props.required("data-type")?;
+        let value = match (props.take("latency")?, props.take("bandwidth")?) {
+            (Some(ns), None) => HmatLbValue::Latency(ns),
+            (None, Some(bytes)) => HmatLbValue::Bandwidth(bytes),
+            _ => {
+                return Err(ParseError::unsupported(
+                    "-numa",
+                    "hmat-lb needs either latency or bandwidth",
+                ))
+            }
+        };
+        Ok(Self {
+            initiator,
+            target,
+            hierarchy,
+            data_type,
+            value,
+        })
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, QemuValue)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum HmatHierarchy {
+    Memory,
+    FirstLevel,
+    SecondLevel,
+    ThirdLevel,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, QemuValue)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum HmatDataType {
+    AccessLatency,
+    ReadLatency,
+    WriteLatency,
+    AccessBandwidth,
+    ReadBandwidth,
+    WriteBandwidth,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum HmatLbValue {
+    /// Latency in nanoseconds.
+    Latency(u64),
+    /// Bandwidth in bytes per second.
+    Bandwidth(u64),
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub struct HmatCache {
+    pub node_id: u32,
+    /// The cache size in bytes.
+    pub size: u64,
+    pub level: u8,
+    pub associativity: HmatCacheAssociativity,
+    pub policy: HmatCachePolicy,
+    /// The cache line size in bytes.
+    pub line: u16,
+}
+
+impl HmatCache {
+    fn props(&self) -> Props {
+        let mut props = Props::new();
+        props
+            .push("type", "hmat-cache")
+            .push("node-id", self.node_id)
+            .push("size", self.size)
+            .push("level", u32::from(self.level))
+            .push("associativity", self.associativity.to_prop_value())
+            .push("policy", self.policy.to_prop_value())
+            .push("line", u32::from(self.line));
+        props
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, QemuValue)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum HmatCacheAssociativity {
+    None,
+    Direct,
+    Complex,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, QemuValue)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum HmatCachePolicy {
+    None,
+    WriteBack,
+    WriteThrough,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum NumaError {
+    DuplicateNode(u32),
+    UnknownNode(u32),
+    UnknownMemdev(String),
+    CpuOutOfRange(u32),
+    CpuAssignedTwice(u32),
+    TopologyOutOfRange {
+        name: &'static str,
+        id: u32,
+    },
+    /// The `-smp` topology itself is invalid.
+    Smp(InvalidValue),
+}
+
+impl Display for NumaError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::DuplicateNode(id) => write!(f, "numa node {} is defined twice", id),
+            Self::UnknownNode(id) => write!(f, "numa node {} is not defined", id),
+            Self::UnknownMemdev(id) => write!(f, "memory backend '{}' is not defined", id),
+            Self::CpuOutOfRange(cpu) => write!(f, "cpu {} exceeds the -smp topology", cpu),
+            Self::CpuAssignedTwice(cpu) => {
+                write!(f, "cpu {} is assigned to more than one node", cpu)
+            }
+            Self::TopologyOutOfRange { name, id } => {
+                write!(f, "{}={} exceeds the -smp topology", name, id)
+            }
+            Self::Smp(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for NumaError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::args::MemoryBackendKind;
+    use crate::Generic;
+    use crate::Qemu;
+
+    fn backends() -> Vec<MemoryBackend> {
+        vec![
+            MemoryBackend::new("mem0", MemoryBackendKind::Ram, 1 << 30),
+            MemoryBackend::new("mem1", MemoryBackendKind::Ram, 1 << 29),
+        ]
+    }
+
+    fn smp() -> Smp {
+        Smp {
+            cpus: Some(4),
+            sockets: Some(2),
+            cores: Some(2),
+            ..Default::default()
+        }
+    }
+
+    fn topology() -> NumaTopology {
+        let mut numa = NumaTopology::new();
+        numa.node(NumaNode {
+            memdev: Some(ObjectId::new("mem0")),
+            cpus: vec![0..=2],
+            ..NumaNode::new(0)
+        })
+        .node(NumaNode {
+            memdev: Some(ObjectId::new("mem1")),
+            cpus: vec![3..=3],
+            ..NumaNode::new(1)
+        })
+        .dist(0, 1, 30);
+        numa
+    }
+
+    #[test]
+    fn test_numa_args() {
+        let mut numa = topology();
+        numa.cpu(NumaCpu {
+            node_id: 1,
+            socket_id: Some(1),
+            ..Default::default()
+        });
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.numa(numa);
+
+        let args = qemu.args().unwrap();
+        assert_eq!(
+            &[
+                "-numa",
+                "node,nodeid=0,cpus=0-2,memdev=mem0",
+                "-numa",
+                "node,nodeid=1,cpus=3,memdev=mem1",
+                "-numa",
+                "dist,src=0,dst=1,val=30",
+                "-numa",
+                "cpu,node-id=1,socket-id=1"
+            ],
+            args.as_slice()
+        );
+    }
+
+    #[test]
+    fn test_hmat_args() {
+        let mut numa = NumaTopology::new();
+        numa.hmat_lb(HmatLb {
+            initiator: 0,
+            target: 1,
+            hierarchy: HmatHierarchy::Memory,
+            data_type: HmatDataType::AccessLatency,
+            value: HmatLbValue::Latency(10),
+        })
+        .hmat_cache(HmatCache {
+            node_id: 1,
+            size: 10240,
+            level: 1,
+            associativity: HmatCacheAssociativity::Direct,
+            policy: HmatCachePolicy::WriteBack,
+            line: 8,
+        });
+
+        assert_eq!(
+            vec![
+                "-numa",
+                "hmat-lb,initiator=0,target=1,hierarchy=memory,data-type=access-latency,latency=10",
+                "-numa",
+                "hmat-cache,node-id=1,size=10240,level=1,associativity=direct,policy=write-back,line=8"
+            ],
+            numa.format().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_validate_ok() {
+        assert_eq!(Ok(()), topology().validate(&smp(), &backends()));
+    }
+
+    #[test]
+    fn test_validate_unknown_memdev() {
+        let mut numa = topology();
+        numa.node(NumaNode {
+            memdev: Some(ObjectId::new("mem2")),
+            ..NumaNode::new(2)
+        });
+        assert_eq!(
+            Err(NumaError::UnknownMemdev("mem2".to_string())),
+            numa.validate(&smp(), &backends())
+        );
+    }
+
+    #[test]
+    fn test_validate_cpus() {
+        let mut numa = topology();
+        numa.nodes[1].cpus.push(4..=4);
+        assert_eq!(
+            Err(NumaError::CpuOutOfRange(4)),
+            numa.validate(&smp(), &backends())
+        );
+
+        let mut numa = topology();
+        numa.nodes[1].cpus.push(2..=2);
+        assert_eq!(
+            Err(NumaError::CpuAssignedTwice(2)),
+            numa.validate(&smp(), &backends())
+        );
+    }
+
+    #[test]
+    fn test_validate_nodes() {
+        let mut numa = topology();
+        numa.dist(0, 3, 20);
+        assert_eq!(
+            Err(NumaError::UnknownNode(3)),
+            numa.validate(&smp(), &backends())
+        );
+
+        let mut numa = topology();
+        numa.node(NumaNode::new(1));
+        assert_eq!(
+            Err(NumaError::DuplicateNode(1)),
+            numa.validate(&smp(), &backends())
+        );
+    }
+
+    #[test]
+    fn test_validate_cpu_topology() {
+        let mut numa = NumaTopology::new();
+        numa.node(NumaNode::new(0)).cpu(NumaCpu {
+            node_id: 0,
+            socket_id: Some(2),
+            ..Default::default()
+        });
+        assert_eq!(
+            Err(NumaError::TopologyOutOfRange {
+                name: "socket-id",
+                id: 2
+            }),
+            numa.validate(&smp(), &backends())
+        );
+    }
+
+    #[test]
+    fn test_validate_derived_topology() {
+        let mut numa = NumaTopology::new();
+        numa.node(NumaNode::new(0)).cpu(NumaCpu {
+            node_id: 0,
+            core_id: Some(3),
+            ..Default::default()
+        });
+        let smp = Smp {
+            cpus: Some(8),
+            sockets: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(Ok(()), numa.validate(&smp, &backends()));
+
+        // Whether QEMU derives the sockets or the cores from cpus=8
+        // depends on the machine type.
+        numa.cpu(NumaCpu {
+            node_id: 0,
+            socket_id: Some(7),
+            ..Default::default()
+        });
+        let smp = Smp {
+            cpus: Some(8),
+            ..Default::default()
+        };
+        assert_eq!(Ok(()), numa.validate(&smp, &backends()));
+
+        numa.cpu(NumaCpu {
+            node_id: 0,
+            thread_id: Some(1),
+            ..Default::default()
+        });
+        assert_eq!(
+            Err(NumaError::TopologyOutOfRange {
+                name: "thread-id",
+                id: 1
+            }),
+            numa.validate(&smp, &backends())
+        );
+    }
+
+    #[test]
+    fn test_validate_smp_overflow() {
+        let smp = Smp {
+            sockets: Some(1 << 16),
+            cores: Some(1 << 16),
+            ..Default::default()
+        };
+        assert!(matches!(
+            topology().validate(&smp, &backends()),
+            Err(NumaError::Smp(_))
+        ));
+    }
+}
@@ -0,0 +1,375 @@
+use crate::args::{
+    required_value, ArgSyntax, Filter, FromArg, InvalidValue, KeyValues, MemoryBackend, ParseError,
+    Props, QemuArgument, Rng, Secret, ThrottleGroup, TlsCreds,
+};
+use std::ffi::{OsStr, OsString};
+use std::fmt::{Debug, Display, Formatter};
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+
+/// The id of an object of type `T`, or of a backend such as a
+/// [`Netdev`](crate::args::Netdev) or a [`Chardev`](crate::args::Chardev).
+///
+/// Options that reference an object take an `ObjectId` of the matching
+/// type, so that for example a drive can only use a [`Secret`] as key.
+pub struct ObjectId<T> {
+    id: String,
+    _kind: PhantomData<fn() -> T>,
+}
+
+impl<T> ObjectId<T> {
+    pub fn new(id: impl ToString) -> Self {
+        Self {
+            id: id.to_string(),
+            _kind: PhantomData,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.id
+    }
+}
+
+// These are implemented manually, since deriving them would require
+// `T` to implement them as well.
+
+impl<T> Clone for ObjectId<T> {
+    fn clone(&self) -> Self {
+        Self::new(&self.id)
+    }
+}
+
+impl<T> PartialEq for ObjectId<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl<T> Eq for ObjectId<T> {}
+
+impl<T> Hash for ObjectId<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+
+impl<T> Debug for ObjectId<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("ObjectId").field(&self.id).finish()
+    }
+}
+
+impl<T> Display for ObjectId<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.id)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T> serde::Serialize for ObjectId<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.id)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T> serde::Deserialize<'de> for ObjectId<T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        String::deserialize(deserializer).map(Self::new)
+    }
+}
+
+/// A user creatable object, added via the `-object` option.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum Object {
+    MemoryBackend(MemoryBackend),
+    Secret(Secret),
+    Rng(Rng),
+    Iothread(Iothread),
+    TlsCreds(TlsCreds),
+    AuthzSimple(AuthzSimple),
+    ThrottleGroup(ThrottleGroup),
+    Filter(Filter),
+    CryptodevBackendBuiltin(CryptodevBackendBuiltin),
+}
+
+impl Object {
+    pub fn id(&self) -> &str {
+        match self {
+            Self::MemoryBackend(o) => o.id.as_str(),
+            Self::Secret(o) => o.id.as_str(),
+            Self::Rng(o) => o.id.as_str(),
+            Self::Iothread(o) => o.id.as_str(),
+            Self::TlsCreds(o) => o.id.as_str(),
+            Self::AuthzSimple(o) => o.id.as_str(),
+            Self::ThrottleGroup(o) => o.id.as_str(),
+            Self::Filter(o) => o.id.as_str(),
+            Self::CryptodevBackendBuiltin(o) => o.id.as_str(),
+        }
+    }
+
+    fn props(&self) -> Props {
+        match self {
+            Self::MemoryBackend(o) => o.props(),
+            Self::Secret(o) => o.props(),
+            Self::Rng(o) => o.props(),
+            Self::Iothread(o) => o.props(),
+            Self::TlsCreds(o) => o.props(),
+            Self::AuthzSimple(o) => o.props(),
+            Self::ThrottleGroup(o) => o.props(),
+            Self::Filter(o) => o.props(),
+            Self::CryptodevBackendBuiltin(o) => o.props(),
+        }
+    }
+}
+
+impl QemuArgument for Object {
+    fn format(&self) -> Result<Vec<OsString>, InvalidValue> {
+        self.format_with(ArgSyntax::KeyValue)
+    }
+
+    fn format_with(&self, syntax: ArgSyntax) -> Result<Vec<OsString>, InvalidValue> {
+        Ok(vec![
+            "-object".into(),
+            self.props().render(syntax, Some("qom-type"))?,
+        ])
+    }
+}
+
+impl FromArg for Object {
+    fn from_arg(value: Option<&OsStr>) -> Result<Self, ParseError> {
+        let value = required_value("-object", value)?;
+        let mut props = KeyValues::parse("-object", value, Some("qom-type"))?;
+        let qom_type: String = props.required("qom-type")?;
+        let object: Object = match qom_type.as_str() {
+            "memory-backend-ram" | "memory-backend-file" | "memory-backend-memfd" => {
+                MemoryBackend::from_props(&qom_type, &mut props)?.into()
+            }
+            "secret" => Secret::from_props(&mut props)?.into(),
+            "rng-random" | "rng-builtin" | "rng-egd" => {
+                Rng::from_props(&qom_type, &mut props)?.into()
+            }
+            "iothread" => Iothread::from_props(&mut props)?.into(),
+            "tls-creds-x509" | "tls-creds-psk" => {
+                TlsCreds::from_props(&qom_type, &mut props)?.into()
+            }
+            "authz-simple" => AuthzSimple::from_props(&mut props)?.into(),
+            "throttle-group" => ThrottleGroup::from_props(&mut props)?.into(),
+            "filter-buffer" | "filter-mirror" | "filter-redirector" | "filter-dump"
+            | "filter-rewriter" | "filter-replay" => {
+                Filter::from_props(&qom_type, &mut props)?.into()
+            }
+            "cryptodev-backend-builtin" => CryptodevBackendBuiltin::from_props(&mut props)?.into(),
+            _ => return Err(unknown_qom_type(&qom_type)),
+        };
+        props.finish()?;
+        Ok(object)
+    }
+}
+
+/// The error for an object type that isn't modeled by [`Object`].
+pub(crate) fn unknown_qom_type(qom_type: &str) -> ParseError {
+    ParseError::unsupported("-object", format!("unknown qom-type {}", qom_type))
+}
+
+macro_rules! impl_from_for_object {
+    ($($ty:ident),*) => {
+        $(
+            impl From<$ty> for Object {
+                fn from(value: $ty) -> Self {
+                    Self::$ty(value)
+                }
+            }
+        )*
+    };
+}
+
+impl_from_for_object!(
+    MemoryBackend,
+    Secret,
+    Rng,
+    Iothread,
+    TlsCreds,
+    AuthzSimple,
+    ThrottleGroup,
+    Filter,
+    CryptodevBackendBuiltin
+);
+
+/// An I/O thread that devices can use instead of the main loop.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub struct Iothread {
+    pub id: ObjectId<Iothread>,
+    pub poll_max_ns: Option<u64>,
+}
+
+impl Iothread {
+    pub fn new(id: impl ToString) -> Self {
+        Self {
+            id: ObjectId::new(id),
+            poll_max_ns: None,
+        }
+    }
+
+    fn props(&self) -> Props {
+        let mut props = Props::new();
+        props
+            .push("qom-type", "iothread")
+            .push("id", &self.id)
+            .push_opt("poll-max-ns", self.poll_max_ns);
+        props
+    }
+
+    /// Parses the properties of [`Self::props`] after the `qom-type`.
+    pub(crate) fn from_props(props: &mut KeyValues) -> Result<Self, ParseError> {
+        Ok(Self {
+            id: props.required("id")?,
+            poll_max_ns: props.take("poll-max-ns")?,
+        })
+    }
+}
+
+/// An authorization object that allows exactly one identity.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub struct AuthzSimple {
+    pub id: ObjectId<AuthzSimple>,
+    pub identity: String,
+}
+
+impl AuthzSimple {
+    fn props(&self) -> Props {
+        let mut props = Props::new();
+        props
+            .push("qom-type", "authz-simple")
+            .push("id", &self.id)
+            .push("identity", &self.identity);
+        props
+    }
+
+    /// Parses the properties of [`Self::props`] after the `qom-type`.
+    pub(crate) fn from_props(props: &mut KeyValues) -> Result<Self, ParseError> {
+        Ok(Self {
+            id: props.required("id")?,
+            identity: props.required("identity")?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub struct CryptodevBackendBuiltin {
+    pub id: ObjectId<CryptodevBackendBuiltin>,
+    pub queues: Option<u32>,
+}
+
+impl CryptodevBackendBuiltin {
+    fn props(&self) -> Props {
+        let mut props = Props::new();
+        props
+            .push("qom-type", "cryptodev-backend-builtin")
+            .push("id", &self.id)
+            .push_opt("queues", self.queues);
+        props
+    }
+
+    /// Parses the properties of [`Self::props`] after the `qom-type`.
+    pub(crate) fn from_props(props: &mut KeyValues) -> Result<Self, ParseError> {
+        Ok(Self {
+            id: props.required("id")?,
+            queues: props.take("queues")?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Generic;
+    use crate::Qemu;
+
+    #[test]
+    fn test_iothread() {
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.object(Iothread {
+            poll_max_ns: Some(32768),
+            ..Iothread::new("io0")
+        });
+
+        let args = qemu.args().unwrap();
+        assert_eq!(
+            &["-object", "iothread,id=io0,poll-max-ns=32768"],
+            args.as_slice()
+        );
+    }
+
+    #[test]
+    fn test_authz_simple() {
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.object(AuthzSimple {
+            id: ObjectId::new("auth0"),
+            identity: "CN=client".to_string(),
+        });
+
+        let args = qemu.args().unwrap();
+        assert_eq!(
+            &["-object", "authz-simple,id=auth0,identity=CN=client"],
+            args.as_slice()
+        );
+    }
+
+    #[test]
+    fn test_cryptodev_backend_builtin() {
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.object(CryptodevBackendBuiltin {
+            id: ObjectId::new("cryptodev0"),
+            queues: Some(2),
+        });
+
+        let args = qemu.args().unwrap();
+        assert_eq!(
+            &[
+                "-object",
+                "cryptodev-backend-builtin,id=cryptodev0,queues=2"
+            ],
+            args.as_slice()
+        );
+    }
+
+    #[test]
+    fn test_objects_keep_order() {
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.object(Iothread::new("io0"));
+        qemu.object(Iothread::new("io1"));
+
+        let args = qemu.args().unwrap();
+        assert_eq!(
+            &["-object", "iothread,id=io0", "-object", "iothread,id=io1"],
+            args.as_slice()
+        );
+    }
+}
@@ -0,0 +1,711 @@
+use crate::args::ObjectId;
+use std::ffi::{OsStr, OsString};
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
+
+/// An argument that can't be parsed into a typed option.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum ParseError {
+    /// The argument is malformed, for example an option is missing
+    /// its value or a property has an invalid value.
+    Invalid { option: String, reason: String },
+    /// The argument uses a property or syntax that isn't modeled.
+    /// [`Qemu::parse_args`](crate::Qemu::parse_args) keeps such options
+    /// as other arguments.
+    Unsupported { option: String, reason: String },
+}
+
+impl ParseError {
+    pub fn invalid(option: impl ToString, reason: impl ToString) -> Self {
+        Self::Invalid {
+            option: option.to_string(),
+            reason: reason.to_string(),
+        }
+    }
+
+    pub fn unsupported(option: impl ToString, reason: impl ToString) -> Self {
+        Self::Unsupported {
+            option: option.to_string(),
+            reason: reason.to_string(),
+        }
+    }
+
+    pub fn is_unsupported(&self) -> bool {
+        matches!(self, Self::Unsupported { .. })
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Invalid { option, reason } => {
+                write!(f, "invalid {} argument: {}", option, reason)
+            }
+            Self::Unsupported { option, reason } => {
+                write!(f, "unsupported {} argument: {}", option, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Parses the value of a property, the inverse of [`ToPropValue`](crate::args::ToPropValue).
+///
+/// This is implemented for enums by [`derive@QemuValue`](crate::args::QemuValue).
+pub trait FromProp: Sized {
+    fn from_prop(value: &OsStr) -> Option<Self>;
+}
+
+impl FromProp for String {
+    fn from_prop(value: &OsStr) -> Option<Self> {
+        value.to_str().map(str::to_string)
+    }
+}
+
+impl FromProp for OsString {
+    fn from_prop(value: &OsStr) -> Option<Self> {
+        Some(value.to_os_string())
+    }
+}
+
+impl FromProp for PathBuf {
+    fn from_prop(value: &OsStr) -> Option<Self> {
+        Some(PathBuf::from(value))
+    }
+}
+
+impl FromProp for bool {
+    fn from_prop(value: &OsStr) -> Option<Self> {
+        match value.to_str()? {
+            "on" | "yes" | "true" => Some(true),
+            "off" | "no" | "false" => Some(false),
+            _ => None,
+        }
+    }
+}
+
+macro_rules! impl_from_prop_for_int {
+    ($($ty:ty),*) => {
+        $(
+            impl FromProp for $ty {
+                fn from_prop(value: &OsStr) -> Option<Self> {
+                    value.to_str()?.parse().ok()
+                }
+            }
+        )*
+    };
+}
+
+impl_from_prop_for_int!(u8, u16, u32, u64, usize);
+
+impl<T> FromProp for ObjectId<T> {
+    fn from_prop(value: &OsStr) -> Option<Self> {
+        Some(ObjectId::new(value.to_str()?))
+    }
+}
+
+/// Parses the value of an option, the inverse of
+/// [`QemuArgument::format`](crate::args::QemuArgument::format).
+///
+/// This is implemented by [`derive@QemuArgument`](crate::args::QemuArgument).
+pub trait FromArg: Sized {
+    /// Parses the value of the option, which is `None` for options
+    /// that don't take a value.
+    fn from_arg(value: Option<&OsStr>) -> Result<Self, ParseError>;
+}
+
+/// The properties of an option in key-value syntax, with escaped
+/// commas resolved.
+///
+/// Properties are consumed with [`Self::take`] and similar methods, and
+/// [`Self::finish`] fails on properties that were not consumed.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct KeyValues {
+    option: &'static str,
+    entries: Vec<(String, OsString)>,
+}
+
+impl KeyValues {
+    /// Splits the value of `option`. A leading value without a key is
+    /// assigned to the `implied` key.
+    pub fn parse(
+        option: &'static str,
+        value: &OsStr,
+        implied: Option<&str>,
+    ) -> Result<Self, ParseError> {
+        let bytes = value.as_encoded_bytes();
+        if bytes.starts_with(b"{") {
+            return Err(ParseError::unsupported(option, "JSON syntax"));
+        }
+
+        let mut items = vec![Vec::new()];
+        let mut i = 0;
+        while i < bytes.len() {
+            match (bytes[i], bytes.get(i + 1)) {
+                (b',', Some(b',')) => {
+                    items.last_mut().unwrap().push(b',');
+                    i += 1;
+                }
+                (b',', _) => items.push(Vec::new()),
+                (b, _) => items.last_mut().unwrap().push(b),
+            }
+            i += 1;
+        }
+
+        let mut entries = Vec::new();
+        for (i, item) in items.into_iter().enumerate() {
+            let (key, value) = match item.iter().position(|&b| b == b'=') {
+                Some(eq) => {
+                    let key = String::from_utf8(item[..eq].to_vec())
+                        .map_err(|_| ParseError::invalid(option, "non-UTF-8 key"))?;
+                    (key, item[eq + 1..].to_vec())
+                }
+                None => match implied {
+                    Some(implied) if i == 0 => (implied.to_string(), item),
+                    _ => {
+                        return Err(ParseError::unsupported(
+                            option,
+                            format!(
+                                "property without a value: {}",
+                                String::from_utf8_lossy(&item)
+                            ),
+                        ))
+                    }
+                },
+            };
+            // SAFETY: the bytes come from an `OsStr` and were only split
+            // at ASCII characters.
+            let value = unsafe { OsString::from_encoded_bytes_unchecked(value) };
+            entries.push((key, value));
+        }
+        Ok(Self { option, entries })
+    }
+
+    /// Removes the first property with the given key.
+    pub fn take<T>(&mut self, key: &str) -> Result<Option<T>, ParseError>
+    where
+        T: FromProp,
+    {
+        let Some(i) = self.entries.iter().position(|(k, _)| k == key) else {
+            return Ok(None);
+        };
+        let (_, value) = self.entries.remove(i);
+        match T::from_prop(&value) {
+            Some(value) => Ok(Some(value)),
+            None => Err(ParseError::invalid(
+                self.option,
+                format!("invalid value {:?} for {}", value, key),
+            )),
+        }
+    }
+
+    /// Removes all properties with the given key, which are the
+    /// elements of a list.
+    pub fn take_all<T>(&mut self, key: &str) -> Result<Vec<T>, ParseError>
+    where
+        T: FromProp,
+    {
+        let mut res = Vec::new();
+        while let Some(value) = self.take(key)? {
+            res.push(value);
+        }
+        Ok(res)
+    }
+
+    /// Removes the first property with the given key, and fails if
+    /// there is none.
+    pub fn required<T>(&mut self, key: &str) -> Result<T, ParseError>
+    where
+        T: FromProp,
+    {
+        self.take(key)?
+            .ok_or_else(|| ParseError::unsupported(self.option, format!("missing {}", key)))
+    }
+
+    /// Removes the property with the given key, and fails unless it
+    /// has the `expected` value.
+    pub fn expect(&mut self, key: &str, expected: &str) -> Result<(), ParseError> {
+        let value: OsString = self.required(key)?;
+        if value != expected {
+            return Err(ParseError::unsupported(
+                self.option,
+                format!("{} is {:?} instead of {:?}", key, value, expected),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Removes all properties whose key starts with `prefix` and a dot,
+    /// which are the properties of a nested value in key-value syntax.
+    /// The returned properties have the prefix stripped from their keys.
+    pub fn take_prefixed(&mut self, prefix: &str) -> KeyValues {
+        let mut nested = Vec::new();
+        self.entries.retain(|(key, value)| {
+            match key
+                .strip_prefix(prefix)
+                .and_then(|key| key.strip_prefix('.'))
+            {
+                Some(key) => {
+                    nested.push((key.to_string(), value.clone()));
+                    false
+                }
+                None => true,
+            }
+        });
+        Self {
+            option: self.option,
+            entries: nested,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the properties that were not consumed, in their order.
+    pub fn into_entries(self) -> Vec<(String, OsString)> {
+        self.entries
+    }
+
+    /// Fails if there are properties left.
+    pub fn finish(self) -> Result<(), ParseError> {
+        match self.entries.first() {
+            Some((key, _)) => Err(ParseError::unsupported(
+                self.option,
+                format!("unknown property {}", key),
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Returns the value of `option`, and fails if it is missing.
+pub fn required_value<'a>(
+    option: &'static str,
+    value: Option<&'a OsStr>,
+) -> Result<&'a OsStr, ParseError> {
+    value.ok_or_else(|| ParseError::invalid(option, "missing value"))
+}
+
+/// Returns the name of an option with a single dash, as QEMU accepts
+/// options with one or two dashes.
+pub(crate) fn option_name(arg: &OsStr) -> Option<&str> {
+    match arg.to_str()? {
+        name if name.starts_with("--") => Some(&name[1..]),
+        name if name.starts_with('-') => Some(name),
+        _ => None,
+    }
+}
+
+/// Returns the value of the option at the start of `args` if it is
+/// `option`, which is used to parse options that span several arguments.
+pub(crate) fn leading_value<'a>(args: &'a [OsString], option: &str) -> Option<&'a OsStr> {
+    match args {
+        [name, value, ..] if option_name(name) == Some(option) => Some(value),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::args::*;
+    use crate::chardev::QemuCharDevice;
+    use crate::{Generic, Qemu, QemuOption};
+
+    #[test]
+    fn test_key_values() {
+        let mut kv = KeyValues::parse(
+            "-device",
+            OsStr::new("virtio-net-pci,id=a,,b,queues=1,queues=2"),
+            Some("driver"),
+        )
+        .unwrap();
+        assert_eq!(
+            Some("virtio-net-pci".to_string()),
+            kv.take("driver").unwrap()
+        );
+        assert_eq!(Some("a,b".to_string()), kv.take("id").unwrap());
+        assert_eq!(vec![1u32, 2], kv.take_all::<u32>("queues").unwrap());
+        assert_eq!(Ok(()), kv.finish());
+    }
+
+    #[test]
+    fn test_key_values_prefixed() {
+        let mut kv = KeyValues::parse(
+            "-blockdev",
+            OsStr::new("driver=raw,file.driver=file,file.filename=a.img,filename=b"),
+            None,
+        )
+        .unwrap();
+        let mut file = kv.take_prefixed("file");
+        assert_eq!(Some("file".to_string()), file.take("driver").unwrap());
+        assert_eq!(Some(PathBuf::from("a.img")), file.take("filename").unwrap());
+        assert!(file.is_empty());
+        assert_eq!(
+            vec![
+                ("driver".to_string(), OsString::from("raw")),
+                ("filename".to_string(), OsString::from("b"))
+            ],
+            kv.into_entries()
+        );
+    }
+
+    #[test]
+    fn test_key_values_errors() {
+        assert!(KeyValues::parse("-smp", OsStr::new("4"), None)
+            .unwrap_err()
+            .is_unsupported());
+        assert!(
+            KeyValues::parse("-device", OsStr::new(r#"{"driver":"a"}"#), None)
+                .unwrap_err()
+                .is_unsupported()
+        );
+
+        let mut kv = KeyValues::parse("-smp", OsStr::new("cpus=x,dies=2"), None).unwrap();
+        assert!(!kv.take::<u32>("cpus").unwrap_err().is_unsupported());
+        assert!(kv.finish().unwrap_err().is_unsupported());
+    }
+
+    /// A configuration that sets every option.
+    fn configured() -> Qemu<Generic> {
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.no_reboot()
+            .help()
+            .version()
+            .fullscreen()
+            .accel(Accel {
+                accelerator: Accelerator::Kvm,
+                notify_vmexit: Some(NotifyVmexit::DisableAndNotifyWindow(10)),
+                ..Default::default()
+            })
+            .other("-m")
+            .other("1G")
+            .drive(Drive {
+                file: PathBuf::from("/images/a,b.qcow2"),
+                format: Some(Format::Raw),
+                cache: Some(Cache::None),
+                ..Default::default()
+            })
+            .bios(PathBuf::from("bios.bin"));
+        // The pflash pair is only configured by `Qemu::uefi`, which
+        // needs a prepared firmware.
+        qemu.pflash = Some(Pflash {
+            code: PathBuf::from("/usr/share/OVMF/OVMF_CODE.secboot.fd"),
+            vars: PathBuf::from("/tmp/OVMF_VARS.fd"),
+            format: Format::Raw,
+            secure: true,
+        });
+        qemu.place(QemuOption::Pflash);
+        qemu.serial(QemuCharDevice::Stdio)
+            .log_items([LogItem::GuestErrors, LogItem::Trace("virtio_*".to_string())])
+            .freeze_on_startup()
+            .gdb(&"tcp::1235")
+            .global(Global::new("kvm-pit", "lost_tick_policy", "discard"))
+            .global(Global::new("cfi.pflash01", "secure", "on"))
+            .compat(Compat {
+                deprecated_input: Some(DeprecatedInput::Reject),
+                deprecated_output: None,
+            })
+            .action(Action {
+                panic: Some(PanicAction::ExitFailure),
+                ..Default::default()
+            })
+            .watchdog(Watchdog::I6300esb)
+            .watchdog_action(WatchdogAction::InjectNmi)
+            .tpm(Tpm::new(PathBuf::from("/run/swtpm.sock"), TpmModel::Crb))
+            .machine(Machine {
+                kind: Some("q35".to_string()),
+                memory_backend: Some(ObjectId::new("ram0")),
+                smm: Some(true),
+                ..Default::default()
+            })
+            .object(MemoryBackend {
+                share: Some(true),
+                host_nodes: vec![0, 1],
+                policy: Some(HostMemPolicy::Bind),
+                ..MemoryBackend::new(
+                    "ram0",
+                    MemoryBackendKind::Memfd {
+                        hugetlb: Some(true),
+                        hugetlbsize: Some(2 << 20),
+                    },
+                    1 << 30,
+                )
+            })
+            .object(MemoryBackend::new(
+                "ram1",
+                MemoryBackendKind::File {
+                    mem_path: PathBuf::from("/dev/hugepages"),
+                },
+                1 << 30,
+            ))
+            .object(Secret {
+                format: Some(SecretFormat::Base64),
+                ..Secret::new("sec0", SecretSource::Data("c2VjcmV0".to_string()))
+            })
+            .object(Secret::new(
+                "sec1",
+                SecretSource::File(PathBuf::from("/etc/qemu/key")),
+            ))
+            .object(Rng::new(
+                "rng0",
+                RngKind::Egd {
+                    chardev: ObjectId::new("egd0"),
+                },
+            ))
+            .object(Iothread {
+                poll_max_ns: Some(32768),
+                ..Iothread::new("io0")
+            })
+            .object(TlsCreds {
+                id: ObjectId::new("tls0"),
+                kind: TlsCredsKind::X509 {
+                    verify_peer: Some(true),
+                    passwordid: Some(ObjectId::new("sec1")),
+                },
+                endpoint: TlsEndpoint::Server,
+                dir: PathBuf::from("/etc/pki/qemu"),
+            })
+            .object(AuthzSimple {
+                id: ObjectId::new("auth0"),
+                identity: "CN=client".to_string(),
+            })
+            .object(ThrottleGroup {
+                id: ObjectId::new("tg0"),
+                limits: ThrottleLimits {
+                    iops_total: Some(1000),
+                    bps_write: Some(1 << 20),
+                    ..Default::default()
+                },
+            })
+            .object(Filter {
+                id: ObjectId::new("f0"),
+                kind: FilterKind::Redirector {
+                    indev: None,
+                    outdev: Some(ObjectId::new("mirror0")),
+                },
+                netdev: ObjectId::new("net0"),
+                queue: Some(FilterQueue::Tx),
+            })
+            .object(CryptodevBackendBuiltin {
+                id: ObjectId::new("cryptodev0"),
+                queues: Some(2),
+            })
+            .mem_path(PathBuf::from("/dev/hugepages"))
+            .mem_prealloc()
+            .overcommit(Overcommit {
+                mem_lock: Some(true),
+                cpu_pm: None,
+            })
+            .smp(Smp {
+                cpus: Some(4),
+                threads: Some(2),
+                ..Default::default()
+            })
+            .numa(
+                NumaTopology::new()
+                    .node(NumaNode {
+                        memdev: Some(ObjectId::new("ram0")),
+                        cpus: vec![0..=1, 3..=3],
+                        ..NumaNode::new(0)
+                    })
+                    .node(NumaNode {
+                        memdev: Some(ObjectId::new("ram1")),
+                        initiator: Some(0),
+                        ..NumaNode::new(1)
+                    })
+                    .dist(0, 1, 20)
+                    .cpu(NumaCpu {
+                        node_id: 0,
+                        socket_id: Some(0),
+                        core_id: Some(1),
+                        ..Default::default()
+                    })
+                    .hmat_lb(HmatLb {
+                        initiator: 0,
+                        target: 1,
+                        hierarchy: HmatHierarchy::Memory,
+                        data_type: HmatDataType::AccessLatency,
+                        value: HmatLbValue::Latency(10),
+                    })
+                    .hmat_cache(HmatCache {
+                        node_id: 1,
+                        size: 10 << 10,
+                        level: 1,
+                        associativity: HmatCacheAssociativity::Direct,
+                        policy: HmatCachePolicy::WriteBack,
+                        line: 8,
+                    })
+                    .clone(),
+            )
+            .virtio_rng(VirtioRng {
+                rng: ObjectId::new("rng0"),
+            })
+            .blockdev(Blockdev {
+                read_only: Some(false),
+                cache: Some(BlockdevCache {
+                    direct: Some(true),
+                    no_flush: None,
+                }),
+                ..Blockdev::new(
+                    "disk0",
+                    BlockdevDriver::Qcow2 {
+                        file: BlockdevRef::Inline(Box::new(Blockdev::inline(
+                            BlockdevDriver::File {
+                                filename: PathBuf::from("/images/disk.qcow2"),
+                            },
+                        ))),
+                        backing: Some(BlockdevRef::Node("base0".to_string())),
+                    },
+                )
+            })
+            .blockdev(Blockdev::new(
+                "throttled0",
+                BlockdevDriver::Throttle {
+                    throttle_group: ObjectId::new("tg0"),
+                    file: BlockdevRef::Node("disk0".to_string()),
+                },
+            ))
+            .netdev(Netdev {
+                id: ObjectId::new("net0"),
+                kind: NetdevKind::Tap {
+                    ifname: Some("tap0".to_string()),
+                    script: Some(PathBuf::from("no")),
+                    downscript: None,
+                    vhost: Some(true),
+                },
+            })
+            .chardev(Chardev::new(
+                "egd0",
+                ChardevKind::Socket {
+                    addr: ChardevAddr::Tcp {
+                        host: "localhost".to_string(),
+                        port: 8000,
+                    },
+                    server: None,
+                    wait: None,
+                    tls_creds: None,
+                    tls_authz: None,
+                },
+            ))
+            .chardev(Chardev::new(
+                "mirror0",
+                ChardevKind::File {
+                    path: PathBuf::from("/tmp/mirror.pcap"),
+                },
+            ))
+            .device(
+                Device::new("virtio-net-pci")
+                    .prop("netdev", "net0")
+                    .iothread(&ObjectId::new("io0")),
+            )
+            .vnc(Vnc {
+                tls_creds: Some(ObjectId::new("tls0")),
+                tls_authz: Some(ObjectId::new("auth0")),
+                ..Vnc::new("localhost:1")
+            })
+            .incoming(Incoming {
+                tls_creds: Some(ObjectId::new("tls0")),
+                tls_authz: None,
+                ..Incoming::new("tcp:0:4444")
+            })
+            .other("disk.img");
+        qemu
+    }
+
+    #[test]
+    fn test_parse_round_trip() {
+        let args = configured().into_args().unwrap();
+        let parsed = Qemu::<Generic>::parse_args(args.clone()).unwrap();
+        assert_eq!(configured(), parsed);
+        assert_eq!(args, parsed.into_args().unwrap());
+    }
+
+    #[test]
+    fn test_parse_unsupported_as_other() {
+        let parsed = Qemu::<Generic>::parse_args([
+            "-drive",
+            "if=pflash,format=raw,file=code.fd",
+            "--machine",
+            "q35,kernel-irqchip=split",
+            "-S",
+            "-device",
+            r#"{"driver":"virtio-rng-pci"}"#,
+            "-object",
+            "pr-manager-helper,id=pr0,path=/run/pr.sock",
+            "disk.img",
+        ])
+        .unwrap();
+
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.other("-drive")
+            .other("if=pflash,format=raw,file=code.fd")
+            .other("--machine")
+            .other("q35,kernel-irqchip=split")
+            .freeze_on_startup()
+            .other("-device")
+            .other(r#"{"driver":"virtio-rng-pci"}"#)
+            .other("-object")
+            .other("pr-manager-helper,id=pr0,path=/run/pr.sock")
+            .other("disk.img");
+        assert_eq!(qemu, parsed);
+    }
+
+    #[test]
+    fn test_parse_normalized() {
+        let args = [
+            "-numa",
+            "node,nodeid=0",
+            "-device",
+            "ib700",
+            "-device",
+            "i6300esb",
+            "-device",
+            "virtio-blk-pci,num-queues=4",
+            "-numa",
+            "node,nodeid=1",
+        ];
+        let parsed = Qemu::<Generic>::parse_args(args).unwrap();
+
+        // The second watchdog is a generic device, device properties are
+        // strings, and the NUMA nodes are at the position of the first one.
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.numa(
+            NumaTopology::new()
+                .node(NumaNode::new(0))
+                .node(NumaNode::new(1))
+                .clone(),
+        )
+        .watchdog(Watchdog::Ib700)
+        .device(Device::new("i6300esb"))
+        .device(Device::new("virtio-blk-pci").prop("num-queues", "4"));
+        assert_eq!(qemu, parsed);
+        assert_eq!(
+            &[
+                "-numa",
+                "node,nodeid=0",
+                "-numa",
+                "node,nodeid=1",
+                "-device",
+                "ib700",
+                "-device",
+                "i6300esb",
+                "-device",
+                "virtio-blk-pci,num-queues=4"
+            ],
+            parsed.args().unwrap().as_slice()
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        let err = Qemu::<Generic>::parse_args(["-smp", "cpus=x"]).unwrap_err();
+        assert!(!err.is_unsupported());
+        let err = Qemu::<Generic>::parse_args(["-S", "-drive"]).unwrap_err();
+        assert_eq!(ParseError::invalid("-drive", "missing value"), err);
+        let err = Qemu::<Generic>::parse_args(["-watchdog-action", "explode"]).unwrap_err();
+        assert!(!err.is_unsupported());
+    }
+}
@@ -0,0 +1,39 @@
+use crate::args::{ArgSyntax, InvalidValue, PropValue, Props, QemuArgument};
+use std::ffi::OsString;
+use std::path::Path;
+
+/// A TCG plugin, loaded via the `-plugin` option.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct Plugin {
+    props: Props,
+}
+
+impl Plugin {
+    pub fn new(file: impl AsRef<Path>) -> Self {
+        let mut props = Props::new();
+        props.push("file", file.as_ref());
+        Self { props }
+    }
+
+    /// Sets an argument of the plugin.
+    pub fn arg(mut self, name: impl ToString, value: impl Into<PropValue>) -> Self {
+        self.props.push(name, value);
+        self
+    }
+}
+
+impl QemuArgument for Plugin {
+    fn format(&self) -> Result<Vec<OsString>, InvalidValue> {
+        // The file is given with its key, so that a '=' in the path is
+        // not taken for an argument.
+        Ok(vec![
+            "-plugin".into(),
+            self.props.render(ArgSyntax::KeyValue, None)?,
+        ])
+    }
+}
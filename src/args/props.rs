@@ -0,0 +1,717 @@
+use crate::args::{on_off, ObjectId};
+use std::ffi::{OsStr, OsString};
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+
+/// The syntax that structured options such as `-device`, `-object`,
+/// `-blockdev` and `-netdev` are rendered in.
+#[derive(Default, Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum ArgSyntax {
+    /// The traditional `key=value,...` syntax. Nested values are
+    /// rendered with dotted keys, such as `file.driver=file`.
+    #[default]
+    KeyValue,
+    /// JSON syntax, such as `{"driver":"file"}`.
+    Json,
+}
+
+/// A value of a property in a structured option.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum PropValue {
+    String(String),
+    /// A value that is passed byte-exact, such as a path. Rendering it
+    /// as JSON requires it to be valid UTF-8.
+    OsString(OsString),
+    Int(u64),
+    Bool(bool),
+    List(Vec<PropValue>),
+    Props(Props),
+}
+
+impl From<&str> for PropValue {
+    fn from(value: &str) -> Self {
+        Self::String(value.to_string())
+    }
+}
+
+impl From<String> for PropValue {
+    fn from(value: String) -> Self {
+        Self::String(value)
+    }
+}
+
+impl From<&String> for PropValue {
+    fn from(value: &String) -> Self {
+        Self::String(value.clone())
+    }
+}
+
+impl From<&OsStr> for PropValue {
+    fn from(value: &OsStr) -> Self {
+        Self::OsString(value.to_os_string())
+    }
+}
+
+impl From<OsString> for PropValue {
+    fn from(value: OsString) -> Self {
+        Self::OsString(value)
+    }
+}
+
+impl From<&Path> for PropValue {
+    fn from(value: &Path) -> Self {
+        value.as_os_str().into()
+    }
+}
+
+impl From<&PathBuf> for PropValue {
+    fn from(value: &PathBuf) -> Self {
+        value.as_path().into()
+    }
+}
+
+impl From<u64> for PropValue {
+    fn from(value: u64) -> Self {
+        Self::Int(value)
+    }
+}
+
+impl From<u32> for PropValue {
+    fn from(value: u32) -> Self {
+        Self::Int(value.into())
+    }
+}
+
+impl From<usize> for PropValue {
+    fn from(value: usize) -> Self {
+        Self::Int(value as u64)
+    }
+}
+
+impl From<bool> for PropValue {
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
+}
+
+impl From<Props> for PropValue {
+    fn from(value: Props) -> Self {
+        Self::Props(value)
+    }
+}
+
+impl<T> From<&ObjectId<T>> for PropValue {
+    fn from(value: &ObjectId<T>) -> Self {
+        Self::String(value.as_str().to_string())
+    }
+}
+
+impl<T> From<Vec<T>> for PropValue
+where
+    T: Into<PropValue>,
+{
+    fn from(value: Vec<T>) -> Self {
+        Self::List(value.into_iter().map(Into::into).collect())
+    }
+}
+
+/// Converts a field of an option into a [`PropValue`].
+///
+/// This is used by [`derive@QemuArgument`](crate::args::QemuArgument),
+/// and implemented for enums by [`derive@QemuValue`](crate::args::QemuValue).
+pub trait ToPropValue {
+    fn to_prop_value(&self) -> PropValue;
+}
+
+macro_rules! impl_to_prop_value {
+    ($($ty:ty),*) => {
+        $(
+            impl ToPropValue for $ty {
+                fn to_prop_value(&self) -> PropValue {
+                    self.clone().into()
+                }
+            }
+        )*
+    };
+}
+
+impl_to_prop_value!(String, OsString, u32, u64, usize, bool, Props);
+
+impl ToPropValue for u8 {
+    fn to_prop_value(&self) -> PropValue {
+        u32::from(*self).into()
+    }
+}
+
+impl ToPropValue for u16 {
+    fn to_prop_value(&self) -> PropValue {
+        u32::from(*self).into()
+    }
+}
+
+impl ToPropValue for PathBuf {
+    fn to_prop_value(&self) -> PropValue {
+        self.into()
+    }
+}
+
+impl<T> ToPropValue for ObjectId<T> {
+    fn to_prop_value(&self) -> PropValue {
+        self.into()
+    }
+}
+
+impl<T> ToPropValue for Vec<T>
+where
+    T: ToPropValue,
+{
+    fn to_prop_value(&self) -> PropValue {
+        PropValue::List(self.iter().map(ToPropValue::to_prop_value).collect())
+    }
+}
+
+/// An ordered list of properties of a structured option, that can be
+/// rendered in any [`ArgSyntax`].
+#[derive(Default, Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Props {
+    entries: Vec<(String, PropValue)>,
+}
+
+impl Props {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn push(&mut self, key: impl ToString, value: impl Into<PropValue>) -> &mut Self {
+        self.entries.push((key.to_string(), value.into()));
+        self
+    }
+
+    /// Pushes the property if `value` is `Some`.
+    pub fn push_opt<V>(&mut self, key: impl ToString, value: Option<V>) -> &mut Self
+    where
+        V: Into<PropValue>,
+    {
+        if let Some(value) = value {
+            self.push(key, value);
+        }
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the value of the first property with the given key.
+    pub fn get(&self, key: &str) -> Option<&PropValue> {
+        self.entries
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| value)
+    }
+
+    /// Renders the properties in the given syntax.
+    ///
+    /// In key-value syntax, the value of the `implied` key is rendered
+    /// first and without its key, like the driver in `-device virtio-rng-pci,...`.
+    /// Commas in values are escaped by doubling them.
+    pub fn render(
+        &self,
+        syntax: ArgSyntax,
+        implied: Option<&str>,
+    ) -> Result<OsString, InvalidValue> {
+        match syntax {
+            ArgSyntax::KeyValue => self.to_key_value(implied),
+            ArgSyntax::Json => Ok(self.to_json()?.into()),
+        }
+    }
+
+    fn to_key_value(&self, implied: Option<&str>) -> Result<OsString, InvalidValue> {
+        let mut parts = Vec::new();
+        if let Some(implied) = implied {
+            for (key, value) in &self.entries {
+                if key == implied {
+                    value.push_key_value(None, &mut parts)?;
+                }
+            }
+        }
+        for (key, value) in &self.entries {
+            if Some(key.as_str()) != implied {
+                value.push_key_value(Some(key), &mut parts)?;
+            }
+        }
+        let mut res = OsString::new();
+        for (i, part) in parts.iter().enumerate() {
+            if i > 0 {
+                res.push(",");
+            }
+            res.push(part);
+        }
+        Ok(res)
+    }
+
+    fn to_json(&self) -> Result<String, InvalidValue> {
+        let entries = self
+            .entries
+            .iter()
+            .map(|(key, value)| {
+                Ok(format!(
+                    "{}:{}",
+                    json_string(check_arg(key)?),
+                    value.to_json()?
+                ))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(format!("{{{}}}", entries.join(",")))
+    }
+}
+
+impl PropValue {
+    /// Renders a single value as an argument of its own, such as the
+    /// path in `-bios <path>`. Such arguments aren't escaped.
+    pub fn render_arg(&self) -> Result<OsString, InvalidValue> {
+        match self {
+            Self::String(s) => os_arg(s),
+            Self::OsString(s) => os_arg(s),
+            Self::Int(i) => Ok(i.to_string().into()),
+            Self::Bool(b) => Ok(on_off(*b).into()),
+            Self::List(_) | Self::Props(_) => Err(InvalidValue::new(
+                format!("{:?}", self),
+                "only single values can be rendered as an argument",
+            )),
+        }
+    }
+
+    fn push_key_value(
+        &self,
+        key: Option<&str>,
+        parts: &mut Vec<OsString>,
+    ) -> Result<(), InvalidValue> {
+        let prefix = match key {
+            Some(key) => format!("{}=", check_key(key)?),
+            None => String::new(),
+        };
+        let mut push = |value: OsString| {
+            let mut part = OsString::from(&prefix);
+            part.push(value);
+            parts.push(part);
+        };
+        match self {
+            Self::String(s) => push(escape(OsStr::new(s), key.is_none())?),
+            Self::OsString(s) => push(escape(s, key.is_none())?),
+            Self::Int(i) => push(i.to_string().into()),
+            Self::Bool(b) => push(on_off(*b).into()),
+            Self::List(values) => {
+                for value in values {
+                    value.push_key_value(key, parts)?;
+                }
+            }
+            Self::Props(props) => {
+                for (inner, value) in &props.entries {
+                    let inner = match key {
+                        Some(key) => format!("{}.{}", key, inner),
+                        None => inner.clone(),
+                    };
+                    value.push_key_value(Some(&inner), parts)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn to_json(&self) -> Result<String, InvalidValue> {
+        Ok(match self {
+            Self::String(s) => json_string(check_arg(s)?),
+            Self::OsString(s) => match s.to_str() {
+                Some(s) => json_string(check_arg(s)?),
+                None => return Err(InvalidValue::new(s, "JSON values must be valid UTF-8")),
+            },
+            Self::Int(i) => i.to_string(),
+            Self::Bool(b) => b.to_string(),
+            Self::List(values) => {
+                let values = values
+                    .iter()
+                    .map(|v| v.to_json())
+                    .collect::<Result<Vec<_>, _>>()?;
+                format!("[{}]", values.join(","))
+            }
+            Self::Props(props) => props.to_json()?,
+        })
+    }
+}
+
+/// A value that cannot be represented on the QEMU command line.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct InvalidValue {
+    pub value: String,
+    pub reason: &'static str,
+}
+
+impl InvalidValue {
+    pub(crate) fn new(value: impl AsRef<OsStr>, reason: &'static str) -> Self {
+        Self {
+            value: value.as_ref().to_string_lossy().into_owned(),
+            reason,
+        }
+    }
+}
+
+impl Display for InvalidValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid value {:?}: {}", self.value, self.reason)
+    }
+}
+
+impl std::error::Error for InvalidValue {}
+
+/// Checks that `value` can be passed as a command line argument.
+pub(crate) fn check_arg(value: &str) -> Result<&str, InvalidValue> {
+    if value.contains('\0') {
+        return Err(InvalidValue::new(
+            value,
+            "arguments must not contain NUL bytes",
+        ));
+    }
+    Ok(value)
+}
+
+/// Checks that `value` can be passed as a command line argument, and
+/// converts it into one.
+pub(crate) fn os_arg(value: impl AsRef<OsStr>) -> Result<OsString, InvalidValue> {
+    let value = value.as_ref();
+    if value.as_encoded_bytes().contains(&0) {
+        return Err(InvalidValue::new(
+            value,
+            "arguments must not contain NUL bytes",
+        ));
+    }
+    Ok(value.to_os_string())
+}
+
+/// Checks a key in key-value syntax, which can't be escaped, so that it
+/// can't inject further properties.
+fn check_key(key: &str) -> Result<&str, InvalidValue> {
+    if key.is_empty() || key.contains([',', '=']) {
+        return Err(InvalidValue::new(
+            key,
+            "keys must not be empty or contain ',' or '='",
+        ));
+    }
+    check_arg(key)
+}
+
+/// Escapes a value in key-value syntax, where a literal comma is
+/// written as two commas.
+///
+/// An implied value that contains a '=' would be parsed as a key,
+/// so it is rejected.
+fn escape(value: &OsStr, implied: bool) -> Result<OsString, InvalidValue> {
+    let value = os_arg(value)?;
+    let bytes = value.as_encoded_bytes();
+    if implied && bytes.contains(&b'=') {
+        return Err(InvalidValue::new(
+            &value,
+            "an implied value must not contain '='",
+        ));
+    }
+    if !bytes.contains(&b',') {
+        return Ok(value);
+    }
+    let mut escaped = Vec::with_capacity(bytes.len() + 1);
+    for &b in bytes {
+        escaped.push(b);
+        if b == b',' {
+            escaped.push(b',');
+        }
+    }
+    // SAFETY: the bytes come from an `OsStr`, and ASCII commas were
+    // only inserted right after existing ASCII commas.
+    Ok(unsafe { OsString::from_encoded_bytes_unchecked(escaped) })
+}
+
+/// Quotes a string for JSON, escaping quotes, backslashes and
+/// control characters.
+fn json_string(s: &str) -> String {
+    let mut res = String::with_capacity(s.len() + 2);
+    res.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => res.push_str("\\\""),
+            '\\' => res.push_str("\\\\"),
+            '\n' => res.push_str("\\n"),
+            '\r' => res.push_str("\\r"),
+            '\t' => res.push_str("\\t"),
+            '\u{8}' => res.push_str("\\b"),
+            '\u{c}' => res.push_str("\\f"),
+            c if c < ' ' => res.push_str(&format!("\\u{:04x}", u32::from(c))),
+            c => res.push(c),
+        }
+    }
+    res.push('"');
+    res
+}
+
+// Values are (de)serialized as the corresponding plain data, and
+// properties as a map that keeps the order of its entries.
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for PropValue {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::Error;
+
+        match self {
+            Self::String(s) => serializer.serialize_str(s),
+            Self::OsString(s) => match s.to_str() {
+                Some(s) => serializer.serialize_str(s),
+                None => Err(S::Error::custom(format!("non-UTF-8 value {:?}", s))),
+            },
+            Self::Int(i) => serializer.serialize_u64(*i),
+            Self::Bool(b) => serializer.serialize_bool(*b),
+            Self::List(list) => serializer.collect_seq(list),
+            Self::Props(props) => props.serialize(serializer),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for PropValue {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct Visitor;
+
+        impl<'de> serde::de::Visitor<'de> for Visitor {
+            type Value = PropValue;
+
+            fn expecting(&self, f: &mut Formatter) -> std::fmt::Result {
+                f.write_str("a string, unsigned integer, boolean, list or map")
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E> {
+                Ok(PropValue::String(v.to_string()))
+            }
+
+            fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E> {
+                Ok(PropValue::Int(v))
+            }
+
+            fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                u64::try_from(v).map(PropValue::Int).map_err(|_| {
+                    E::invalid_value(serde::de::Unexpected::Signed(v), &"an unsigned integer")
+                })
+            }
+
+            fn visit_bool<E>(self, v: bool) -> Result<Self::Value, E> {
+                Ok(PropValue::Bool(v))
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+            where
+                A: serde::de::SeqAccess<'de>,
+            {
+                let mut list = Vec::new();
+                while let Some(value) = seq.next_element()? {
+                    list.push(value);
+                }
+                Ok(PropValue::List(list))
+            }
+
+            fn visit_map<A>(self, map: A) -> Result<Self::Value, A::Error>
+            where
+                A: serde::de::MapAccess<'de>,
+            {
+                PropsVisitor.visit_map(map).map(PropValue::Props)
+            }
+        }
+
+        deserializer.deserialize_any(Visitor)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Props {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_map(self.entries.iter().map(|(k, v)| (k, v)))
+    }
+}
+
+#[cfg(feature = "serde")]
+struct PropsVisitor;
+
+#[cfg(feature = "serde")]
+impl<'de> serde::de::Visitor<'de> for PropsVisitor {
+    type Value = Props;
+
+    fn expecting(&self, f: &mut Formatter) -> std::fmt::Result {
+        f.write_str("a map of properties")
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: serde::de::MapAccess<'de>,
+    {
+        let mut props = Props::new();
+        while let Some((key, value)) = map.next_entry::<String, PropValue>()? {
+            props.push(key, value);
+        }
+        Ok(props)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Props {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        deserializer.deserialize_map(PropsVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn props() -> Props {
+        let mut file = Props::new();
+        file.push("driver", "file").push("filename", "disk.img");
+        let mut props = Props::new();
+        props
+            .push("driver", "qcow2")
+            .push("node-name", "disk0")
+            .push("read-only", true)
+            .push("file", file)
+            .push("host-nodes", vec![0u32, 2]);
+        props
+    }
+
+    #[test]
+    fn test_key_value() {
+        assert_eq!(
+            "driver=qcow2,node-name=disk0,read-only=on,file.driver=file,file.filename=disk.img,host-nodes=0,host-nodes=2",
+            props().render(ArgSyntax::KeyValue, None).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_key_value_implied() {
+        assert_eq!(
+            "qcow2,node-name=disk0,read-only=on,file.driver=file,file.filename=disk.img,host-nodes=0,host-nodes=2",
+            props().render(ArgSyntax::KeyValue, Some("driver")).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_json() {
+        assert_eq!(
+            r#"{"driver":"qcow2","node-name":"disk0","read-only":true,"file":{"driver":"file","filename":"disk.img"},"host-nodes":[0,2]}"#,
+            props().render(ArgSyntax::Json, Some("driver")).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_json_escaping() {
+        let mut props = Props::new();
+        props.push("filename", "a \"b\",c.img");
+        assert_eq!(
+            r#"{"filename":"a \"b\",c.img"}"#,
+            props.render(ArgSyntax::Json, None).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_json_string() {
+        assert_eq!(r#""a\\b\tc""#, json_string("a\\b\tc"));
+        assert_eq!(r#""\u001b[0m\n""#, json_string("\u{1b}[0m\n"));
+        assert_eq!(r#""größe""#, json_string("größe"));
+    }
+
+    #[test]
+    fn test_invalid_keys() {
+        for key in ["id=x,share", "share=on", ""] {
+            let mut props = Props::new();
+            props.push("driver", "e1000").push(key, "x");
+            assert!(props.render(ArgSyntax::KeyValue, Some("driver")).is_err());
+        }
+
+        let mut inner = Props::new();
+        inner.push("a,b", 1u32);
+        let mut props = Props::new();
+        props.push("file", inner);
+        assert!(props.render(ArgSyntax::KeyValue, None).is_err());
+
+        // JSON keys are quoted, so only NUL bytes are rejected
+        let mut props = Props::new();
+        props.push("a,b=c", 1u32);
+        assert!(props.render(ArgSyntax::Json, None).is_ok());
+        props.push("a\0", 1u32);
+        assert!(props.render(ArgSyntax::Json, None).is_err());
+    }
+
+    #[derive(crate::args::QemuValue)]
+    enum Mode {
+        ReadOnly,
+        #[qemu(rename = "rw")]
+        ReadWrite,
+    }
+
+    #[derive(crate::args::QemuArgument)]
+    #[qemu(flag = "-device", json, implied = "driver", value = "test-dev")]
+    struct TestDevice {
+        #[qemu(rename = "id")]
+        name: String,
+        mode: Mode,
+        max_size: Option<u64>,
+        queues: Vec<u32>,
+        #[qemu(skip)]
+        _internal: bool,
+    }
+
+    #[test]
+    fn test_derive() {
+        use crate::args::QemuArgument;
+
+        let device = TestDevice {
+            name: "a,b".to_string(),
+            mode: Mode::ReadOnly,
+            max_size: None,
+            queues: vec![1, 2],
+            _internal: true,
+        };
+        assert_eq!(
+            &[
+                "-device",
+                "test-dev,id=a,,b,mode=read-only,queues=1,queues=2"
+            ],
+            device.format().unwrap().as_slice()
+        );
+
+        let device = TestDevice {
+            mode: Mode::ReadWrite,
+            max_size: Some(4096),
+            ..device
+        };
+        assert_eq!(
+            &[
+                "-device",
+                r#"{"driver":"test-dev","id":"a,b","mode":"rw","max-size":4096,"queues":[1,2]}"#
+            ],
+            device.format_with(ArgSyntax::Json).unwrap().as_slice()
+        );
+    }
+}
@@ -0,0 +1,145 @@
+use crate::args::{
+    unknown_qom_type, Chardev, KeyValues, ObjectId, ParseError, Props, QemuArgument,
+};
+use std::path::PathBuf;
+
+/// A random number generator backend.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub struct Rng {
+    pub id: ObjectId<Rng>,
+    pub kind: RngKind,
+}
+
+impl Rng {
+    pub fn new(id: impl ToString, kind: RngKind) -> Self {
+        Self {
+            id: ObjectId::new(id),
+            kind,
+        }
+    }
+
+    pub(crate) fn props(&self) -> Props {
+        let mut props = Props::new();
+        match &self.kind {
+            RngKind::Random { filename } => {
+                props
+                    .push("qom-type", "rng-random")
+                    .push("id", &self.id)
+                    .push_opt("filename", filename.as_ref());
+            }
+            RngKind::Builtin => {
+                props.push("qom-type", "rng-builtin").push("id", &self.id);
+            }
+            RngKind::Egd { chardev } => {
+                props
+                    .push("qom-type", "rng-egd")
+                    .push("id", &self.id)
+                    .push("chardev", chardev);
+            }
+        }
+        props
+    }
+
+    /// Parses the properties of [`Self::props`] after the `qom-type`.
+    pub(crate) fn from_props(qom_type: &str, props: &mut KeyValues) -> Result<Self, ParseError> {
+        let id = props.required("id")?;
+        let kind = match qom_type {
+            "rng-random" => RngKind::Random {
+                filename: props.take("filename")?,
+            },
+            "rng-builtin" => RngKind::Builtin,
+            "rng-egd" => RngKind::Egd {
+                chardev: props.required("chardev")?,
+            },
+            _ => return Err(unknown_qom_type(qom_type)),
+        };
+        Ok(Self { id, kind })
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum RngKind {
+    /// `rng-random`, reads from a host device, `/dev/urandom` by default.
+    Random { filename: Option<PathBuf> },
+    /// `rng-builtin`, uses QEMU's internal random number generator.
+    Builtin,
+    /// `rng-egd`, reads from an EGD daemon connected to the given chardev.
+    Egd { chardev: ObjectId<Chardev> },
+}
+
+/// A virtio-rng device, added via the `-device` option.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, QemuArgument)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+#[qemu(flag = "-device", json, implied = "driver", value = "virtio-rng-pci")]
+pub struct VirtioRng {
+    pub rng: ObjectId<Rng>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Generic;
+    use crate::Qemu;
+
+    #[test]
+    fn test_rng_random() {
+        let mut qemu = Qemu::<Generic>::new();
+        let rng = Rng::new(
+            "rng0",
+            RngKind::Random {
+                filename: Some(PathBuf::from("/dev/urandom")),
+            },
+        );
+        let id = rng.id.clone();
+        qemu.object(rng);
+        qemu.virtio_rng(VirtioRng { rng: id });
+
+        let args = qemu.args().unwrap();
+        assert_eq!(
+            &[
+                "-object",
+                "rng-random,id=rng0,filename=/dev/urandom",
+                "-device",
+                "virtio-rng-pci,rng=rng0"
+            ],
+            args.as_slice()
+        );
+    }
+
+    #[test]
+    fn test_rng_builtin_and_egd() {
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.object(Rng::new("rng0", RngKind::Builtin));
+        qemu.object(Rng::new(
+            "rng1",
+            RngKind::Egd {
+                chardev: ObjectId::new("chr0"),
+            },
+        ));
+
+        let args = qemu.args().unwrap();
+        assert_eq!(
+            &[
+                "-object",
+                "rng-builtin,id=rng0",
+                "-object",
+                "rng-egd,id=rng1,chardev=chr0"
+            ],
+            args.as_slice()
+        );
+    }
+}
@@ -0,0 +1,133 @@
+use crate::args::{KeyValues, ObjectId, ParseError, Props, QemuValue, ToPropValue};
+use std::path::PathBuf;
+
+/// A secret, for example a disk encryption passphrase or a TLS key password.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub struct Secret {
+    pub id: ObjectId<Secret>,
+    pub source: SecretSource,
+    pub format: Option<SecretFormat>,
+    /// Another secret that is used to decrypt this one.
+    pub keyid: Option<ObjectId<Secret>>,
+    /// The base64 encoded initialization vector for the decryption.
+    pub iv: Option<String>,
+}
+
+impl Secret {
+    pub fn new(id: impl ToString, source: SecretSource) -> Self {
+        Self {
+            id: ObjectId::new(id),
+            source,
+            format: None,
+            keyid: None,
+            iv: None,
+        }
+    }
+
+    pub(crate) fn props(&self) -> Props {
+        let mut props = Props::new();
+        props.push("qom-type", "secret").push("id", &self.id);
+        match &self.source {
+            SecretSource::Data(data) => props.push("data", data),
+            SecretSource::File(file) => props.push("file", file),
+        };
+        props
+            .push_opt("format", self.format.map(|f| f.to_prop_value()))
+            .push_opt("keyid", self.keyid.as_ref())
+            .push_opt("iv", self.iv.as_ref());
+        props
+    }
+
+    /// Parses the properties of [`Self::props`] after the `qom-type`.
+    pub(crate) fn from_props(props: &mut KeyValues) -> Result<Self, ParseError> {
+        let id = props.required("id")?;
+        let source = match (props.take("data")?, props.take("file")?) {
+            (Some(data), None) => SecretSource::Data(data),
+            (None, Some(file)) => SecretSource::File(file),
+            _ => {
+                return Err(ParseError::unsupported(
+                    "-object",
+                    "a secret needs either data or file",
+                ))
+            }
+        };
+        Ok(Self {
+            id,
+            source,
+            format: props.take("format")?,
+            keyid: props.take("keyid")?,
+            iv: props.take("iv")?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum SecretSource {
+    /// The secret is passed inline. It will be visible in the process list,
+    /// so this should only be used for test data or encrypted secrets.
+    Data(String),
+    File(PathBuf),
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, QemuValue)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum SecretFormat {
+    Raw,
+    Base64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Generic;
+    use crate::Qemu;
+
+    #[test]
+    fn test_secret_file() {
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.object(Secret::new(
+            "sec0",
+            SecretSource::File(PathBuf::from("/run/pass")),
+        ));
+
+        let args = qemu.args().unwrap();
+        assert_eq!(
+            &["-object", "secret,id=sec0,file=/run/pass"],
+            args.as_slice()
+        );
+    }
+
+    #[test]
+    fn test_secret_encrypted() {
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.object(Secret {
+            format: Some(SecretFormat::Base64),
+            keyid: Some(ObjectId::new("master")),
+            iv: Some("0I7Gw/TKuA+Old2W2apQ3g==".to_string()),
+            ..Secret::new("sec0", SecretSource::Data("CdFJ2A==".to_string()))
+        });
+
+        let args = qemu.args().unwrap();
+        assert_eq!(
+            &[
+                "-object",
+                "secret,id=sec0,data=CdFJ2A==,format=base64,keyid=master,iv=0I7Gw/TKuA+Old2W2apQ3g=="
+            ],
+            args.as_slice()
+        );
+    }
+}
@@ -1,76 +1,63 @@
-use crate::args::QemuArgument;
+use crate::args::{os_arg, required_value, FromArg, InvalidValue, ParseError, QemuArgument};
 use crate::chardev::QemuCharDevice;
+use std::ffi::{OsStr, OsString};
 use std::path::PathBuf;
 
-#[derive(Default, Debug, Eq, PartialEq, Hash)]
+#[derive(Default, Debug, Copy, Clone, Eq, PartialEq, Hash, QemuArgument)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[qemu(flag = "--no-reboot")]
 pub struct NoReboot;
 
-impl QemuArgument for NoReboot {
-    fn format(&self) -> Vec<String> {
-        vec!["-no-reboot".to_string()]
-    }
-}
-
-#[derive(Default, Debug, Eq, PartialEq, Hash)]
+#[derive(Default, Debug, Copy, Clone, Eq, PartialEq, Hash, QemuArgument)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[qemu(flag = "--help")]
 pub struct Help;
 
-impl QemuArgument for Help {
-    fn format(&self) -> Vec<String> {
-        vec!["-help".to_string()]
-    }
-}
-
-#[derive(Default, Debug, Eq, PartialEq, Hash)]
+#[derive(Default, Debug, Copy, Clone, Eq, PartialEq, Hash, QemuArgument)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[qemu(flag = "--version")]
 pub struct Version;
 
-impl QemuArgument for Version {
-    fn format(&self) -> Vec<String> {
-        vec!["-version".to_string()]
-    }
-}
-
-#[derive(Default, Debug, Clone, Eq, PartialEq, Hash)]
+#[derive(Default, Debug, Clone, Eq, PartialEq, Hash, QemuArgument)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[qemu(flag = "-bios")]
 pub struct Bios(pub PathBuf);
 
-impl QemuArgument for Bios {
-    fn format(&self) -> Vec<String> {
-        vec!["-bios".to_string(), self.0.display().to_string()]
-    }
-}
-
-#[derive(Default, Debug, Eq, PartialEq, Hash)]
+#[derive(Default, Debug, Copy, Clone, Eq, PartialEq, Hash, QemuArgument)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[qemu(flag = "-fullscreen")]
 pub struct Fullscreen;
 
-impl QemuArgument for Fullscreen {
-    fn format(&self) -> Vec<String> {
-        vec!["-fullscreen".to_string()]
-    }
-}
-
-#[derive(Default, Debug, Eq, PartialEq, Hash)]
+#[derive(Default, Debug, Copy, Clone, Eq, PartialEq, Hash, QemuArgument)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[qemu(flag = "-S")]
 pub struct FreezeOnStartup;
 
-impl QemuArgument for FreezeOnStartup {
-    fn format(&self) -> Vec<String> {
-        vec!["-S".to_string()]
-    }
-}
-
-#[derive(Default, Debug, Clone, Eq, PartialEq, Hash)]
+#[derive(Default, Debug, Clone, Eq, PartialEq, Hash, QemuArgument)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[qemu(flag = "-gdb")]
 pub struct Gdb(pub String);
 
-impl QemuArgument for Gdb {
-    fn format(&self) -> Vec<String> {
-        vec!["-gdb".to_string(), self.0.clone()]
-    }
-}
-
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Serial(pub QemuCharDevice);
 
 impl QemuArgument for Serial {
-    fn format(&self) -> Vec<String> {
-        vec!["-serial".to_string(), self.0.format()]
+    fn format(&self) -> Result<Vec<OsString>, InvalidValue> {
+        Ok(vec!["-serial".into(), os_arg(self.0.format())?])
+    }
+}
+
+impl FromArg for Serial {
+    fn from_arg(value: Option<&OsStr>) -> Result<Self, ParseError> {
+        let value = required_value("-serial", value)?;
+        let device = match value.to_str() {
+            Some("stdio") => QemuCharDevice::Stdio,
+            Some("help") => QemuCharDevice::Help,
+            Some(id) => QemuCharDevice::Id(id.to_string()),
+            None => return Err(ParseError::invalid("-serial", "non-UTF-8 value")),
+        };
+        Ok(Self(device))
     }
 }
 
@@ -83,7 +70,7 @@ mod tests {
     #[test]
     fn test_empty_args() {
         let qemu = Qemu::<Generic>::new();
-        let args = qemu.args();
+        let args = qemu.args().unwrap();
         assert!(args.is_empty());
     }
 
@@ -92,7 +79,7 @@ mod tests {
         let mut qemu = Qemu::<Generic>::new();
         qemu.no_reboot();
 
-        let args = qemu.args();
+        let args = qemu.args().unwrap();
         assert_eq!(&["--no-reboot"], args.as_slice());
     }
 
@@ -101,7 +88,7 @@ mod tests {
         let mut qemu = Qemu::<Generic>::new();
         qemu.help();
 
-        let args = qemu.args();
+        let args = qemu.args().unwrap();
         assert_eq!(&["--help"], args.as_slice());
     }
 
@@ -110,7 +97,7 @@ mod tests {
         let mut qemu = Qemu::<Generic>::new();
         qemu.version();
 
-        let args = qemu.args();
+        let args = qemu.args().unwrap();
         assert_eq!(&["--version"], args.as_slice());
     }
 
@@ -119,7 +106,7 @@ mod tests {
         let mut qemu = Qemu::<Generic>::new();
         qemu.fullscreen();
 
-        let args = qemu.args();
+        let args = qemu.args().unwrap();
         assert_eq!(&["-fullscreen"], args.as_slice());
     }
 
@@ -128,7 +115,7 @@ mod tests {
         let mut qemu = Qemu::<Generic>::new();
         qemu.freeze_on_startup();
 
-        let args = qemu.args();
+        let args = qemu.args().unwrap();
         assert_eq!(&["-S"], args.as_slice());
     }
 
@@ -137,7 +124,7 @@ mod tests {
         let mut qemu = Qemu::<Generic>::new();
         qemu.gdb(&"tcp:1235");
 
-        let args = qemu.args();
+        let args = qemu.args().unwrap();
         assert_eq!(&["-gdb", "tcp:1235"], args.as_slice());
     }
 
@@ -146,7 +133,72 @@ mod tests {
         let mut qemu = Qemu::<Generic>::new();
         qemu.serial(QemuCharDevice::Stdio);
 
-        let args = qemu.args();
+        let args = qemu.args().unwrap();
         assert_eq!(&["-serial", "stdio"], args.as_slice());
     }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_bios_non_utf8() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+        use std::path::Path;
+
+        let path = OsStr::from_bytes(b"bios-\xff.bin");
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.bios(Path::new(path).to_path_buf());
+
+        let args = qemu.args().unwrap();
+        assert_eq!(&[OsStr::new("-bios"), path], args.as_slice());
+    }
+
+    #[test]
+    fn test_display_args() {
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.gdb(&"tcp:1235");
+        qemu.other("-append").other("console=ttyS0 quiet");
+
+        let args = qemu.into_args().unwrap();
+        assert_eq!(
+            "-gdb tcp:1235 -append 'console=ttyS0 quiet'",
+            args.to_string()
+        );
+    }
+
+    #[test]
+    fn test_insertion_order() {
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.serial(QemuCharDevice::Stdio);
+        qemu.other("-serial").other("null");
+        qemu.freeze_on_startup();
+        qemu.other("-readconfig").other("vm.cfg");
+        qemu.gdb(&"tcp::1234");
+
+        let args = qemu.args().unwrap();
+        assert_eq!(
+            &[
+                "-serial",
+                "stdio",
+                "-serial",
+                "null",
+                "-S",
+                "-readconfig",
+                "vm.cfg",
+                "-gdb",
+                "tcp::1234"
+            ],
+            args.as_slice()
+        );
+    }
+
+    #[test]
+    fn test_set_again_keeps_position() {
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.gdb(&"tcp::1234");
+        qemu.freeze_on_startup();
+        qemu.gdb(&"tcp::1235");
+
+        let args = qemu.args().unwrap();
+        assert_eq!(&["-gdb", "tcp::1235", "-S"], args.as_slice());
+    }
 }
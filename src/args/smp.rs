@@ -0,0 +1,201 @@
+use crate::args::{InvalidValue, QemuArgument};
+
+/// The cpu topology, configured via the `-smp` option.
+#[derive(Default, Debug, Copy, Clone, Eq, PartialEq, Hash, QemuArgument)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default, rename_all = "kebab-case")
+)]
+#[qemu(flag = "-smp")]
+pub struct Smp {
+    pub cpus: Option<u32>,
+    pub maxcpus: Option<u32>,
+    pub sockets: Option<u32>,
+    pub dies: Option<u32>,
+    pub cores: Option<u32>,
+    pub threads: Option<u32>,
+}
+
+impl Smp {
+    /// The maximum number of cpus, including hotpluggable ones.
+    ///
+    /// If no explicit count is given, this is derived from the topology.
+    /// Fails if the topology overflows.
+    pub fn max_cpus(&self) -> Result<u32, InvalidValue> {
+        if let Some(maxcpus) = self.maxcpus {
+            return Ok(maxcpus);
+        }
+        let counts = [self.sockets, self.dies, self.cores, self.threads].map(|c| c.unwrap_or(1));
+        let topology = counts
+            .iter()
+            .try_fold(1u32, |acc, &count| acc.checked_mul(count))
+            .ok_or_else(|| {
+                InvalidValue::new(
+                    counts.map(|c| c.to_string()).join("*"),
+                    "the cpu topology overflows",
+                )
+            })?;
+        Ok(self.cpus.unwrap_or(topology).max(topology))
+    }
+
+    /// Completes the number of sockets, dies, cores and threads like QEMU
+    /// does. If both sockets and cores are missing, they stay `None`,
+    /// since QEMU derives one or the other depending on the machine type.
+    pub(crate) fn derived(&self) -> Smp {
+        let dies = self.dies.unwrap_or(1);
+        let Some(maxcpus) = self.maxcpus.or(self.cpus) else {
+            return Smp {
+                sockets: Some(self.sockets.unwrap_or(1)),
+                dies: Some(dies),
+                cores: Some(self.cores.unwrap_or(1)),
+                threads: Some(self.threads.unwrap_or(1)),
+                ..*self
+            };
+        };
+        let derive = |a: u32, b: u32| {
+            dies.checked_mul(a)
+                .and_then(|n| n.checked_mul(b))
+                .and_then(|n| maxcpus.checked_div(n))
+        };
+        let (sockets, cores, threads) = match (self.sockets, self.cores, self.threads) {
+            (Some(s), Some(c), t) => (Some(s), Some(c), t.or_else(|| derive(s, c))),
+            (Some(s), None, t) => {
+                let t = t.unwrap_or(1);
+                (Some(s), derive(s, t), Some(t))
+            }
+            (None, Some(c), t) => {
+                let t = t.unwrap_or(1);
+                (derive(c, t), Some(c), Some(t))
+            }
+            (None, None, t) => (None, None, Some(t.unwrap_or(1))),
+        };
+        Smp {
+            sockets,
+            dies: Some(dies),
+            cores,
+            threads,
+            ..*self
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Generic;
+    use crate::Qemu;
+
+    #[test]
+    fn test_smp() {
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.smp(Smp {
+            cpus: Some(4),
+            sockets: Some(2),
+            cores: Some(2),
+            threads: Some(1),
+            ..Default::default()
+        });
+
+        let args = qemu.args().unwrap();
+        assert_eq!(
+            &["-smp", "cpus=4,sockets=2,cores=2,threads=1"],
+            args.as_slice()
+        );
+    }
+
+    #[test]
+    fn test_max_cpus() {
+        assert_eq!(Ok(1), Smp::default().max_cpus());
+        assert_eq!(
+            Ok(8),
+            Smp {
+                sockets: Some(2),
+                cores: Some(2),
+                threads: Some(2),
+                ..Default::default()
+            }
+            .max_cpus()
+        );
+        assert_eq!(
+            Ok(16),
+            Smp {
+                cpus: Some(4),
+                maxcpus: Some(16),
+                ..Default::default()
+            }
+            .max_cpus()
+        );
+    }
+
+    #[test]
+    fn test_max_cpus_overflow() {
+        let smp = Smp {
+            sockets: Some(1 << 16),
+            cores: Some(1 << 16),
+            ..Default::default()
+        };
+        assert_eq!(
+            Err(InvalidValue::new(
+                "65536*1*65536*1",
+                "the cpu topology overflows"
+            )),
+            smp.max_cpus()
+        );
+    }
+
+    #[test]
+    fn test_derived() {
+        let derived = Smp::default().derived();
+        assert_eq!(
+            [Some(1), Some(1), Some(1), Some(1)],
+            [
+                derived.sockets,
+                derived.dies,
+                derived.cores,
+                derived.threads
+            ]
+        );
+
+        let derived = Smp {
+            cpus: Some(8),
+            sockets: Some(2),
+            ..Default::default()
+        }
+        .derived();
+        assert_eq!(
+            [Some(2), Some(1), Some(4), Some(1)],
+            [
+                derived.sockets,
+                derived.dies,
+                derived.cores,
+                derived.threads
+            ]
+        );
+
+        let derived = Smp {
+            maxcpus: Some(16),
+            sockets: Some(2),
+            cores: Some(4),
+            ..Default::default()
+        }
+        .derived();
+        assert_eq!(Some(2), derived.threads);
+
+        let derived = Smp {
+            cpus: Some(8),
+            threads: Some(2),
+            ..Default::default()
+        }
+        .derived();
+        assert_eq!(
+            [None, Some(1), None, Some(2)],
+            [
+                derived.sockets,
+                derived.dies,
+                derived.cores,
+                derived.threads
+            ]
+        );
+    }
+}
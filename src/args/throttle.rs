@@ -0,0 +1,125 @@
+use crate::args::{KeyValues, ObjectId, ParseError, Props};
+
+/// A throttle group. All drives in the same group share its I/O limits.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub struct ThrottleGroup {
+    pub id: ObjectId<ThrottleGroup>,
+    pub limits: ThrottleLimits,
+}
+
+impl ThrottleGroup {
+    pub(crate) fn props(&self) -> Props {
+        let mut props = Props::new();
+        props
+            .push("qom-type", "throttle-group")
+            .push("id", &self.id);
+        let limits = self.limits.props();
+        if !limits.is_empty() {
+            props.push("limits", limits);
+        }
+        props
+    }
+
+    /// Parses the properties of [`Self::props`] after the `qom-type`.
+    pub(crate) fn from_props(props: &mut KeyValues) -> Result<Self, ParseError> {
+        let id = props.required("id")?;
+        let mut nested = props.take_prefixed("limits");
+        let limits = ThrottleLimits {
+            iops_total: nested.take("iops-total")?,
+            iops_read: nested.take("iops-read")?,
+            iops_write: nested.take("iops-write")?,
+            bps_total: nested.take("bps-total")?,
+            bps_read: nested.take("bps-read")?,
+            bps_write: nested.take("bps-write")?,
+        };
+        nested.finish()?;
+        Ok(Self { id, limits })
+    }
+}
+
+#[derive(Default, Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default, rename_all = "kebab-case")
+)]
+pub struct ThrottleLimits {
+    pub iops_total: Option<u64>,
+    pub iops_read: Option<u64>,
+    pub iops_write: Option<u64>,
+    pub bps_total: Option<u64>,
+    pub bps_read: Option<u64>,
+    pub bps_write: Option<u64>,
+}
+
+impl ThrottleLimits {
+    pub(crate) fn props(&self) -> Props {
+        let mut props = Props::new();
+        props
+            .push_opt("iops-total", self.iops_total)
+            .push_opt("iops-read", self.iops_read)
+            .push_opt("iops-write", self.iops_write)
+            .push_opt("bps-total", self.bps_total)
+            .push_opt("bps-read", self.bps_read)
+            .push_opt("bps-write", self.bps_write);
+        props
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::args::ArgSyntax;
+    use crate::Generic;
+    use crate::Qemu;
+
+    #[test]
+    fn test_throttle_group() {
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.object(ThrottleGroup {
+            id: ObjectId::new("limits0"),
+            limits: ThrottleLimits {
+                iops_total: Some(100),
+                bps_write: Some(1 << 20),
+                ..Default::default()
+            },
+        });
+
+        let args = qemu.args().unwrap();
+        assert_eq!(
+            &[
+                "-object",
+                "throttle-group,id=limits0,limits.iops-total=100,limits.bps-write=1048576"
+            ],
+            args.as_slice()
+        );
+    }
+
+    #[test]
+    fn test_throttle_group_json() {
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.arg_syntax(ArgSyntax::Json);
+        qemu.object(ThrottleGroup {
+            id: ObjectId::new("limits0"),
+            limits: ThrottleLimits {
+                iops_total: Some(100),
+                bps_write: Some(1 << 20),
+                ..Default::default()
+            },
+        });
+
+        let args = qemu.args().unwrap();
+        assert_eq!(
+            &[
+                "-object",
+                r#"{"qom-type":"throttle-group","id":"limits0","limits":{"iops-total":100,"bps-write":1048576}}"#
+            ],
+            args.as_slice()
+        );
+    }
+}
@@ -0,0 +1,152 @@
+use crate::args::{
+    unknown_qom_type, KeyValues, ObjectId, ParseError, Props, QemuValue, Secret, ToPropValue,
+};
+use std::path::PathBuf;
+
+/// TLS credentials, used by network services such as VNC, migration
+/// or socket chardevs.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub struct TlsCreds {
+    pub id: ObjectId<TlsCreds>,
+    pub kind: TlsCredsKind,
+    pub endpoint: TlsEndpoint,
+    /// The directory that contains the certificates or keys.
+    pub dir: PathBuf,
+}
+
+impl TlsCreds {
+    pub(crate) fn props(&self) -> Props {
+        let mut props = Props::new();
+        let qom_type = match &self.kind {
+            TlsCredsKind::X509 { .. } => "tls-creds-x509",
+            TlsCredsKind::Psk { .. } => "tls-creds-psk",
+        };
+        props
+            .push("qom-type", qom_type)
+            .push("id", &self.id)
+            .push("endpoint", self.endpoint.to_prop_value())
+            .push("dir", &self.dir);
+        match &self.kind {
+            TlsCredsKind::X509 {
+                verify_peer,
+                passwordid,
+            } => {
+                props
+                    .push_opt("verify-peer", *verify_peer)
+                    .push_opt("passwordid", passwordid.as_ref());
+            }
+            TlsCredsKind::Psk { username } => {
+                props.push_opt("username", username.as_ref());
+            }
+        }
+        props
+    }
+
+    /// Parses the properties of [`Self::props`] after the `qom-type`.
+    pub(crate) fn from_props(qom_type: &str, props: &mut KeyValues) -> Result<Self, ParseError> {
+        let id = props.required("id")?;
+        let endpoint = props.required("endpoint")?;
+        let dir = props.required("dir")?;
+        let kind = match qom_type {
+            "tls-creds-x509" => TlsCredsKind::X509 {
+                verify_peer: props.take("verify-peer")?,
+                passwordid: props.take("passwordid")?,
+            },
+            "tls-creds-psk" => TlsCredsKind::Psk {
+                username: props.take("username")?,
+            },
+            _ => return Err(unknown_qom_type(qom_type)),
+        };
+        Ok(Self {
+            id,
+            kind,
+            endpoint,
+            dir,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum TlsCredsKind {
+    /// `tls-creds-x509`, x509 certificates.
+    X509 {
+        verify_peer: Option<bool>,
+        /// The secret that decrypts the private key.
+        passwordid: Option<ObjectId<Secret>>,
+    },
+    /// `tls-creds-psk`, pre-shared keys.
+    Psk { username: Option<String> },
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, QemuValue)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum TlsEndpoint {
+    Server,
+    Client,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Generic;
+    use crate::Qemu;
+
+    #[test]
+    fn test_tls_creds_x509() {
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.object(TlsCreds {
+            id: ObjectId::new("tls0"),
+            kind: TlsCredsKind::X509 {
+                verify_peer: Some(true),
+                passwordid: Some(ObjectId::new("sec0")),
+            },
+            endpoint: TlsEndpoint::Server,
+            dir: PathBuf::from("/etc/pki/qemu"),
+        });
+
+        let args = qemu.args().unwrap();
+        assert_eq!(
+            &[
+                "-object",
+                "tls-creds-x509,id=tls0,endpoint=server,dir=/etc/pki/qemu,verify-peer=on,passwordid=sec0"
+            ],
+            args.as_slice()
+        );
+    }
+
+    #[test]
+    fn test_tls_creds_psk() {
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.object(TlsCreds {
+            id: ObjectId::new("tls0"),
+            kind: TlsCredsKind::Psk {
+                username: Some("qemu".to_string()),
+            },
+            endpoint: TlsEndpoint::Client,
+            dir: PathBuf::from("/etc/keys"),
+        });
+
+        let args = qemu.args().unwrap();
+        assert_eq!(
+            &[
+                "-object",
+                "tls-creds-psk,id=tls0,endpoint=client,dir=/etc/keys,username=qemu"
+            ],
+            args.as_slice()
+        );
+    }
+}
@@ -0,0 +1,145 @@
+use crate::args::{
+    leading_value, ArgSyntax, InvalidValue, KeyValues, ParseError, Props, QemuArgument, QemuValue,
+    ToPropValue,
+};
+use std::ffi::{OsStr, OsString};
+use std::path::PathBuf;
+
+/// A TPM backed by an external emulator such as `swtpm`.
+///
+/// This expands to a `-chardev socket`, a `-tpmdev emulator` and a
+/// `-device` front-end. The socket must be the control socket of a
+/// running emulator, see [`crate::swtpm::Swtpm`].
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub struct Tpm {
+    pub id: String,
+    pub socket: PathBuf,
+    pub model: TpmModel,
+}
+
+impl Tpm {
+    pub fn new(socket: PathBuf, model: TpmModel) -> Self {
+        Self {
+            id: "tpm0".to_string(),
+            socket,
+            model,
+        }
+    }
+}
+
+impl QemuArgument for Tpm {
+    fn format(&self) -> Result<Vec<OsString>, InvalidValue> {
+        self.format_with(ArgSyntax::KeyValue)
+    }
+
+    fn format_with(&self, syntax: ArgSyntax) -> Result<Vec<OsString>, InvalidValue> {
+        let chardev_id = format!("chr{}", self.id);
+        let mut chardev = Props::new();
+        chardev
+            .push("backend", "socket")
+            .push("id", &chardev_id)
+            .push("path", &self.socket);
+        let mut tpmdev = Props::new();
+        tpmdev
+            .push("type", "emulator")
+            .push("id", &self.id)
+            .push("chardev", &chardev_id);
+        let mut device = Props::new();
+        device
+            .push("driver", self.model.to_prop_value())
+            .push("tpmdev", &self.id);
+        Ok(vec![
+            "-chardev".into(),
+            chardev.render(ArgSyntax::KeyValue, Some("backend"))?,
+            "-tpmdev".into(),
+            tpmdev.render(ArgSyntax::KeyValue, Some("type"))?,
+            "-device".into(),
+            device.render(syntax, Some("driver"))?,
+        ])
+    }
+}
+
+impl Tpm {
+    /// Parses the options of [`Self::format`] at the start of `args`,
+    /// and returns the TPM with the number of arguments it spans.
+    pub(crate) fn parse_leading(args: &[OsString]) -> Option<(Self, usize)> {
+        let chardev = leading_value(args, "-chardev")?;
+        let tpmdev = leading_value(&args[2..], "-tpmdev")?;
+        let device = leading_value(&args[4..], "-device")?;
+        Self::parse(chardev, tpmdev, device)
+            .ok()
+            .map(|tpm| (tpm, 6))
+    }
+
+    fn parse(chardev: &OsStr, tpmdev: &OsStr, device: &OsStr) -> Result<Self, ParseError> {
+        let mut tpmdev = KeyValues::parse("-tpmdev", tpmdev, Some("type"))?;
+        tpmdev.expect("type", "emulator")?;
+        let id: String = tpmdev.required("id")?;
+        let chardev_id = format!("chr{}", id);
+        tpmdev.expect("chardev", &chardev_id)?;
+        tpmdev.finish()?;
+
+        let mut chardev = KeyValues::parse("-chardev", chardev, Some("backend"))?;
+        chardev.expect("backend", "socket")?;
+        chardev.expect("id", &chardev_id)?;
+        let socket = chardev.required("path")?;
+        chardev.finish()?;
+
+        let mut device = KeyValues::parse("-device", device, Some("driver"))?;
+        let model = device.required("driver")?;
+        device.expect("tpmdev", &id)?;
+        device.finish()?;
+
+        Ok(Self { id, socket, model })
+    }
+}
+
+#[derive(Default, Debug, Copy, Clone, Eq, PartialEq, Hash, QemuValue)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum TpmModel {
+    /// TPM TIS interface on the ISA bus, for x86 machines.
+    #[default]
+    #[qemu(rename = "tpm-tis")]
+    Tis,
+    /// TPM CRB interface, for x86 machines. Only supports TPM 2.0.
+    #[qemu(rename = "tpm-crb")]
+    Crb,
+    /// TPM TIS interface as a sysbus device, for arm `virt` machines.
+    #[qemu(rename = "tpm-tis-device")]
+    TisDevice,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Generic;
+    use crate::Qemu;
+
+    #[test]
+    fn test_tpm() {
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.tpm(Tpm::new(PathBuf::from("/tmp/swtpm-sock"), TpmModel::Crb));
+
+        let args = qemu.args().unwrap();
+        assert_eq!(
+            &[
+                "-chardev",
+                "socket,id=chrtpm0,path=/tmp/swtpm-sock",
+                "-tpmdev",
+                "emulator,id=tpm0,chardev=chrtpm0",
+                "-device",
+                "tpm-crb,tpmdev=tpm0"
+            ],
+            args.as_slice()
+        );
+    }
+}
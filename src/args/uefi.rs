@@ -0,0 +1,343 @@
+use crate::args::{
+    leading_value, ArgSyntax, Format, InvalidValue, KeyValues, ParseError, Props, QemuArgument,
+    ToPropValue,
+};
+use crate::tmp::TempFile;
+use std::ffi::{OsStr, OsString};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// UEFI firmware that is split into a read-only CODE and a writable
+/// VARS flash image, such as OVMF or AAVMF.
+///
+/// The VARS file is only used as a template, see [`Self::prepare`].
+#[derive(Default, Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default, rename_all = "kebab-case")
+)]
+pub struct Uefi {
+    pub code: PathBuf,
+    pub vars_template: PathBuf,
+    /// The format of both the CODE and the VARS image.
+    pub format: Format,
+    /// Whether the firmware is a secure boot build. On x86_64, such
+    /// builds require SMM and a secure flash, which will be enabled.
+    pub secure_boot: bool,
+}
+
+impl Uefi {
+    /// Copies the VARS template into a temporary file, so that parallel
+    /// runs don't share their variable store.
+    ///
+    /// The copy is removed when the returned [`UefiFirmware`] is dropped,
+    /// so it must outlive the QEMU process that uses it.
+    pub fn prepare(&self) -> io::Result<UefiFirmware> {
+        let vars = TempFile::copy_from(&self.vars_template, "uefi-vars")?;
+        Ok(UefiFirmware {
+            code: self.code.clone(),
+            vars,
+            format: self.format,
+            secure_boot: self.secure_boot,
+        })
+    }
+}
+
+/// UEFI firmware with a private copy of the variable store.
+#[derive(Debug)]
+pub struct UefiFirmware {
+    code: PathBuf,
+    vars: TempFile,
+    format: Format,
+    secure_boot: bool,
+}
+
+impl UefiFirmware {
+    pub fn code(&self) -> &Path {
+        &self.code
+    }
+
+    /// The private copy of the variable store.
+    pub fn vars(&self) -> &Path {
+        self.vars.path()
+    }
+
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    pub fn secure_boot(&self) -> bool {
+        self.secure_boot
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub(crate) struct Pflash {
+    pub code: PathBuf,
+    pub vars: PathBuf,
+    pub format: Format,
+    pub secure: bool,
+}
+
+/// The `-global` option of a secure [`Pflash`].
+const SECURE_GLOBAL: &str = "driver=cfi.pflash01,property=secure,value=on";
+
+impl QemuArgument for Pflash {
+    fn format(&self) -> Result<Vec<OsString>, InvalidValue> {
+        let mut args = Vec::new();
+        if self.secure {
+            args.push("-global".into());
+            args.push(SECURE_GLOBAL.into());
+        }
+        let mut code = Props::new();
+        code.push("if", "pflash")
+            .push("format", self.format.to_prop_value())
+            .push("unit", 0u32)
+            .push("readonly", true)
+            .push("file", &self.code);
+        let mut vars = Props::new();
+        vars.push("if", "pflash")
+            .push("format", self.format.to_prop_value())
+            .push("unit", 1u32)
+            .push("file", &self.vars);
+        args.push("-drive".into());
+        args.push(code.render(ArgSyntax::KeyValue, None)?);
+        args.push("-drive".into());
+        args.push(vars.render(ArgSyntax::KeyValue, None)?);
+        Ok(args)
+    }
+}
+
+impl Pflash {
+    /// Parses the options of [`Self::format`] at the start of `args`,
+    /// and returns the pflash pair with the number of arguments it spans.
+    pub(crate) fn parse_leading(args: &[OsString]) -> Option<(Self, usize)> {
+        let (secure, skip) = match leading_value(args, "-global") {
+            Some(global) if global == SECURE_GLOBAL => (true, 2),
+            _ => (false, 0),
+        };
+        let code = leading_value(&args[skip..], "-drive")?;
+        let vars = leading_value(&args[skip + 2..], "-drive")?;
+        let (format, code) = Self::parse_drive(code, 0).ok()?;
+        let (vars_format, vars) = Self::parse_drive(vars, 1).ok()?;
+        if format != vars_format {
+            return None;
+        }
+        let pflash = Self {
+            code,
+            vars,
+            format,
+            secure,
+        };
+        Some((pflash, skip + 4))
+    }
+
+    /// Parses a `-drive` of [`Self::format`], the CODE if `unit` is 0
+    /// and the VARS otherwise.
+    fn parse_drive(value: &OsStr, unit: u32) -> Result<(Format, PathBuf), ParseError> {
+        let mut drive = KeyValues::parse("-drive", value, None)?;
+        drive.expect("if", "pflash")?;
+        let format = drive.required("format")?;
+        drive.expect("unit", &unit.to_string())?;
+        if unit == 0 {
+            drive.expect("readonly", "on")?;
+        }
+        let file = drive.required("file")?;
+        drive.finish()?;
+        Ok((format, file))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tmp;
+    use crate::{Aarch64, Qemu, X86_64};
+    use std::fs;
+
+    struct Fixture {
+        dir: PathBuf,
+        uefi: Uefi,
+    }
+
+    impl Fixture {
+        fn new() -> Self {
+            let dir = tmp::unique_path("uefi-test");
+            fs::create_dir_all(&dir).unwrap();
+            let code = dir.join("CODE.fd");
+            let vars_template = dir.join("VARS.fd");
+            fs::write(&code, b"code").unwrap();
+            fs::write(&vars_template, b"vars").unwrap();
+            Self {
+                dir,
+                uefi: Uefi {
+                    code,
+                    vars_template,
+                    format: Format::Raw,
+                    secure_boot: false,
+                },
+            }
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    #[test]
+    fn test_uefi_x86_64() {
+        let fixture = Fixture::new();
+        let firmware = fixture.uefi.prepare().unwrap();
+        assert_ne!(fixture.uefi.vars_template, firmware.vars());
+        assert_eq!(b"vars".as_slice(), fs::read(firmware.vars()).unwrap());
+
+        let mut qemu = Qemu::<X86_64>::new();
+        qemu.uefi(&firmware);
+
+        let args = qemu.args().unwrap();
+        assert_eq!(
+            &[
+                "-drive".to_string(),
+                format!(
+                    "if=pflash,format=raw,unit=0,readonly=on,file={}",
+                    fixture.uefi.code.display()
+                ),
+                "-drive".to_string(),
+                format!(
+                    "if=pflash,format=raw,unit=1,file={}",
+                    firmware.vars().display()
+                ),
+                "-machine".to_string(),
+                "q35".to_string(),
+            ]
+            .map(OsString::from),
+            args.as_slice()
+        );
+    }
+
+    #[test]
+    fn test_uefi_secure_boot() {
+        let fixture = Fixture::new();
+        let firmware = Uefi {
+            secure_boot: true,
+            ..fixture.uefi.clone()
+        }
+        .prepare()
+        .unwrap();
+        let mut qemu = Qemu::<X86_64>::new();
+        qemu.uefi(&firmware);
+
+        let args = qemu.args().unwrap();
+        let args = args.as_slice();
+        assert_eq!(
+            &[
+                "-global",
+                "driver=cfi.pflash01,property=secure,value=on",
+                "-drive"
+            ],
+            &args[..3]
+        );
+        assert_eq!(&["-machine", "q35,smm=on"], &args[args.len() - 2..]);
+    }
+
+    #[test]
+    fn test_uefi_aarch64() {
+        let fixture = Fixture::new();
+        let firmware = Uefi {
+            secure_boot: true,
+            ..fixture.uefi.clone()
+        }
+        .prepare()
+        .unwrap();
+        let mut qemu = Qemu::<Aarch64>::new();
+        qemu.uefi(&firmware);
+
+        let args = qemu.args().unwrap();
+        let args = args.as_slice();
+        assert_eq!("-drive", args[0]);
+        assert_eq!(&["-machine", "virt"], &args[args.len() - 2..]);
+    }
+
+    #[test]
+    fn test_uefi_qcow2() {
+        let fixture = Fixture::new();
+        let firmware = Uefi {
+            format: Format::Qcow2,
+            ..fixture.uefi.clone()
+        }
+        .prepare()
+        .unwrap();
+        assert_eq!(Format::Qcow2, firmware.format());
+        let mut qemu = Qemu::<X86_64>::new();
+        qemu.uefi(&firmware);
+
+        let args = qemu.args().unwrap();
+        assert_eq!(
+            &[
+                "-drive".to_string(),
+                format!(
+                    "if=pflash,format=qcow2,unit=0,readonly=on,file={}",
+                    fixture.uefi.code.display()
+                ),
+                "-drive".to_string(),
+                format!(
+                    "if=pflash,format=qcow2,unit=1,file={}",
+                    firmware.vars().display()
+                ),
+            ]
+            .map(OsString::from),
+            &args.as_slice()[..4]
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_uefi_readonly_template() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let fixture = Fixture::new();
+        let template = &fixture.uefi.vars_template;
+        fs::set_permissions(template, fs::Permissions::from_mode(0o444)).unwrap();
+        let firmware = fixture.uefi.prepare().unwrap();
+        fs::write(firmware.vars(), b"updated").unwrap();
+        assert_eq!(b"vars".as_slice(), fs::read(template).unwrap());
+    }
+
+    #[test]
+    fn test_uefi_vars_per_run() {
+        let fixture = Fixture::new();
+        let first = fixture.uefi.prepare().unwrap();
+        let second = fixture.uefi.prepare().unwrap();
+        assert_ne!(first.vars(), second.vars());
+    }
+
+    #[test]
+    fn test_uefi_vars_removed_on_drop() {
+        let fixture = Fixture::new();
+        let firmware = fixture.uefi.prepare().unwrap();
+        let vars = firmware.vars().to_path_buf();
+        assert!(vars.exists());
+
+        drop(firmware);
+        assert!(!vars.exists());
+    }
+
+    #[test]
+    fn test_uefi_missing_template() {
+        let uefi = Uefi {
+            code: PathBuf::from("/nonexistent/CODE.fd"),
+            vars_template: PathBuf::from("/nonexistent/VARS.fd"),
+            format: Format::Raw,
+            secure_boot: false,
+        };
+        assert!(uefi.prepare().is_err());
+    }
+}
@@ -0,0 +1,74 @@
+use crate::args::{AuthzSimple, ObjectId, QemuArgument, Secret, TlsCreds};
+
+/// A VNC server for the display, configured via the `-vnc` option.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, QemuArgument)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+#[qemu(flag = "-vnc", implied = "vnc")]
+pub struct Vnc {
+    /// The display to listen on, such as `:0`, `localhost:1` or
+    /// `unix:/run/vnc.sock`.
+    #[qemu(rename = "vnc")]
+    pub display: String,
+    /// The secret that holds the password of the clients.
+    pub password_secret: Option<ObjectId<Secret>>,
+    /// Encrypts the connections with the given credentials.
+    pub tls_creds: Option<ObjectId<TlsCreds>>,
+    /// Restricts the clients to the authorized x509 identities.
+    pub tls_authz: Option<ObjectId<AuthzSimple>>,
+    pub sasl: Option<bool>,
+    /// Restricts the clients to the authorized SASL usernames.
+    pub sasl_authz: Option<ObjectId<AuthzSimple>>,
+}
+
+impl Vnc {
+    pub fn new(display: impl ToString) -> Self {
+        Self {
+            display: display.to_string(),
+            password_secret: None,
+            tls_creds: None,
+            tls_authz: None,
+            sasl: None,
+            sasl_authz: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Generic;
+    use crate::Qemu;
+
+    #[test]
+    fn test_vnc() {
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.vnc(Vnc::new(":0"));
+
+        let args = qemu.args().unwrap();
+        assert_eq!(&["-vnc", ":0"], args.as_slice());
+    }
+
+    #[test]
+    fn test_vnc_tls() {
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.vnc(Vnc {
+            tls_creds: Some(ObjectId::new("tls0")),
+            tls_authz: Some(ObjectId::new("auth0")),
+            password_secret: Some(ObjectId::new("vncpass")),
+            ..Vnc::new("localhost:1")
+        });
+
+        let args = qemu.args().unwrap();
+        assert_eq!(
+            &[
+                "-vnc",
+                "localhost:1,password-secret=vncpass,tls-creds=tls0,tls-authz=auth0"
+            ],
+            args.as_slice()
+        );
+    }
+}
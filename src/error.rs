@@ -0,0 +1,92 @@
+use crate::args::InvalidValue;
+use crate::validate::Diagnostic;
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::path::PathBuf;
+use std::process::ExitStatus;
+use std::time::Duration;
+
+/// An error of this crate.
+#[derive(Debug)]
+pub enum Error {
+    /// The configuration can't be used, for example because a value
+    /// can't be represented on the command line.
+    InvalidConfig(Vec<Diagnostic>),
+    /// A binary, such as `qemu-system-x86_64` or `swtpm`, wasn't found or
+    /// isn't executable.
+    MissingBinary {
+        binary: PathBuf,
+        source: io::Error,
+    },
+    /// A process exited before it was ready, or with a failure status.
+    Startup {
+        binary: PathBuf,
+        status: ExitStatus,
+        stderr: String,
+    },
+    /// A process responded in a way that doesn't follow its protocol.
+    Protocol(String),
+    /// A process didn't become ready or finish in time.
+    Timeout {
+        binary: PathBuf,
+        timeout: Duration,
+    },
+    Io(io::Error),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidConfig(diagnostics) => {
+                write!(f, "invalid configuration")?;
+                for (i, diagnostic) in diagnostics.iter().enumerate() {
+                    let sep = if i == 0 { ": " } else { ", " };
+                    write!(f, "{}{}", sep, diagnostic)?;
+                }
+                Ok(())
+            }
+            Self::MissingBinary { binary, source } => match source.kind() {
+                io::ErrorKind::NotFound => write!(f, "{} not found", binary.display()),
+                _ => write!(f, "{} can't be run: {}", binary.display(), source),
+            },
+            Self::Startup {
+                binary,
+                status,
+                stderr,
+            } => {
+                write!(f, "{} failed with {}", binary.display(), status)?;
+                match stderr.trim() {
+                    "" => Ok(()),
+                    stderr => write!(f, ": {}", stderr),
+                }
+            }
+            Self::Protocol(msg) => write!(f, "protocol error: {}", msg),
+            Self::Timeout { binary, timeout } => {
+                write!(f, "{} timed out after {:?}", binary.display(), timeout)
+            }
+            Self::Io(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::MissingBinary { source, .. } => Some(source),
+            Self::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<InvalidValue> for Error {
+    fn from(e: InvalidValue) -> Self {
+        Self::InvalidConfig(vec![Diagnostic::InvalidValue(e)])
+    }
+}
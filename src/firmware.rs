@@ -0,0 +1,535 @@
+//! Discovery of firmware images via the firmware descriptor files
+//! that are described in QEMU's `docs/interop/firmware.json`.
+//!
+//! Distributions install these descriptors into directories such as
+//! `/usr/share/qemu/firmware`, so that firmware paths don't have to be
+//! hardcoded.
+//!
+//! This module requires the `firmware` feature.
+
+use crate::args::{Format, FromProp, Uefi};
+use crate::QemuSystem;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// A parsed firmware descriptor.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct FirmwareDescriptor {
+    #[serde(default)]
+    pub description: String,
+    pub interface_types: Vec<InterfaceType>,
+    pub mapping: FirmwareMapping,
+    pub targets: Vec<FirmwareTarget>,
+    #[serde(default)]
+    pub features: Vec<FirmwareFeature>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum InterfaceType {
+    Bios,
+    Openfirmware,
+    Uboot,
+    Uefi,
+}
+
+/// How the firmware is mapped into the guest.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize)]
+#[serde(tag = "device", rename_all = "kebab-case")]
+pub enum FirmwareMapping {
+    /// Loaded into pflash devices.
+    Flash(FlashMapping),
+    /// Loaded like a kernel via `-kernel`, for example OpenSBI.
+    Kernel { filename: PathBuf },
+    /// Loaded into guest memory via `-bios`, for example SeaBIOS.
+    Memory { filename: PathBuf },
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct FlashMapping {
+    #[serde(default)]
+    pub mode: FlashMode,
+    pub executable: FlashFile,
+    /// The template of the variable store, only present in split mode.
+    pub nvram_template: Option<FlashFile>,
+}
+
+#[derive(Default, Debug, Clone, Eq, PartialEq, Hash, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FlashMode {
+    #[default]
+    Split,
+    Combined,
+    Stateless,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize)]
+pub struct FlashFile {
+    pub filename: PathBuf,
+    pub format: String,
+}
+
+impl FlashFile {
+    /// The format of the image, if it can be used for a pflash drive.
+    fn flash_format(&self) -> Option<Format> {
+        match Format::from_prop(self.format.as_ref())? {
+            Format::Luks => None,
+            format => Some(format),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize)]
+pub struct FirmwareTarget {
+    pub architecture: String,
+    /// Glob patterns of the supported, versioned machine types,
+    /// for example `pc-q35-*`.
+    pub machines: Vec<String>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize)]
+#[serde(from = "String")]
+pub enum FirmwareFeature {
+    AcpiS3,
+    AcpiS4,
+    AmdSev,
+    AmdSevEs,
+    AmdSevSnp,
+    EnrolledKeys,
+    IntelTdx,
+    RequiresSmm,
+    SecureBoot,
+    VerboseDynamic,
+    VerboseStatic,
+    Other(String),
+}
+
+impl From<String> for FirmwareFeature {
+    fn from(value: String) -> Self {
+        match value.as_str() {
+            "acpi-s3" => Self::AcpiS3,
+            "acpi-s4" => Self::AcpiS4,
+            "amd-sev" => Self::AmdSev,
+            "amd-sev-es" => Self::AmdSevEs,
+            "amd-sev-snp" => Self::AmdSevSnp,
+            "enrolled-keys" => Self::EnrolledKeys,
+            "intel-tdx" => Self::IntelTdx,
+            "requires-smm" => Self::RequiresSmm,
+            "secure-boot" => Self::SecureBoot,
+            "verbose-dynamic" => Self::VerboseDynamic,
+            "verbose-static" => Self::VerboseStatic,
+            _ => Self::Other(value),
+        }
+    }
+}
+
+impl FirmwareDescriptor {
+    pub fn parse(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+
+    pub fn has_feature(&self, feature: &FirmwareFeature) -> bool {
+        self.features.contains(feature)
+    }
+
+    /// Whether this firmware supports the given architecture and,
+    /// if given, versioned machine type.
+    pub fn supports(&self, architecture: &str, machine: Option<&str>) -> bool {
+        self.targets.iter().any(|target| {
+            target.architecture == architecture
+                && machine.is_none_or(|machine| {
+                    target
+                        .machines
+                        .iter()
+                        .any(|glob| glob_matches(glob, machine))
+                })
+        })
+    }
+
+    /// Converts a split flash mapping into a [`Uefi`] configuration.
+    ///
+    /// Returns `None` for other mappings, and for images in a format
+    /// other than `raw` or `qcow2`, or in different formats.
+    pub fn to_uefi(&self) -> Option<Uefi> {
+        match &self.mapping {
+            FirmwareMapping::Flash(FlashMapping {
+                mode: FlashMode::Split,
+                executable,
+                nvram_template: Some(nvram_template),
+            }) if executable.format == nvram_template.format => Some(Uefi {
+                code: executable.filename.clone(),
+                vars_template: nvram_template.filename.clone(),
+                format: executable.flash_format()?,
+                secure_boot: self.has_feature(&FirmwareFeature::RequiresSmm),
+            }),
+            _ => None,
+        }
+    }
+
+    /// The file of a firmware that is loaded into memory, suitable
+    /// for [`Qemu::bios`](crate::Qemu::bios).
+    pub fn bios_file(&self) -> Option<&Path> {
+        match &self.mapping {
+            FirmwareMapping::Memory { filename } => Some(filename),
+            _ => None,
+        }
+    }
+}
+
+/// The directories that QEMU searches for descriptors, from highest to
+/// lowest priority.
+pub fn default_search_dirs() -> Vec<PathBuf> {
+    let mut dirs = vec![PathBuf::from("/etc/qemu/firmware")];
+    if let Some(config) = std::env::var_os("XDG_CONFIG_HOME") {
+        dirs.push(PathBuf::from(config).join("qemu/firmware"));
+    } else if let Some(home) = std::env::var_os("HOME") {
+        dirs.push(PathBuf::from(home).join(".config/qemu/firmware"));
+    }
+    dirs.push(PathBuf::from("/usr/share/qemu/firmware"));
+    dirs
+}
+
+/// Loads all descriptors from the given directories, which are ordered
+/// from highest to lowest priority.
+///
+/// Like QEMU, a descriptor shadows descriptors with the same file name in
+/// lower priority directories, and an empty file disables that name.
+/// The result is sorted by file name, which is the order of preference.
+/// Missing directories are skipped.
+pub fn load_descriptors(dirs: &[PathBuf]) -> io::Result<Vec<(PathBuf, FirmwareDescriptor)>> {
+    let mut files = BTreeMap::new();
+    for dir in dirs.iter().rev() {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                files.insert(path.file_name().unwrap().to_os_string(), path);
+            }
+        }
+    }
+
+    let mut descriptors = Vec::new();
+    for path in files.into_values() {
+        let content = fs::read_to_string(&path)?;
+        if content.trim().is_empty() {
+            continue;
+        }
+        let descriptor = FirmwareDescriptor::parse(&content)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        descriptors.push((path, descriptor));
+    }
+    Ok(descriptors)
+}
+
+/// Requirements for selecting a firmware.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct FirmwareQuery {
+    pub interface: InterfaceType,
+    /// A versioned machine type, for example `pc-q35-8.2`.
+    pub machine: Option<String>,
+    pub required_features: Vec<FirmwareFeature>,
+    pub excluded_features: Vec<FirmwareFeature>,
+}
+
+impl FirmwareQuery {
+    pub fn new(interface: InterfaceType) -> Self {
+        Self {
+            interface,
+            machine: None,
+            required_features: Vec::new(),
+            excluded_features: Vec::new(),
+        }
+    }
+
+    fn matches(&self, architecture: &str, descriptor: &FirmwareDescriptor) -> bool {
+        descriptor.interface_types.contains(&self.interface)
+            && descriptor.supports(architecture, self.machine.as_deref())
+            && self
+                .required_features
+                .iter()
+                .all(|f| descriptor.has_feature(f))
+            && !self
+                .excluded_features
+                .iter()
+                .any(|f| descriptor.has_feature(f))
+    }
+}
+
+/// Selects the most preferred descriptor in `dirs` that matches the
+/// `system` and the query.
+pub fn select<S>(
+    system: &S,
+    dirs: &[PathBuf],
+    query: &FirmwareQuery,
+) -> io::Result<Option<FirmwareDescriptor>>
+where
+    S: QemuSystem,
+{
+    Ok(load_descriptors(dirs)?
+        .into_iter()
+        .map(|(_, descriptor)| descriptor)
+        .find(|descriptor| query.matches(system.architecture(), descriptor)))
+}
+
+/// Matches `text` against a glob pattern that may contain `*` wildcards.
+fn glob_matches(pattern: &str, text: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == text,
+        Some((prefix, rest)) => {
+            let Some(text) = text.strip_prefix(prefix) else {
+                return false;
+            };
+            (0..=text.len())
+                .filter(|&i| text.is_char_boundary(i))
+                .any(|i| glob_matches(rest, &text[i..]))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tmp;
+    use crate::{Aarch64, X86_64};
+
+    const OVMF: &str = r#"{
+        "description": "OVMF for x86_64",
+        "interface-types": ["uefi"],
+        "mapping": {
+            "device": "flash",
+            "executable": {"filename": "/usr/share/OVMF/OVMF_CODE.fd", "format": "raw"},
+            "nvram-template": {"filename": "/usr/share/OVMF/OVMF_VARS.fd", "format": "raw"}
+        },
+        "targets": [{"architecture": "x86_64", "machines": ["pc-i440fx-*", "pc-q35-*"]}],
+        "features": ["acpi-s3", "verbose-dynamic"],
+        "tags": []
+    }"#;
+
+    const OVMF_SECURE: &str = r#"{
+        "description": "OVMF with secure boot",
+        "interface-types": ["uefi"],
+        "mapping": {
+            "device": "flash",
+            "mode": "split",
+            "executable": {"filename": "/usr/share/OVMF/OVMF_CODE.secboot.fd", "format": "raw"},
+            "nvram-template": {"filename": "/usr/share/OVMF/OVMF_VARS.secboot.fd", "format": "raw"}
+        },
+        "targets": [{"architecture": "x86_64", "machines": ["pc-q35-*"]}],
+        "features": ["requires-smm", "secure-boot", "enrolled-keys", "some-new-feature"]
+    }"#;
+
+    const SEABIOS: &str = r#"{
+        "description": "SeaBIOS",
+        "interface-types": ["bios"],
+        "mapping": {"device": "memory", "filename": "/usr/share/seabios/bios-256k.bin"},
+        "targets": [{"architecture": "x86_64", "machines": ["pc-i440fx-*", "pc-q35-*"]}],
+        "features": ["acpi-s3", "acpi-s4"]
+    }"#;
+
+    const AAVMF: &str = r#"{
+        "interface-types": ["uefi"],
+        "mapping": {
+            "device": "flash",
+            "executable": {"filename": "/usr/share/AAVMF/AAVMF_CODE.fd", "format": "raw"},
+            "nvram-template": {"filename": "/usr/share/AAVMF/AAVMF_VARS.fd", "format": "raw"}
+        },
+        "targets": [{"architecture": "aarch64", "machines": ["virt-*"]}]
+    }"#;
+
+    const AAVMF_QCOW2: &str = r#"{
+        "interface-types": ["uefi"],
+        "mapping": {
+            "device": "flash",
+            "executable": {"filename": "/usr/share/AAVMF/AAVMF_CODE.qcow2", "format": "qcow2"},
+            "nvram-template": {"filename": "/usr/share/AAVMF/AAVMF_VARS.qcow2", "format": "qcow2"}
+        },
+        "targets": [{"architecture": "aarch64", "machines": ["virt-*"]}]
+    }"#;
+
+    struct Fixture {
+        high: PathBuf,
+        low: PathBuf,
+    }
+
+    impl Fixture {
+        fn new() -> Self {
+            let high = tmp::unique_path("firmware-high");
+            let low = tmp::unique_path("firmware-low");
+            fs::create_dir_all(&high).unwrap();
+            fs::create_dir_all(&low).unwrap();
+            fs::write(low.join("50-ovmf.json"), OVMF).unwrap();
+            fs::write(low.join("40-ovmf-secure.json"), OVMF_SECURE).unwrap();
+            fs::write(low.join("60-seabios.json"), SEABIOS).unwrap();
+            fs::write(low.join("60-aavmf.json"), AAVMF).unwrap();
+            fs::write(low.join("README"), "not a descriptor").unwrap();
+            Self { high, low }
+        }
+
+        fn dirs(&self) -> Vec<PathBuf> {
+            vec![self.high.clone(), self.low.clone()]
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.high);
+            let _ = fs::remove_dir_all(&self.low);
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let descriptor = FirmwareDescriptor::parse(OVMF_SECURE).unwrap();
+        assert_eq!(vec![InterfaceType::Uefi], descriptor.interface_types);
+        assert!(descriptor.has_feature(&FirmwareFeature::SecureBoot));
+        assert!(descriptor.has_feature(&FirmwareFeature::Other("some-new-feature".to_string())));
+        assert_eq!(
+            Some(Uefi {
+                code: PathBuf::from("/usr/share/OVMF/OVMF_CODE.secboot.fd"),
+                vars_template: PathBuf::from("/usr/share/OVMF/OVMF_VARS.secboot.fd"),
+                format: Format::Raw,
+                secure_boot: true,
+            }),
+            descriptor.to_uefi()
+        );
+        assert_eq!(None, descriptor.bios_file());
+    }
+
+    #[test]
+    fn test_parse_qcow2() {
+        let descriptor = FirmwareDescriptor::parse(AAVMF_QCOW2).unwrap();
+        assert_eq!(
+            Some(Uefi {
+                code: PathBuf::from("/usr/share/AAVMF/AAVMF_CODE.qcow2"),
+                vars_template: PathBuf::from("/usr/share/AAVMF/AAVMF_VARS.qcow2"),
+                format: Format::Qcow2,
+                secure_boot: false,
+            }),
+            descriptor.to_uefi()
+        );
+
+        let mixed = AAVMF_QCOW2.replace(
+            r#"VARS.qcow2", "format": "qcow2""#,
+            r#"VARS.fd", "format": "raw""#,
+        );
+        assert_eq!(None, FirmwareDescriptor::parse(&mixed).unwrap().to_uefi());
+        let unknown = AAVMF_QCOW2.replace("qcow2\"", "vmdk\"");
+        assert_eq!(None, FirmwareDescriptor::parse(&unknown).unwrap().to_uefi());
+    }
+
+    #[test]
+    fn test_parse_memory_mapping() {
+        let descriptor = FirmwareDescriptor::parse(SEABIOS).unwrap();
+        assert_eq!(
+            Some(Path::new("/usr/share/seabios/bios-256k.bin")),
+            descriptor.bios_file()
+        );
+        assert_eq!(None, descriptor.to_uefi());
+    }
+
+    #[test]
+    fn test_supports() {
+        let descriptor = FirmwareDescriptor::parse(OVMF_SECURE).unwrap();
+        assert!(descriptor.supports("x86_64", None));
+        assert!(descriptor.supports("x86_64", Some("pc-q35-8.2")));
+        assert!(!descriptor.supports("x86_64", Some("pc-i440fx-8.2")));
+        assert!(!descriptor.supports("aarch64", None));
+    }
+
+    #[test]
+    fn test_load_sorted_by_name() {
+        let fixture = Fixture::new();
+        let descriptors = load_descriptors(&fixture.dirs()).unwrap();
+        let names: Vec<_> = descriptors
+            .iter()
+            .map(|(path, _)| path.file_name().unwrap().to_str().unwrap())
+            .collect();
+        assert_eq!(
+            vec![
+                "40-ovmf-secure.json",
+                "50-ovmf.json",
+                "60-aavmf.json",
+                "60-seabios.json"
+            ],
+            names
+        );
+    }
+
+    #[test]
+    fn test_load_shadowing() {
+        let fixture = Fixture::new();
+        fs::write(fixture.high.join("40-ovmf-secure.json"), "").unwrap();
+        fs::write(fixture.high.join("60-seabios.json"), OVMF).unwrap();
+
+        let descriptors = load_descriptors(&fixture.dirs()).unwrap();
+        assert_eq!(3, descriptors.len());
+        assert_eq!(fixture.high.join("60-seabios.json"), descriptors[2].0);
+        assert_eq!(vec![InterfaceType::Uefi], descriptors[2].1.interface_types);
+    }
+
+    #[test]
+    fn test_load_invalid() {
+        let fixture = Fixture::new();
+        fs::write(fixture.high.join("10-broken.json"), "{").unwrap();
+
+        let err = load_descriptors(&fixture.dirs()).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, err.kind());
+    }
+
+    #[test]
+    fn test_select() {
+        let fixture = Fixture::new();
+
+        let query = FirmwareQuery::new(InterfaceType::Uefi);
+        let selected = select(&X86_64, &fixture.dirs(), &query).unwrap().unwrap();
+        assert_eq!("OVMF with secure boot", selected.description);
+
+        let query = FirmwareQuery {
+            excluded_features: vec![FirmwareFeature::SecureBoot],
+            ..FirmwareQuery::new(InterfaceType::Uefi)
+        };
+        let selected = select(&X86_64, &fixture.dirs(), &query).unwrap().unwrap();
+        assert_eq!("OVMF for x86_64", selected.description);
+
+        let query = FirmwareQuery {
+            machine: Some("pc-i440fx-8.2".to_string()),
+            ..FirmwareQuery::new(InterfaceType::Bios)
+        };
+        let selected = select(&X86_64, &fixture.dirs(), &query).unwrap().unwrap();
+        assert_eq!("SeaBIOS", selected.description);
+
+        let query = FirmwareQuery::new(InterfaceType::Uefi);
+        let selected = select(&Aarch64, &fixture.dirs(), &query).unwrap().unwrap();
+        assert_eq!(
+            PathBuf::from("/usr/share/AAVMF/AAVMF_CODE.fd"),
+            selected.to_uefi().unwrap().code
+        );
+
+        let query = FirmwareQuery {
+            required_features: vec![FirmwareFeature::AmdSev],
+            ..FirmwareQuery::new(InterfaceType::Uefi)
+        };
+        assert_eq!(None, select(&X86_64, &fixture.dirs(), &query).unwrap());
+    }
+
+    #[test]
+    fn test_glob_matches() {
+        assert!(glob_matches("pc-q35-*", "pc-q35-8.2"));
+        assert!(glob_matches("*", "virt"));
+        assert!(glob_matches("virt", "virt"));
+        assert!(glob_matches("a*c*e", "abcde"));
+        assert!(!glob_matches("pc-q35-*", "virt-8.2"));
+        assert!(!glob_matches("virt", "virt-8.2"));
+    }
+}
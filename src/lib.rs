@@ -1,77 +1,190 @@
 use crate::args::{
-    Accel, Bios, Drive, FreezeOnStartup, Fullscreen, Gdb, Help, LogItem, LogItems, NoReboot,
-    Serial, Version,
+    option_name, Accel, Action, ArgSyntax, Bios, Blockdev, Chardev, Compat, Device, Drive,
+    FreezeOnStartup, FromArg, Fullscreen, Gdb, Global, Help, Incoming, InvalidValue, LogItem,
+    LogItems, Machine, MemPath, MemPrealloc, MemoryBackend, Netdev, NoReboot, NumaError,
+    NumaTopology, Object, Overcommit, ParseError, Pflash, QemuArgs, Serial, Smp, Tpm, UefiFirmware,
+    Version, VirtioRng, Vnc, Watchdog, WatchdogAction,
 };
 use crate::chardev::QemuCharDevice;
 use args::QemuArgument;
-use std::marker::PhantomData;
+use std::ffi::{OsStr, OsString};
 use std::path::PathBuf;
-use std::process::Command;
+use std::process::{Child, Command, Output};
+use std::time::Duration;
 
-pub mod args;
-pub mod chardev;
-
-pub trait QemuSystem {
-    fn command() -> &'static str;
-}
-
-#[cfg(test)]
-#[derive(Default, Debug, Eq, PartialEq, Hash)]
-pub struct Generic;
-
-#[cfg(test)]
-impl QemuSystem for Generic {
-    fn command() -> &'static str {
-        "qemu-generic"
-    }
-}
-
-#[derive(Default, Debug, Eq, PartialEq, Hash)]
-pub struct X86_64;
-
-impl QemuSystem for X86_64 {
-    fn command() -> &'static str {
-        "qemu-system-x86_64"
-    }
-}
+pub use error::Error;
+pub use system::*;
+pub use version::QemuVersion;
 
-#[derive(Default, Debug, Eq, PartialEq, Hash)]
-pub struct Aarch64;
+// Allows the derive macros to refer to `::qemu_api` within this crate.
+extern crate self as qemu_api;
 
-impl QemuSystem for Aarch64 {
-    fn command() -> &'static str {
-        "qemu-system-aarch64"
-    }
-}
+pub mod args;
+pub mod chardev;
+mod error;
+#[cfg(feature = "firmware")]
+pub mod firmware;
+pub mod probe;
+mod process;
+#[cfg(feature = "serde")]
+pub mod profile;
+pub mod swtpm;
+mod system;
+mod tmp;
+pub mod user;
+pub mod validate;
+mod version;
 
-impl<S> From<Qemu<S>> for Command
+/// Converts a configuration into a command, see [`Qemu::to_command`].
+///
+/// This replaces the former `From<Qemu<S>>` implementation, which
+/// couldn't report values that aren't representable on the command line.
+/// Code that used `Command::from(qemu)` or `qemu.into()` has to handle the
+/// [`InvalidValue`] now, or use [`Qemu::to_command`], which doesn't
+/// consume the configuration.
+impl<S> TryFrom<Qemu<S>> for Command
 where
     S: QemuSystem,
 {
-    fn from(value: Qemu<S>) -> Self {
-        let mut cmd = Command::new(S::command());
-        for arg in value.into_args() {
-            cmd.arg(arg);
-        }
-        cmd
+    type Error = InvalidValue;
+
+    fn try_from(value: Qemu<S>) -> Result<Self, Self::Error> {
+        value.to_command()
     }
 }
 
-#[derive(Default, Debug, Eq, PartialEq, Hash)]
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(
+        default,
+        rename_all = "kebab-case",
+        bound(deserialize = "S: QemuSystem + Default")
+    )
+)]
 pub struct Qemu<S> {
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "crate::profile::flag", skip_serializing_if = "Option::is_none")
+    )]
     no_reboot: Option<NoReboot>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "crate::profile::flag", skip_serializing_if = "Option::is_none")
+    )]
     help: Option<Help>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "crate::profile::flag", skip_serializing_if = "Option::is_none")
+    )]
     version: Option<Version>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "crate::profile::flag", skip_serializing_if = "Option::is_none")
+    )]
     fullscreen: Option<Fullscreen>,
     accel: Option<Accel>,
     drives: Vec<Drive>,
     bios: Option<Bios>,
+    pflash: Option<Pflash>,
     serial: Option<Serial>,
     log_items: Option<LogItems>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "crate::profile::flag", skip_serializing_if = "Option::is_none")
+    )]
     freeze_on_startup: Option<FreezeOnStartup>,
     gdb: Option<Gdb>,
-    others: Vec<String>,
-    _system: PhantomData<S>,
+    globals: Vec<Global>,
+    compat: Option<Compat>,
+    action: Option<Action>,
+    watchdog: Option<Watchdog>,
+    watchdog_action: Option<WatchdogAction>,
+    tpm: Option<Tpm>,
+    machine: Option<Machine>,
+    objects: Vec<Object>,
+    mem_path: Option<MemPath>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "crate::profile::flag", skip_serializing_if = "Option::is_none")
+    )]
+    mem_prealloc: Option<MemPrealloc>,
+    overcommit: Option<Overcommit>,
+    smp: Option<Smp>,
+    numa: Option<NumaTopology>,
+    virtio_rng: Option<VirtioRng>,
+    blockdevs: Vec<Blockdev>,
+    netdevs: Vec<Netdev>,
+    chardevs: Vec<Chardev>,
+    devices: Vec<Device>,
+    vnc: Option<Vnc>,
+    incoming: Option<Incoming>,
+    syntax: Option<ArgSyntax>,
+    binary: Option<PathBuf>,
+    search_dirs: Vec<PathBuf>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::profile::os_strings"))]
+    others: Vec<OsString>,
+    order: Vec<QemuOption>,
+    removed: Vec<QemuOption>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    system: S,
+}
+
+/// An option of a [`Qemu`], which can be removed with [`Qemu::remove`].
+///
+/// The configured options are also recorded in the order in which they
+/// were set. Repeatable options such as [`Self::Drive`] occur once per
+/// value there, and their values are emitted in the order of their list.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum QemuOption {
+    NoReboot,
+    Help,
+    Version,
+    Fullscreen,
+    Accel,
+    Drive,
+    Bios,
+    Pflash,
+    Serial,
+    LogItems,
+    FreezeOnStartup,
+    Gdb,
+    Global,
+    Compat,
+    Action,
+    Watchdog,
+    WatchdogAction,
+    Tpm,
+    Machine,
+    Object,
+    MemPath,
+    MemPrealloc,
+    Overcommit,
+    Smp,
+    Numa,
+    VirtioRng,
+    Blockdev,
+    Netdev,
+    Chardev,
+    Device,
+    Vnc,
+    Incoming,
+    Other,
+}
+
+impl<S> Default for Qemu<S>
+where
+    S: QemuSystem + Default,
+{
+    fn default() -> Self {
+        Self::with_system(S::default())
+    }
 }
 
 impl<S> Qemu<S>
@@ -81,35 +194,575 @@ where
     pub fn new() -> Self {
         Default::default()
     }
+
+    /// Parses a command line, without the binary, into a configuration.
+    /// This is the inverse of [`Self::into_args`].
+    ///
+    /// Options that are modeled by a setter of this type are parsed into
+    /// their typed fields. All other arguments, and options with properties
+    /// or syntax that isn't modeled (see [`ParseError::Unsupported`]), are
+    /// kept as [`Self::other`] arguments at their position.
+    ///
+    /// Parsing the arguments of a configuration gives the same arguments
+    /// back, and the same configuration with these exceptions:
+    ///
+    /// - Options in JSON syntax, see [`Self::arg_syntax`], are kept as
+    ///   other arguments.
+    /// - The properties of a generic [`Device`] are parsed as strings.
+    /// - A `-device` that matches a [`Watchdog`] or a [`VirtioRng`] is
+    ///   parsed as such, unless one is already configured.
+    /// - A `-global` of the secure pflash or of the migration TLS
+    ///   parameters is parsed as part of the pflash or [`Incoming`] if
+    ///   the options of these follow it.
+    /// - All `-numa` options form one [`NumaTopology`] at the position of
+    ///   the first one.
+    ///
+    /// Fails if a typed option is malformed.
+    pub fn parse_args<I>(args: I) -> Result<Self, ParseError>
+    where
+        I: IntoIterator,
+        I::Item: Into<OsString>,
+    {
+        let args: Vec<OsString> = args.into_iter().map(Into::into).collect();
+        let mut qemu = Self::new();
+        let mut i = 0;
+        while i < args.len() {
+            if let Some(len) = qemu.parse_leading(&args[i..]) {
+                i += len;
+                continue;
+            }
+            let arg = &args[i];
+            i += 1;
+            let Some(name) = option_name(arg) else {
+                qemu.other(arg);
+                continue;
+            };
+            let value = if OPTIONS_WITH_VALUE.contains(&name) {
+                let value = args
+                    .get(i)
+                    .ok_or_else(|| ParseError::invalid(name, "missing value"))?;
+                i += 1;
+                Some(value.as_os_str())
+            } else {
+                None
+            };
+            let parsed = match qemu.parse_option(name, value) {
+                Ok(parsed) => parsed,
+                Err(e) if e.is_unsupported() => false,
+                Err(e) => return Err(e),
+            };
+            if !parsed {
+                qemu.other(arg);
+                qemu.others_extend(value);
+            }
+        }
+        Ok(qemu)
+    }
+
+    /// Parses a typed option that spans several options at the start of
+    /// `args`, such as the `-chardev`, `-tpmdev` and `-device` of a [`Tpm`].
+    /// Returns the number of arguments it takes.
+    fn parse_leading(&mut self, args: &[OsString]) -> Option<usize> {
+        if self.tpm.is_none() {
+            if let Some((tpm, len)) = Tpm::parse_leading(args) {
+                self.tpm(tpm);
+                return Some(len);
+            }
+        }
+        if self.pflash.is_none() {
+            if let Some((pflash, len)) = Pflash::parse_leading(args) {
+                self.pflash = Some(pflash);
+                self.place(QemuOption::Pflash);
+                return Some(len);
+            }
+        }
+        if self.incoming.is_none() {
+            if let Some((incoming, len)) = Incoming::parse_leading(args) {
+                self.incoming(incoming);
+                return Some(len);
+            }
+        }
+        None
+    }
+
+    /// Parses a typed option. Returns whether the option is modeled.
+    fn parse_option(&mut self, name: &str, value: Option<&OsStr>) -> Result<bool, ParseError> {
+        match name {
+            "-no-reboot" => {
+                self.no_reboot();
+            }
+            "-help" | "-h" => {
+                self.help();
+            }
+            "-version" => {
+                self.version();
+            }
+            "-fullscreen" | "-full-screen" => {
+                self.fullscreen();
+            }
+            "-accel" => {
+                self.accel(Accel::from_arg(value)?);
+            }
+            "-drive" => {
+                self.drive(Drive::from_arg(value)?);
+            }
+            "-bios" => {
+                self.bios(Bios::from_arg(value)?.0);
+            }
+            "-serial" => {
+                self.serial(Serial::from_arg(value)?.0);
+            }
+            "-d" => {
+                self.log_items(LogItems::from_arg(value)?.items);
+            }
+            "-S" => {
+                self.freeze_on_startup();
+            }
+            "-gdb" => {
+                self.gdb(&Gdb::from_arg(value)?.0);
+            }
+            "-s" => {
+                self.s();
+            }
+            "-global" => {
+                self.global(Global::from_arg(value)?);
+            }
+            "-compat" => {
+                self.compat(Compat::from_arg(value)?);
+            }
+            "-action" => {
+                self.action(Action::from_arg(value)?);
+            }
+            "-watchdog-action" => {
+                self.watchdog_action(WatchdogAction::from_arg(value)?);
+            }
+            "-machine" | "-M" => {
+                self.machine(Machine::from_arg(value)?);
+            }
+            "-mem-path" => {
+                self.mem_path(MemPath::from_arg(value)?.0);
+            }
+            "-mem-prealloc" => {
+                self.mem_prealloc();
+            }
+            "-overcommit" => {
+                self.overcommit(Overcommit::from_arg(value)?);
+            }
+            "-smp" => {
+                self.smp(Smp::from_arg(value)?);
+            }
+            "-numa" => {
+                let mut numa = self.numa.clone().unwrap_or_default();
+                numa.parse_entry(value)?;
+                self.numa(numa);
+            }
+            "-object" => {
+                self.object(Object::from_arg(value)?);
+            }
+            "-blockdev" => {
+                self.blockdev(Blockdev::from_arg(value)?);
+            }
+            "-netdev" => {
+                self.netdev(Netdev::from_arg(value)?);
+            }
+            "-chardev" => {
+                self.chardev(Chardev::from_arg(value)?);
+            }
+            "-device" => {
+                self.parse_device(value)?;
+            }
+            "-vnc" => {
+                self.vnc(Vnc::from_arg(value)?);
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    /// Parses a `-device` into the typed option it configures, or into
+    /// a generic [`Device`].
+    fn parse_device(&mut self, value: Option<&OsStr>) -> Result<(), ParseError> {
+        if self.watchdog.is_none() {
+            if let Ok(watchdog) = Watchdog::from_arg(value) {
+                self.watchdog(watchdog);
+                return Ok(());
+            }
+        }
+        if self.virtio_rng.is_none() {
+            if let Ok(virtio_rng) = VirtioRng::from_arg(value) {
+                self.virtio_rng(virtio_rng);
+                return Ok(());
+            }
+        }
+        self.device(Device::from_arg(value)?);
+        Ok(())
+    }
+
+    fn others_extend(&mut self, args: impl IntoIterator<Item = impl AsRef<OsStr>>) {
+        for arg in args {
+            self.other(arg);
+        }
+    }
 }
 
+/// The options that take a value, including the ones that are only
+/// supported as other arguments.
+const OPTIONS_WITH_VALUE: &[&str] = &[
+    "-accel",
+    "-drive",
+    "-bios",
+    "-serial",
+    "-d",
+    "-gdb",
+    "-global",
+    "-compat",
+    "-action",
+    "-watchdog-action",
+    "-machine",
+    "-M",
+    "-mem-path",
+    "-overcommit",
+    "-smp",
+    "-m",
+    "-cpu",
+    "-kernel",
+    "-initrd",
+    "-append",
+    "-dtb",
+    "-device",
+    "-object",
+    "-blockdev",
+    "-netdev",
+    "-nic",
+    "-chardev",
+    "-numa",
+    "-tpmdev",
+    "-display",
+    "-vga",
+    "-monitor",
+    "-qmp",
+    "-mon",
+    "-name",
+    "-uuid",
+    "-boot",
+    "-cdrom",
+    "-hda",
+    "-hdb",
+    "-hdc",
+    "-hdd",
+    "-fda",
+    "-fdb",
+    "-pflash",
+    "-L",
+    "-readconfig",
+    "-k",
+    "-audiodev",
+    "-icount",
+    "-rtc",
+    "-incoming",
+    "-loadvm",
+    "-pidfile",
+    "-D",
+    "-trace",
+    "-plugin",
+    "-semihosting-config",
+    "-fw_cfg",
+    "-smbios",
+    "-acpitable",
+    "-parallel",
+    "-virtfs",
+    "-add-fd",
+    "-spice",
+    "-vnc",
+    "-run-with",
+    "-msg",
+    "-sandbox",
+    "-option-rom",
+    "-usbdevice",
+    "-sd",
+    "-mtdblock",
+    "-watchdog",
+    "-iscsi",
+    "-prom-env",
+];
+
 impl<S> Qemu<S>
 where
     S: QemuSystem,
 {
-    pub fn into_args(self) -> impl Iterator<Item = String> {
-        self.args().into_iter()
+    /// Creates an empty configuration for a system that is selected at
+    /// runtime, such as an [`AnySystem`].
+    pub fn with_system(system: S) -> Self {
+        Self {
+            no_reboot: Default::default(),
+            help: Default::default(),
+            version: Default::default(),
+            fullscreen: Default::default(),
+            accel: Default::default(),
+            drives: Default::default(),
+            bios: Default::default(),
+            pflash: Default::default(),
+            serial: Default::default(),
+            log_items: Default::default(),
+            freeze_on_startup: Default::default(),
+            gdb: Default::default(),
+            globals: Default::default(),
+            compat: Default::default(),
+            action: Default::default(),
+            watchdog: Default::default(),
+            watchdog_action: Default::default(),
+            tpm: Default::default(),
+            machine: Default::default(),
+            objects: Default::default(),
+            mem_path: Default::default(),
+            mem_prealloc: Default::default(),
+            overcommit: Default::default(),
+            smp: Default::default(),
+            numa: Default::default(),
+            virtio_rng: Default::default(),
+            blockdevs: Default::default(),
+            netdevs: Default::default(),
+            chardevs: Default::default(),
+            devices: Default::default(),
+            vnc: Default::default(),
+            incoming: Default::default(),
+            syntax: Default::default(),
+            binary: Default::default(),
+            search_dirs: Default::default(),
+            others: Default::default(),
+            order: Default::default(),
+            removed: Default::default(),
+            system,
+        }
     }
 
-    fn args(self) -> Vec<String> {
+    pub fn system(&self) -> &S {
+        &self.system
+    }
+
+    /// Returns the arguments, or an error if a value can't be
+    /// represented on the command line.
+    ///
+    /// Options are emitted in the order in which they were first
+    /// configured, including the ones added with [`Self::other`].
+    /// Setting a non-repeatable option again keeps its position.
+    ///
+    /// This borrows the configuration, so that it can be used for
+    /// several runs.
+    pub fn args(&self) -> Result<QemuArgs, InvalidValue> {
         let mut args = Vec::new();
+        let syntax = self.syntax.unwrap_or_default();
+        let mut drives = self.drives.iter();
+        let mut globals = self.globals.iter();
+        let mut objects = self.objects.iter();
+        let mut blockdevs = self.blockdevs.iter();
+        let mut netdevs = self.netdevs.iter();
+        let mut chardevs = self.chardevs.iter();
+        let mut devices = self.devices.iter();
+        let mut others = self.others.iter();
 
-        // TODO: this could be done with a macro
-        push_if_exists(&mut args, self.no_reboot);
-        push_if_exists(&mut args, self.help);
-        push_if_exists(&mut args, self.version);
-        push_if_exists(&mut args, self.fullscreen);
-        push_if_exists(&mut args, self.accel);
-        push_many_if_exists(&mut args, self.drives);
-        push_if_exists(&mut args, self.bios);
-        push_if_exists(&mut args, self.serial);
-        push_if_exists(&mut args, self.log_items);
-        push_if_exists(&mut args, self.freeze_on_startup);
-        push_if_exists(&mut args, self.gdb);
+        for slot in self.full_order() {
+            let args = &mut args;
+            match slot {
+                QemuOption::NoReboot => push_if_exists(args, self.no_reboot.as_ref(), syntax)?,
+                QemuOption::Help => push_if_exists(args, self.help.as_ref(), syntax)?,
+                QemuOption::Version => push_if_exists(args, self.version.as_ref(), syntax)?,
+                QemuOption::Fullscreen => push_if_exists(args, self.fullscreen.as_ref(), syntax)?,
+                QemuOption::Accel => push_if_exists(args, self.accel.as_ref(), syntax)?,
+                QemuOption::Drive => push_if_exists(args, drives.next(), syntax)?,
+                QemuOption::Bios => push_if_exists(args, self.bios.as_ref(), syntax)?,
+                QemuOption::Pflash => push_if_exists(args, self.pflash.as_ref(), syntax)?,
+                QemuOption::Serial => push_if_exists(args, self.serial.as_ref(), syntax)?,
+                QemuOption::LogItems => push_if_exists(args, self.log_items.as_ref(), syntax)?,
+                QemuOption::FreezeOnStartup => {
+                    push_if_exists(args, self.freeze_on_startup.as_ref(), syntax)?
+                }
+                QemuOption::Gdb => push_if_exists(args, self.gdb.as_ref(), syntax)?,
+                QemuOption::Global => push_if_exists(args, globals.next(), syntax)?,
+                QemuOption::Compat => push_if_exists(args, self.compat.as_ref(), syntax)?,
+                QemuOption::Action => push_if_exists(args, self.action.as_ref(), syntax)?,
+                QemuOption::Watchdog => push_if_exists(args, self.watchdog.as_ref(), syntax)?,
+                QemuOption::WatchdogAction => {
+                    push_if_exists(args, self.watchdog_action.as_ref(), syntax)?
+                }
+                QemuOption::Tpm => push_if_exists(args, self.tpm.as_ref(), syntax)?,
+                QemuOption::Machine => push_if_exists(args, self.machine.as_ref(), syntax)?,
+                QemuOption::Object => push_if_exists(args, objects.next(), syntax)?,
+                QemuOption::MemPath => push_if_exists(args, self.mem_path.as_ref(), syntax)?,
+                QemuOption::MemPrealloc => {
+                    push_if_exists(args, self.mem_prealloc.as_ref(), syntax)?
+                }
+                QemuOption::Overcommit => push_if_exists(args, self.overcommit.as_ref(), syntax)?,
+                QemuOption::Smp => push_if_exists(args, self.smp.as_ref(), syntax)?,
+                QemuOption::Numa => push_if_exists(args, self.numa.as_ref(), syntax)?,
+                QemuOption::VirtioRng => push_if_exists(args, self.virtio_rng.as_ref(), syntax)?,
+                QemuOption::Blockdev => push_if_exists(args, blockdevs.next(), syntax)?,
+                QemuOption::Netdev => push_if_exists(args, netdevs.next(), syntax)?,
+                QemuOption::Chardev => push_if_exists(args, chardevs.next(), syntax)?,
+                QemuOption::Device => push_if_exists(args, devices.next(), syntax)?,
+                QemuOption::Vnc => push_if_exists(args, self.vnc.as_ref(), syntax)?,
+                QemuOption::Incoming => push_if_exists(args, self.incoming.as_ref(), syntax)?,
+                QemuOption::Other => args.extend(others.next().cloned()),
+            }
+        }
 
-        args.extend(self.others);
+        Ok(QemuArgs::from(args))
+    }
 
-        args
+    /// Like [`Self::args`], but consumes the configuration.
+    pub fn into_args(self) -> Result<QemuArgs, InvalidValue> {
+        self.args()
+    }
+
+    /// Returns a command that runs QEMU with the arguments of this
+    /// configuration. Each call returns a new command.
+    ///
+    /// Unlike [`Self::spawn`], this doesn't check that the binary exists,
+    /// and leaves the lookup in `PATH` to [`Command`].
+    pub fn to_command(&self) -> Result<Command, InvalidValue> {
+        let command = self.system.command();
+        let binary = process::locate(command, self.binary.as_deref(), &self.search_dirs)
+            .unwrap_or_else(|| command.into());
+        let mut cmd = Command::new(binary);
+        cmd.args(self.args()?);
+        Ok(cmd)
+    }
+
+    /// Returns the QEMU binary that is run, which is, in this order of
+    /// precedence:
+    ///
+    /// 1. the path given with [`Self::binary`],
+    /// 2. the system's command in the directory named by the `QEMU_DIR`
+    ///    environment variable,
+    /// 3. the system's command in the first directory added with
+    ///    [`Self::search_dir`] that contains it,
+    /// 4. the system's command in `PATH`.
+    ///
+    /// Fails with [`Error::MissingBinary`] if the binary doesn't exist or
+    /// isn't executable. An explicit binary or `QEMU_DIR` never falls back
+    /// to the other locations.
+    pub fn resolve_binary(&self) -> Result<PathBuf, Error> {
+        process::resolve(
+            self.system.command(),
+            self.binary.as_deref(),
+            &self.search_dirs,
+        )
+    }
+
+    /// Fails with [`Error::InvalidConfig`] if [`Self::validate`] finds
+    /// any mistakes.
+    pub fn check(&self) -> Result<(), Error> {
+        let diagnostics = self.validate();
+        if diagnostics.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::InvalidConfig(diagnostics))
+        }
+    }
+
+    /// Spawns QEMU. This doesn't [`check`](Self::check) the configuration.
+    pub fn spawn(&self) -> Result<Child, Error> {
+        process::spawn(&mut self.resolved_command()?)
+    }
+
+    /// Runs QEMU to completion with captured output, for example to
+    /// print the help. Fails if QEMU doesn't exit within `timeout` or
+    /// exits with a failure status.
+    pub fn run(&self, timeout: Duration) -> Result<Output, Error> {
+        process::run(&mut self.resolved_command()?, timeout)
+    }
+
+    /// Like [`Self::to_command`], but with the binary of
+    /// [`Self::resolve_binary`].
+    fn resolved_command(&self) -> Result<Command, Error> {
+        let args = self.args()?;
+        let mut cmd = Command::new(self.resolve_binary()?);
+        cmd.args(args);
+        Ok(cmd)
+    }
+
+    /// The positions of all options. Options without a position, which is
+    /// the case for configurations that were deserialized without an order,
+    /// are appended, and positions without a value are dropped.
+    fn full_order(&self) -> Vec<QemuOption> {
+        let counts = [
+            (QemuOption::NoReboot, usize::from(self.no_reboot.is_some())),
+            (QemuOption::Help, usize::from(self.help.is_some())),
+            (QemuOption::Version, usize::from(self.version.is_some())),
+            (
+                QemuOption::Fullscreen,
+                usize::from(self.fullscreen.is_some()),
+            ),
+            (QemuOption::Accel, usize::from(self.accel.is_some())),
+            (QemuOption::Drive, self.drives.len()),
+            (QemuOption::Bios, usize::from(self.bios.is_some())),
+            (QemuOption::Pflash, usize::from(self.pflash.is_some())),
+            (QemuOption::Serial, usize::from(self.serial.is_some())),
+            (QemuOption::LogItems, usize::from(self.log_items.is_some())),
+            (
+                QemuOption::FreezeOnStartup,
+                usize::from(self.freeze_on_startup.is_some()),
+            ),
+            (QemuOption::Gdb, usize::from(self.gdb.is_some())),
+            (QemuOption::Global, self.globals.len()),
+            (QemuOption::Compat, usize::from(self.compat.is_some())),
+            (QemuOption::Action, usize::from(self.action.is_some())),
+            (QemuOption::Watchdog, usize::from(self.watchdog.is_some())),
+            (
+                QemuOption::WatchdogAction,
+                usize::from(self.watchdog_action.is_some()),
+            ),
+            (QemuOption::Tpm, usize::from(self.tpm.is_some())),
+            (QemuOption::Machine, usize::from(self.machine.is_some())),
+            (QemuOption::Object, self.objects.len()),
+            (QemuOption::MemPath, usize::from(self.mem_path.is_some())),
+            (
+                QemuOption::MemPrealloc,
+                usize::from(self.mem_prealloc.is_some()),
+            ),
+            (
+                QemuOption::Overcommit,
+                usize::from(self.overcommit.is_some()),
+            ),
+            (QemuOption::Smp, usize::from(self.smp.is_some())),
+            (QemuOption::Numa, usize::from(self.numa.is_some())),
+            (
+                QemuOption::VirtioRng,
+                usize::from(self.virtio_rng.is_some()),
+            ),
+            (QemuOption::Blockdev, self.blockdevs.len()),
+            (QemuOption::Netdev, self.netdevs.len()),
+            (QemuOption::Chardev, self.chardevs.len()),
+            (QemuOption::Device, self.devices.len()),
+            (QemuOption::Vnc, usize::from(self.vnc.is_some())),
+            (QemuOption::Incoming, usize::from(self.incoming.is_some())),
+            (QemuOption::Other, self.others.len()),
+        ];
+        let count = |option| {
+            counts
+                .iter()
+                .find(|(o, _)| *o == option)
+                .map_or(0, |(_, count)| *count)
+        };
+        let mut order = Vec::new();
+        for &option in &self.order {
+            if order.iter().filter(|&&o| o == option).count() < count(option) {
+                order.push(option);
+            }
+        }
+        for (option, count) in counts {
+            let placed = order.iter().filter(|&&o| o == option).count();
+            order.extend((placed..count).map(|_| option));
+        }
+        order
+    }
+
+    /// Records the position of an option. A non-repeatable option that
+    /// is set again keeps its first position.
+    fn place(&mut self, slot: QemuOption) {
+        if !self.order.contains(&slot) {
+            self.order.push(slot);
+        }
     }
 
     /// Activate the `-no-reboot` option.
@@ -118,6 +771,7 @@ where
     /// the guest triple faulting).
     pub fn no_reboot(&mut self) -> &mut Self {
         self.no_reboot = Some(NoReboot);
+        self.place(QemuOption::NoReboot);
         self
     }
 
@@ -126,6 +780,7 @@ where
     /// This will display the help text and exit.
     pub fn help(&mut self) -> &mut Self {
         self.help = Some(Help);
+        self.place(QemuOption::Help);
         self
     }
 
@@ -134,6 +789,7 @@ where
     /// This will display the version and exit.
     pub fn version(&mut self) -> &mut Self {
         self.version = Some(Version);
+        self.place(QemuOption::Version);
         self
     }
 
@@ -142,6 +798,7 @@ where
     /// This will launch qemu in full screen.
     pub fn fullscreen(&mut self) -> &mut Self {
         self.fullscreen = Some(Fullscreen);
+        self.place(QemuOption::Fullscreen);
         self
     }
 
@@ -154,6 +811,7 @@ where
     /// command fail.
     pub fn accel(&mut self, accel: Accel) -> &mut Self {
         self.accel = Some(accel);
+        self.place(QemuOption::Accel);
         self
     }
 
@@ -161,12 +819,14 @@ where
     /// is repeatable.
     pub fn drive(&mut self, drive: Drive) -> &mut Self {
         self.drives.push(drive);
+        self.order.push(QemuOption::Drive);
         self
     }
 
     /// Specifies a bios file via the `-bios` option.
     pub fn bios(&mut self, bios: PathBuf) -> &mut Self {
         self.bios = Some(Bios(bios));
+        self.place(QemuOption::Bios);
         self
     }
 
@@ -176,6 +836,7 @@ where
     /// correct and doesn't support everything that it should.
     pub fn serial(&mut self, serial_device: QemuCharDevice) -> &mut Self {
         self.serial = Some(Serial(serial_device));
+        self.place(QemuOption::Serial);
         self
     }
 
@@ -199,6 +860,7 @@ where
     {
         let items = LogItems::from(log_items);
         self.log_items = Some(items);
+        self.place(QemuOption::LogItems);
         self
     }
 
@@ -218,6 +880,7 @@ where
     /// Makes the cpu freeze on startup.
     pub fn freeze_on_startup(&mut self) -> &mut Self {
         self.freeze_on_startup = Some(FreezeOnStartup);
+        self.place(QemuOption::FreezeOnStartup);
         self
     }
 
@@ -235,29 +898,675 @@ where
     pub fn gdb(&mut self, dev: &dyn AsRef<str>) -> &mut Self {
         let dev = dev.as_ref().to_string();
         self.gdb = Some(Gdb(dev));
+        self.place(QemuOption::Gdb);
+        self
+    }
+
+    /// Sets a default value for a driver property via the `-global` option.
+    /// This option is repeatable.
+    pub fn global(&mut self, global: Global) -> &mut Self {
+        self.globals.push(global);
+        self.order.push(QemuOption::Global);
+        self
+    }
+
+    /// Sets the policy for handling deprecated management interfaces
+    /// via the `-compat` option.
+    ///
+    /// Using [`args::DeprecatedInput::Reject`] makes QEMU refuse deprecated
+    /// options, which is useful to detect their usage early.
+    pub fn compat(&mut self, compat: Compat) -> &mut Self {
+        self.compat = Some(compat);
+        self.place(QemuOption::Compat);
+        self
+    }
+
+    /// Configures lifecycle policies via the `-action` option.
+    ///
+    /// This is a more fine-grained alternative to [`Self::no_reboot`],
+    /// and can for example make QEMU exit with a failure on a guest panic.
+    pub fn action(&mut self, action: Action) -> &mut Self {
+        self.action = Some(action);
+        self.place(QemuOption::Action);
+        self
+    }
+
+    /// Adds a hardware watchdog device via the `-device` option.
+    ///
+    /// What happens on expiry can be configured with [`Self::watchdog_action`]
+    /// or [`Action::watchdog`].
+    pub fn watchdog(&mut self, watchdog: Watchdog) -> &mut Self {
+        self.watchdog = Some(watchdog);
+        self.place(QemuOption::Watchdog);
+        self
+    }
+
+    /// Specifies what happens when the watchdog expires via the
+    /// `-watchdog-action` option.
+    pub fn watchdog_action(&mut self, action: WatchdogAction) -> &mut Self {
+        self.watchdog_action = Some(action);
+        self.place(QemuOption::WatchdogAction);
+        self
+    }
+
+    /// Adds an emulated TPM via the `-chardev`, `-tpmdev` and `-device` options.
+    ///
+    /// The emulator has to be started separately, for example with
+    /// [`swtpm::Swtpm::spawn`].
+    pub fn tpm(&mut self, tpm: Tpm) -> &mut Self {
+        self.tpm = Some(tpm);
+        self.place(QemuOption::Tpm);
+        self
+    }
+
+    /// Selects and configures the machine via the `-machine` option.
+    pub fn machine(&mut self, machine: Machine) -> &mut Self {
+        self.machine = Some(machine);
+        self.place(QemuOption::Machine);
+        self
+    }
+
+    /// Adds an object via the `-object` option. This option
+    /// is repeatable.
+    ///
+    /// Objects are referenced by other options through their
+    /// [`args::ObjectId`].
+    pub fn object(&mut self, object: impl Into<Object>) -> &mut Self {
+        self.objects.push(object.into());
+        self.order.push(QemuOption::Object);
+        self
+    }
+
+    /// Adds a memory backend via the `-object` option.
+    ///
+    /// The backend can be used as guest RAM with [`Machine::memory_backend`].
+    pub fn memory_backend(&mut self, backend: MemoryBackend) -> &mut Self {
+        self.object(backend)
+    }
+
+    /// Adds a virtio-rng device via the `-device` option.
+    ///
+    /// The referenced [`args::Rng`] must be added with [`Self::object`].
+    pub fn virtio_rng(&mut self, virtio_rng: VirtioRng) -> &mut Self {
+        self.virtio_rng = Some(virtio_rng);
+        self.place(QemuOption::VirtioRng);
+        self
+    }
+
+    /// Allocates guest RAM from a file in the given directory via
+    /// the `-mem-path` option.
+    pub fn mem_path(&mut self, path: PathBuf) -> &mut Self {
+        self.mem_path = Some(MemPath(path));
+        self.place(QemuOption::MemPath);
         self
     }
 
-    pub fn other(&mut self, v: impl ToString) -> &mut Self {
-        self.others.push(v.to_string());
+    /// Activates the `-mem-prealloc` option.
+    ///
+    /// This preallocates all guest RAM on startup.
+    pub fn mem_prealloc(&mut self) -> &mut Self {
+        self.mem_prealloc = Some(MemPrealloc);
+        self.place(QemuOption::MemPrealloc);
+        self
+    }
+
+    /// Configures host memory locking and cpu power management
+    /// via the `-overcommit` option.
+    pub fn overcommit(&mut self, overcommit: Overcommit) -> &mut Self {
+        self.overcommit = Some(overcommit);
+        self.place(QemuOption::Overcommit);
+        self
+    }
+
+    /// Whether the guest RAM can be shared with other processes. This is
+    /// required by vhost-user devices.
+    ///
+    /// This is the case if all memory backends of the guest RAM, namely
+    /// the one of the machine and those of the NUMA nodes, are shared.
+    /// Without a backend the RAM is private, even with `-mem-path`, whose
+    /// file backend QEMU creates with `share=off`.
+    pub fn has_shared_memory(&self) -> bool {
+        let machine = self
+            .machine
+            .as_ref()
+            .and_then(|m| m.memory_backend.as_ref());
+        let nodes = self
+            .numa
+            .iter()
+            .flat_map(|numa| &numa.nodes)
+            .filter_map(|node| node.memdev.as_ref());
+        let ids: Vec<_> = machine.into_iter().chain(nodes).collect();
+        !ids.is_empty()
+            && ids.into_iter().all(|id| {
+                self.memory_backends()
+                    .any(|backend| &backend.id == id && backend.is_shared())
+            })
+    }
+
+    /// Configures the cpu topology via the `-smp` option.
+    pub fn smp(&mut self, smp: Smp) -> &mut Self {
+        self.smp = Some(smp);
+        self.place(QemuOption::Smp);
+        self
+    }
+
+    /// Configures the NUMA topology via the `-numa` option.
+    ///
+    /// Use [`Self::check_numa`] to check it against the cpu topology
+    /// and the memory backends.
+    pub fn numa(&mut self, numa: NumaTopology) -> &mut Self {
+        self.numa = Some(numa);
+        self.place(QemuOption::Numa);
+        self
+    }
+
+    /// Checks the NUMA topology against the `-smp` topology and the
+    /// memory backends. Succeeds if no NUMA topology is configured.
+    pub fn check_numa(&self) -> Result<(), NumaError> {
+        match &self.numa {
+            Some(numa) => numa.validate(&self.smp.unwrap_or_default(), self.memory_backends()),
+            None => Ok(()),
+        }
+    }
+
+    fn memory_backends(&self) -> impl Iterator<Item = &MemoryBackend> {
+        self.objects.iter().filter_map(|object| match object {
+            Object::MemoryBackend(backend) => Some(backend),
+            _ => None,
+        })
+    }
+
+    /// Selects the syntax of structured options, namely `-device`,
+    /// `-object`, `-blockdev` and `-netdev`.
+    ///
+    /// [`ArgSyntax::Json`] avoids any escaping issues and can express
+    /// nested values, but requires a recent QEMU.
+    pub fn arg_syntax(&mut self, syntax: ArgSyntax) -> &mut Self {
+        self.syntax = Some(syntax);
+        self
+    }
+
+    /// Runs the given QEMU binary instead of looking up the system's
+    /// command, see [`Self::resolve_binary`].
+    pub fn binary(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+        self.binary = Some(path.into());
+        self
+    }
+
+    /// Adds a directory in which the system's command is looked up before
+    /// `PATH`, such as the `bin` directory of an install prefix or a QEMU
+    /// build directory. Directories are searched in the order in which
+    /// they were added.
+    pub fn search_dir(&mut self, dir: impl Into<PathBuf>) -> &mut Self {
+        self.search_dirs.push(dir.into());
+        self
+    }
+
+    /// Adds a block node via the `-blockdev` option. This option
+    /// is repeatable.
+    pub fn blockdev(&mut self, blockdev: Blockdev) -> &mut Self {
+        self.blockdevs.push(blockdev);
+        self.order.push(QemuOption::Blockdev);
+        self
+    }
+
+    /// Adds a network backend via the `-netdev` option. This option
+    /// is repeatable.
+    pub fn netdev(&mut self, netdev: Netdev) -> &mut Self {
+        self.netdevs.push(netdev);
+        self.order.push(QemuOption::Netdev);
+        self
+    }
+
+    /// Adds a character device backend via the `-chardev` option. This
+    /// option is repeatable.
+    pub fn chardev(&mut self, chardev: Chardev) -> &mut Self {
+        self.chardevs.push(chardev);
+        self.order.push(QemuOption::Chardev);
+        self
+    }
+
+    /// Adds a device via the `-device` option. This option
+    /// is repeatable.
+    pub fn device(&mut self, device: Device) -> &mut Self {
+        self.devices.push(device);
+        self.order.push(QemuOption::Device);
+        self
+    }
+
+    /// Starts a VNC server via the `-vnc` option.
+    pub fn vnc(&mut self, vnc: Vnc) -> &mut Self {
+        self.vnc = Some(vnc);
+        self.place(QemuOption::Vnc);
         self
     }
+
+    /// Waits for an incoming migration via the `-incoming` option.
+    pub fn incoming(&mut self, incoming: Incoming) -> &mut Self {
+        self.incoming = Some(incoming);
+        self.place(QemuOption::Incoming);
+        self
+    }
+
+    /// Adds a raw argument, which is emitted at the position at which
+    /// it was added, relative to the typed options.
+    pub fn other(&mut self, v: impl AsRef<OsStr>) -> &mut Self {
+        self.others.push(v.as_ref().to_os_string());
+        self.order.push(QemuOption::Other);
+        self
+    }
+
+    /// Removes an option, or all values of a repeatable option.
+    ///
+    /// The removal is also recorded, so that merging this configuration
+    /// into another one removes the option there, see [`Self::merge`].
+    pub fn remove(&mut self, option: QemuOption) -> &mut Self {
+        match option {
+            QemuOption::NoReboot => self.no_reboot = None,
+            QemuOption::Help => self.help = None,
+            QemuOption::Version => self.version = None,
+            QemuOption::Fullscreen => self.fullscreen = None,
+            QemuOption::Accel => self.accel = None,
+            QemuOption::Drive => self.drives.clear(),
+            QemuOption::Bios => self.bios = None,
+            QemuOption::Pflash => self.pflash = None,
+            QemuOption::Serial => self.serial = None,
+            QemuOption::LogItems => self.log_items = None,
+            QemuOption::FreezeOnStartup => self.freeze_on_startup = None,
+            QemuOption::Gdb => self.gdb = None,
+            QemuOption::Global => self.globals.clear(),
+            QemuOption::Compat => self.compat = None,
+            QemuOption::Action => self.action = None,
+            QemuOption::Watchdog => self.watchdog = None,
+            QemuOption::WatchdogAction => self.watchdog_action = None,
+            QemuOption::Tpm => self.tpm = None,
+            QemuOption::Machine => self.machine = None,
+            QemuOption::Object => self.objects.clear(),
+            QemuOption::MemPath => self.mem_path = None,
+            QemuOption::MemPrealloc => self.mem_prealloc = None,
+            QemuOption::Overcommit => self.overcommit = None,
+            QemuOption::Smp => self.smp = None,
+            QemuOption::Numa => self.numa = None,
+            QemuOption::VirtioRng => self.virtio_rng = None,
+            QemuOption::Blockdev => self.blockdevs.clear(),
+            QemuOption::Netdev => self.netdevs.clear(),
+            QemuOption::Chardev => self.chardevs.clear(),
+            QemuOption::Device => self.devices.clear(),
+            QemuOption::Vnc => self.vnc = None,
+            QemuOption::Incoming => self.incoming = None,
+            QemuOption::Other => self.others.clear(),
+        }
+        self.order.retain(|&o| o != option);
+        if !self.removed.contains(&option) {
+            self.removed.push(option);
+        }
+        self
+    }
+
+    /// Applies an overlay to this configuration, for example to derive
+    /// a variant of a base configuration for a single test.
+    ///
+    /// First, the options that were removed from the overlay are removed.
+    /// Then the options that are set in the overlay replace the ones in this
+    /// configuration, keeping their position, and the values of repeatable
+    /// options and other arguments are appended. Likewise, the overlay's
+    /// binary replaces this one, and its search directories are appended.
+    pub fn merge(&mut self, mut overlay: Qemu<S>) -> &mut Self {
+        for option in std::mem::take(&mut overlay.removed) {
+            self.remove(option);
+        }
+        if overlay.syntax.is_some() {
+            self.syntax = overlay.syntax;
+        }
+        if overlay.binary.is_some() {
+            self.binary = overlay.binary.take();
+        }
+        self.search_dirs.append(&mut overlay.search_dirs);
+
+        overlay.order = overlay.full_order();
+        let mut drives = std::mem::take(&mut overlay.drives).into_iter();
+        let mut globals = std::mem::take(&mut overlay.globals).into_iter();
+        let mut objects = std::mem::take(&mut overlay.objects).into_iter();
+        let mut blockdevs = std::mem::take(&mut overlay.blockdevs).into_iter();
+        let mut netdevs = std::mem::take(&mut overlay.netdevs).into_iter();
+        let mut chardevs = std::mem::take(&mut overlay.chardevs).into_iter();
+        let mut devices = std::mem::take(&mut overlay.devices).into_iter();
+        let mut others = std::mem::take(&mut overlay.others).into_iter();
+
+        for option in std::mem::take(&mut overlay.order) {
+            let o = &mut overlay;
+            match option {
+                QemuOption::NoReboot => {
+                    self.merge_value(option, o.no_reboot.take(), |q| &mut q.no_reboot)
+                }
+                QemuOption::Help => self.merge_value(option, o.help.take(), |q| &mut q.help),
+                QemuOption::Version => {
+                    self.merge_value(option, o.version.take(), |q| &mut q.version)
+                }
+                QemuOption::Fullscreen => {
+                    self.merge_value(option, o.fullscreen.take(), |q| &mut q.fullscreen)
+                }
+                QemuOption::Accel => self.merge_value(option, o.accel.take(), |q| &mut q.accel),
+                QemuOption::Drive => self.merge_item(option, drives.next(), |q| &mut q.drives),
+                QemuOption::Bios => self.merge_value(option, o.bios.take(), |q| &mut q.bios),
+                QemuOption::Pflash => self.merge_value(option, o.pflash.take(), |q| &mut q.pflash),
+                QemuOption::Serial => self.merge_value(option, o.serial.take(), |q| &mut q.serial),
+                QemuOption::LogItems => {
+                    self.merge_value(option, o.log_items.take(), |q| &mut q.log_items)
+                }
+                QemuOption::FreezeOnStartup => {
+                    self.merge_value(option, o.freeze_on_startup.take(), |q| {
+                        &mut q.freeze_on_startup
+                    })
+                }
+                QemuOption::Gdb => self.merge_value(option, o.gdb.take(), |q| &mut q.gdb),
+                QemuOption::Global => self.merge_item(option, globals.next(), |q| &mut q.globals),
+                QemuOption::Compat => self.merge_value(option, o.compat.take(), |q| &mut q.compat),
+                QemuOption::Action => self.merge_value(option, o.action.take(), |q| &mut q.action),
+                QemuOption::Watchdog => {
+                    self.merge_value(option, o.watchdog.take(), |q| &mut q.watchdog)
+                }
+                QemuOption::WatchdogAction => {
+                    self.merge_value(option, o.watchdog_action.take(), |q| &mut q.watchdog_action)
+                }
+                QemuOption::Tpm => self.merge_value(option, o.tpm.take(), |q| &mut q.tpm),
+                QemuOption::Machine => {
+                    self.merge_value(option, o.machine.take(), |q| &mut q.machine)
+                }
+                QemuOption::Object => self.merge_item(option, objects.next(), |q| &mut q.objects),
+                QemuOption::MemPath => {
+                    self.merge_value(option, o.mem_path.take(), |q| &mut q.mem_path)
+                }
+                QemuOption::MemPrealloc => {
+                    self.merge_value(option, o.mem_prealloc.take(), |q| &mut q.mem_prealloc)
+                }
+                QemuOption::Overcommit => {
+                    self.merge_value(option, o.overcommit.take(), |q| &mut q.overcommit)
+                }
+                QemuOption::Smp => self.merge_value(option, o.smp.take(), |q| &mut q.smp),
+                QemuOption::Numa => self.merge_value(option, o.numa.take(), |q| &mut q.numa),
+                QemuOption::VirtioRng => {
+                    self.merge_value(option, o.virtio_rng.take(), |q| &mut q.virtio_rng)
+                }
+                QemuOption::Blockdev => {
+                    self.merge_item(option, blockdevs.next(), |q| &mut q.blockdevs)
+                }
+                QemuOption::Netdev => self.merge_item(option, netdevs.next(), |q| &mut q.netdevs),
+                QemuOption::Chardev => {
+                    self.merge_item(option, chardevs.next(), |q| &mut q.chardevs)
+                }
+                QemuOption::Device => self.merge_item(option, devices.next(), |q| &mut q.devices),
+                QemuOption::Vnc => self.merge_value(option, o.vnc.take(), |q| &mut q.vnc),
+                QemuOption::Incoming => {
+                    self.merge_value(option, o.incoming.take(), |q| &mut q.incoming)
+                }
+                QemuOption::Other => self.merge_item(option, others.next(), |q| &mut q.others),
+            }
+        }
+        self
+    }
+
+    fn merge_value<T>(
+        &mut self,
+        option: QemuOption,
+        value: Option<T>,
+        field: fn(&mut Self) -> &mut Option<T>,
+    ) {
+        if let Some(value) = value {
+            *field(self) = Some(value);
+            self.place(option);
+        }
+    }
+
+    fn merge_item<T>(
+        &mut self,
+        option: QemuOption,
+        value: Option<T>,
+        field: fn(&mut Self) -> &mut Vec<T>,
+    ) {
+        if let Some(value) = value {
+            field(self).push(value);
+            self.order.push(option);
+        }
+    }
 }
 
-fn push_if_exists<A>(vec: &mut Vec<String>, arg: Option<A>)
+impl<S> Qemu<S>
 where
-    A: QemuArgument,
+    S: UefiSystem,
 {
-    if let Some(arg) = arg {
-        vec.extend(arg.format());
+    /// Boots UEFI firmware from a read-only CODE and a writable VARS pflash.
+    ///
+    /// If no machine type is configured, the system's UEFI machine is
+    /// selected, and for secure boot builds on x86_64, SMM is enabled.
+    pub fn uefi(&mut self, firmware: &UefiFirmware) -> &mut Self {
+        let secure = firmware.secure_boot() && self.system.uefi_secure_boot_requires_smm();
+
+        self.place(QemuOption::Pflash);
+        self.place(QemuOption::Machine);
+        let machine = self.machine.get_or_insert_with(Default::default);
+        if machine.kind.is_none() {
+            machine.kind = Some(self.system.uefi_machine().to_string());
+        }
+        if secure {
+            machine.smm = Some(true);
+        }
+
+        self.pflash = Some(Pflash {
+            code: firmware.code().to_path_buf(),
+            vars: firmware.vars().to_path_buf(),
+            format: firmware.format(),
+            secure,
+        });
+        self
     }
 }
 
-fn push_many_if_exists<A>(vec: &mut Vec<String>, args: Vec<A>)
+fn push_if_exists<A>(
+    vec: &mut Vec<OsString>,
+    arg: Option<&A>,
+    syntax: ArgSyntax,
+) -> Result<(), InvalidValue>
 where
     A: QemuArgument,
 {
-    for arg in args {
-        vec.extend(arg.format());
+    if let Some(arg) = arg {
+        vec.extend(arg.format_with(syntax)?);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::args::{Accelerator, Format};
+    use crate::validate::Diagnostic;
+
+    fn base() -> Qemu<Generic> {
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.machine(Machine {
+            kind: Some("q35".to_string()),
+            ..Default::default()
+        })
+        .other("-m")
+        .other("1G")
+        .drive(Drive {
+            file: PathBuf::from("base.img"),
+            ..Default::default()
+        })
+        .no_reboot();
+        qemu
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut overlay = Qemu::<Generic>::new();
+        overlay
+            .drive(Drive {
+                file: PathBuf::from("test.img"),
+                format: Some(Format::Raw),
+                ..Default::default()
+            })
+            .machine(Machine {
+                kind: Some("pc".to_string()),
+                ..Default::default()
+            })
+            .accel(Accel {
+                accelerator: Accelerator::Tcg,
+                ..Default::default()
+            });
+
+        let mut qemu = base();
+        qemu.merge(overlay);
+        let args = qemu.into_args().unwrap();
+        assert_eq!(
+            "-machine pc -m 1G -drive file=base.img --no-reboot \
+             -drive file=test.img,format=raw \
+             -accel accel=tcg,idg-passthru=off,kernel-irqchip=on",
+            args.to_string()
+        );
+    }
+
+    #[test]
+    fn test_merge_removal() {
+        let mut overlay = Qemu::<Generic>::new();
+        overlay
+            .remove(QemuOption::NoReboot)
+            .remove(QemuOption::Drive)
+            .drive(Drive {
+                file: PathBuf::from("test.img"),
+                ..Default::default()
+            });
+
+        let mut qemu = base();
+        qemu.merge(overlay);
+        let args = qemu.into_args().unwrap();
+        assert_eq!("-machine q35 -m 1G -drive file=test.img", args.to_string());
+    }
+
+    #[test]
+    fn test_merge_is_associative() {
+        let mut first = Qemu::<Generic>::new();
+        first.remove(QemuOption::Other).other("-m").other("2G");
+        let mut second = Qemu::<Generic>::new();
+        second.remove(QemuOption::Drive).no_reboot();
+
+        let mut left = base();
+        left.merge(first.clone()).merge(second.clone());
+        let mut overlay = first;
+        overlay.merge(second);
+        let mut right = base();
+        right.merge(overlay);
+
+        assert_eq!(left.into_args().unwrap(), right.into_args().unwrap());
+    }
+
+    #[test]
+    fn test_clone_base() {
+        let base = base();
+        let mut variant = base.clone();
+        variant.remove(QemuOption::Machine);
+
+        assert_eq!(
+            "-machine q35 -m 1G -drive file=base.img --no-reboot",
+            base.into_args().unwrap().to_string()
+        );
+        assert_eq!(
+            "-m 1G -drive file=base.img --no-reboot",
+            variant.into_args().unwrap().to_string()
+        );
+    }
+
+    #[test]
+    fn test_reuse_configuration() {
+        let qemu = base();
+        let first = qemu.args().unwrap();
+        let second = qemu.args().unwrap();
+        assert_eq!(first, second);
+
+        let cmd = qemu.to_command().unwrap();
+        assert_eq!("qemu-generic", cmd.get_program());
+        assert!(cmd.get_args().eq(first.iter()));
+        assert_eq!(first, qemu.into_args().unwrap());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_binary_location() {
+        use std::fs;
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tmp::unique_path("binary");
+        fs::create_dir(&dir).unwrap();
+        let binary = dir.join("qemu-generic");
+        fs::write(&binary, "#!/bin/sh\necho \"$@\"\n").unwrap();
+
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.no_reboot();
+        match qemu.resolve_binary() {
+            Err(e @ Error::MissingBinary { .. }) => {
+                assert_eq!("qemu-generic not found", e.to_string())
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+
+        qemu.search_dir("/nonexistent").search_dir(&dir);
+        assert_eq!(binary, qemu.to_command().unwrap().get_program());
+        match qemu.resolve_binary() {
+            Err(e @ Error::MissingBinary { .. }) => assert_eq!(
+                format!("{} can't be run: not executable", binary.display()),
+                e.to_string()
+            ),
+            other => panic!("unexpected result {:?}", other),
+        }
+
+        fs::set_permissions(&binary, fs::Permissions::from_mode(0o755)).unwrap();
+        assert_eq!(binary, qemu.resolve_binary().unwrap());
+        let output = qemu.run(Duration::from_secs(10)).unwrap();
+        assert_eq!(b"--no-reboot\n", output.stdout.as_slice());
+
+        qemu.binary(dir.join("qemu-custom"));
+        assert!(matches!(
+            qemu.resolve_binary(),
+            Err(Error::MissingBinary { .. })
+        ));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_runtime_system() {
+        let system = AnySystem::from_target("riscv64gc-unknown-none-elf").unwrap();
+        let mut qemu = Qemu::with_system(system);
+        qemu.no_reboot();
+
+        let cmd = qemu.to_command().unwrap();
+        assert_eq!("qemu-system-riscv64", cmd.get_program());
+        assert!(cmd.get_args().eq(["--no-reboot"]));
+        assert_eq!(&AnySystem::Riscv64, qemu.system());
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.no_reboot().other("-m").other("1G");
+        assert!(qemu.check().is_ok());
+        assert!(matches!(
+            qemu.run(Duration::from_secs(1)),
+            Err(Error::MissingBinary { .. })
+        ));
+
+        qemu.machine(Machine {
+            kind: Some("q35,smm=on".to_string()),
+            ..Default::default()
+        });
+        match qemu.check() {
+            Err(Error::InvalidConfig(diagnostics)) => assert!(matches!(
+                diagnostics.as_slice(),
+                [Diagnostic::InvalidValue(_)]
+            )),
+            res => panic!("unexpected result {:?}", res),
+        }
+        assert!(matches!(qemu.spawn(), Err(Error::InvalidConfig(_))));
     }
 }
@@ -24,6 +24,8 @@ fn main() {
 
     qemu.log_items([LogItem::GuestErrors, LogItem::Cpu]);
 
-    let cmd = Command::from(qemu);
-    println!("cmd: {:?}", cmd);
+    match Command::try_from(qemu) {
+        Ok(cmd) => println!("cmd: {:?}", cmd),
+        Err(err) => eprintln!("error: {}", err),
+    }
 }
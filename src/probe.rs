@@ -0,0 +1,543 @@
+//! Probing what an installed QEMU binary supports, by running it with
+//! `-accel help`, `-machine help` and similar options.
+//!
+//! The output of these options is meant for humans and differs between
+//! systems and versions, so parsing is best-effort: entries that aren't
+//! understood are skipped.
+
+use crate::args::{Accelerator, FromProp, LogItem, ToPropValue};
+use crate::validate::Diagnostic;
+use crate::{process, Error, Qemu, QemuOption, QemuSystem};
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, SystemTime};
+
+/// The capabilities of a QEMU binary.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Capabilities {
+    /// The accelerators that are compiled in. They aren't necessarily
+    /// usable on this host, for example if `/dev/kvm` is missing.
+    pub accelerators: Vec<Accelerator>,
+    pub machines: Vec<MachineInfo>,
+    /// The names of the CPU models.
+    pub cpus: Vec<String>,
+    pub devices: Vec<DeviceInfo>,
+    /// The log items, not including [`LogItem::Trace`], which is always
+    /// supported.
+    pub log_items: Vec<LogItem>,
+    /// The names of the chardev backends, such as `socket`.
+    pub chardevs: Vec<String>,
+}
+
+/// A machine type, as listed by `-machine help`.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct MachineInfo {
+    pub name: String,
+    pub description: String,
+    /// The versioned machine type that this name refers to, such as
+    /// `pc-q35-8.2` for `q35`.
+    pub alias_of: Option<String>,
+    /// Whether this machine is used if none is configured.
+    pub is_default: bool,
+    pub deprecated: bool,
+}
+
+/// A device driver, as listed by `-device help`.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct DeviceInfo {
+    pub name: String,
+    /// The category under which the device is listed, such as `Network`.
+    pub category: String,
+    pub bus: Option<String>,
+    pub alias: Option<String>,
+    pub description: Option<String>,
+    /// Whether the device can only be created by the machine, and not
+    /// with `-device`.
+    pub no_user: bool,
+}
+
+impl Capabilities {
+    /// Runs `binary` with each of the help options. Unlike
+    /// [`Qemu::capabilities`], this doesn't cache the result.
+    pub fn probe(binary: &Path, timeout: Duration) -> Result<Self, Error> {
+        let help = |option: &str| -> Result<String, Error> {
+            let mut cmd = Command::new(binary);
+            cmd.args([option, "help"]);
+            let output = process::run(&mut cmd, timeout)?;
+            Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+        };
+        let capabilities = Self {
+            accelerators: parse_accelerators(&help("-accel")?),
+            machines: parse_machines(&help("-machine")?),
+            cpus: parse_cpus(&help("-cpu")?),
+            devices: parse_devices(&help("-device")?),
+            log_items: parse_log_items(&help("-d")?),
+            chardevs: parse_chardevs(&help("-chardev")?),
+        };
+        for (option, empty) in [
+            ("-accel", capabilities.accelerators.is_empty()),
+            ("-machine", capabilities.machines.is_empty()),
+            ("-device", capabilities.devices.is_empty()),
+        ] {
+            if empty {
+                return Err(Error::Protocol(format!(
+                    "no entries in the output of {} help",
+                    option
+                )));
+            }
+        }
+        Ok(capabilities)
+    }
+
+    pub fn supports_accelerator(&self, accelerator: Accelerator) -> bool {
+        accelerator == Accelerator::Help || self.accelerators.contains(&accelerator)
+    }
+
+    /// Whether a machine type or one of its aliases is supported.
+    pub fn supports_machine(&self, name: &str) -> bool {
+        name == "help" || self.machines.iter().any(|machine| machine.name == name)
+    }
+
+    pub fn supports_cpu(&self, name: &str) -> bool {
+        name == "help" || self.cpus.iter().any(|cpu| cpu == name)
+    }
+
+    /// Whether a device driver, or an alias of one, can be used with
+    /// `-device`.
+    pub fn supports_device(&self, name: &str) -> bool {
+        self.devices.iter().any(|device| {
+            !device.no_user && (device.name == name || device.alias.as_deref() == Some(name))
+        })
+    }
+
+    pub fn supports_log_item(&self, item: &LogItem) -> bool {
+        matches!(item, LogItem::Trace(_) | LogItem::Help) || self.log_items.contains(item)
+    }
+
+    pub fn supports_chardev(&self, backend: &str) -> bool {
+        self.chardevs.iter().any(|chardev| chardev == backend)
+    }
+}
+
+type Cache = Mutex<HashMap<(PathBuf, Option<SystemTime>), Arc<Capabilities>>>;
+
+/// The probed capabilities, by binary and its modification time, so that
+/// a rebuilt binary is probed again.
+static CACHE: OnceLock<Cache> = OnceLock::new();
+
+impl<S> Qemu<S>
+where
+    S: QemuSystem,
+{
+    /// Probes the capabilities of the binary of [`Self::resolve_binary`].
+    /// The result is cached per binary for the lifetime of the process.
+    ///
+    /// Fails if a help option doesn't finish within `timeout`, or if its
+    /// output can't be parsed at all.
+    pub fn capabilities(&self, timeout: Duration) -> Result<Arc<Capabilities>, Error> {
+        let binary = self.resolve_binary()?;
+        let modified = binary.metadata().and_then(|m| m.modified()).ok();
+        let key = (binary, modified);
+        let cache = CACHE.get_or_init(Default::default);
+        if let Some(capabilities) = cache.lock().unwrap().get(&key) {
+            return Ok(capabilities.clone());
+        }
+
+        // Probing takes a while, so the cache isn't locked meanwhile.
+        let capabilities = Arc::new(Capabilities::probe(&key.0, timeout)?);
+        cache.lock().unwrap().insert(key, capabilities.clone());
+        Ok(capabilities)
+    }
+
+    /// Checks that the configured accelerator, machine type, devices and
+    /// log items are supported. An empty list means that no unsupported
+    /// value was found.
+    pub fn check_capabilities(&self, capabilities: &Capabilities) -> Vec<Diagnostic> {
+        let mut unsupported = Vec::new();
+        if let Some(accel) = &self.accel {
+            if !capabilities.supports_accelerator(accel.accelerator) {
+                let value = accel.accelerator.to_prop_value().render_arg();
+                let value = value.unwrap_or_default().to_string_lossy().into_owned();
+                unsupported.push((QemuOption::Accel, value));
+            }
+        }
+        if let Some(kind) = self.machine.as_ref().and_then(|m| m.kind.as_deref()) {
+            if !capabilities.supports_machine(kind) {
+                unsupported.push((QemuOption::Machine, kind.to_string()));
+            }
+        }
+        for driver in self.devices.iter().filter_map(|d| d.driver()) {
+            if !capabilities.supports_device(driver) {
+                unsupported.push((QemuOption::Device, driver.to_string()));
+            }
+        }
+        for item in self.log_items.iter().flat_map(|items| &items.items) {
+            if !capabilities.supports_log_item(item) {
+                unsupported.push((QemuOption::LogItems, item.format()));
+            }
+        }
+
+        unsupported
+            .into_iter()
+            .map(|(option, value)| Diagnostic::Unsupported { option, value })
+            .collect()
+    }
+}
+
+/// The entries of a help output, without headers, which end with ':'.
+/// The entries end at the first empty line after them.
+fn entries(output: &str) -> impl Iterator<Item = &str> {
+    output
+        .lines()
+        .map(str::trim_end)
+        .filter(|line| !line.ends_with(':'))
+        .skip_while(|line| line.is_empty())
+        .take_while(|line| !line.is_empty())
+}
+
+fn parse_accelerators(output: &str) -> Vec<Accelerator> {
+    entries(output)
+        .filter_map(|name| Accelerator::from_prop(OsStr::new(name.trim())))
+        .filter(|&accelerator| accelerator != Accelerator::Help)
+        .collect()
+}
+
+fn parse_machines(output: &str) -> Vec<MachineInfo> {
+    entries(output)
+        .filter_map(|line| {
+            let (name, description) = line.split_once(char::is_whitespace)?;
+            let mut machine = MachineInfo {
+                name: name.to_string(),
+                description: String::new(),
+                alias_of: None,
+                is_default: false,
+                deprecated: false,
+            };
+            let mut description = description.trim();
+            loop {
+                if let Some(rest) = description.strip_suffix(" (default)") {
+                    machine.is_default = true;
+                    description = rest;
+                } else if let Some(rest) = description.strip_suffix(" (deprecated)") {
+                    machine.deprecated = true;
+                    description = rest;
+                } else if let Some((rest, alias)) = description
+                    .strip_suffix(')')
+                    .and_then(|d| d.rsplit_once(" (alias of "))
+                {
+                    machine.alias_of = Some(alias.to_string());
+                    description = rest;
+                } else {
+                    break;
+                }
+            }
+            machine.description = description.to_string();
+            Some(machine)
+        })
+        .collect()
+}
+
+/// Prefixes of the CPU lists of some systems, such as `x86 Broadwell`.
+const CPU_LIST_PREFIXES: &[&str] = &["x86", "PowerPC", "s390", "Sparc"];
+
+fn parse_cpus(output: &str) -> Vec<String> {
+    entries(output)
+        .filter_map(|line| {
+            let mut words = line.split_whitespace();
+            let first = words.next()?;
+            match words.next() {
+                Some(name) if CPU_LIST_PREFIXES.contains(&first) => Some(name.to_string()),
+                _ => Some(first.to_string()),
+            }
+        })
+        .collect()
+}
+
+fn parse_devices(output: &str) -> Vec<DeviceInfo> {
+    let mut devices = Vec::new();
+    let mut category = "";
+    for line in output.lines().map(str::trim) {
+        if let Some(header) = line.strip_suffix(" devices:") {
+            category = header;
+            continue;
+        }
+        let mut fields = DeviceFields(line);
+        let Some(("name", Some(name))) = fields.next() else {
+            continue;
+        };
+        let mut device = DeviceInfo {
+            name: name.to_string(),
+            category: category.to_string(),
+            bus: None,
+            alias: None,
+            description: None,
+            no_user: false,
+        };
+        for (key, value) in fields {
+            let value = value.map(str::to_string);
+            match key {
+                "bus" => device.bus = value,
+                "alias" => device.alias = value,
+                "desc" => device.description = value,
+                "no-user" => device.no_user = true,
+                _ => {}
+            }
+        }
+        devices.push(device);
+    }
+    devices
+}
+
+/// The fields of a line like `name "virtio-net-pci", bus PCI, alias
+/// "virtio-net"`. Quoted values may contain commas.
+struct DeviceFields<'a>(&'a str);
+
+impl<'a> Iterator for DeviceFields<'a> {
+    type Item = (&'a str, Option<&'a str>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let rest = self.0.trim_start_matches([',', ' ']);
+        if rest.is_empty() {
+            return None;
+        }
+        let key_end = rest.find([' ', ',']).unwrap_or(rest.len());
+        let (key, rest) = rest.split_at(key_end);
+        let (value, rest) = match rest.strip_prefix(' ') {
+            Some(rest) => match rest.strip_prefix('"') {
+                Some(quoted) => {
+                    let end = quoted.find('"').unwrap_or(quoted.len());
+                    (Some(&quoted[..end]), quoted.get(end + 1..).unwrap_or(""))
+                }
+                None => {
+                    let end = rest.find(',').unwrap_or(rest.len());
+                    (Some(&rest[..end]), &rest[end..])
+                }
+            },
+            None => (None, rest),
+        };
+        self.0 = rest;
+        Some((key, value))
+    }
+}
+
+fn parse_log_items(output: &str) -> Vec<LogItem> {
+    entries(output)
+        .filter_map(|line| LogItem::parse(line.split_whitespace().next()?))
+        .filter(|item| !matches!(item, LogItem::Trace(_) | LogItem::Help))
+        .collect()
+}
+
+fn parse_chardevs(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.ends_with(':'))
+        .filter(|line| !line.contains(' '))
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::args::{Accel, Device, Machine};
+    use crate::{tmp, Generic};
+
+    const MACHINES: &str = "Supported machines are:
+microvm              microvm (i386)
+pc                   Standard PC (i440FX + PIIX, 1996) (alias of pc-i440fx-8.2)
+pc-i440fx-8.2        Standard PC (i440FX + PIIX, 1996) (default)
+pc-i440fx-2.3        Standard PC (i440FX + PIIX, 1996) (deprecated)
+q35                  Standard PC (Q35 + ICH9, 2009) (alias of pc-q35-8.2)
+pc-q35-8.2           Standard PC (Q35 + ICH9, 2009)
+none                 empty machine
+";
+
+    const DEVICES: &str = "Controller/Bridge/Hub devices:
+name \"pci-bridge\", bus PCI, desc \"Standard PCI Bridge\"
+
+Network devices:
+name \"e1000\", bus PCI, alias \"e1000-82540em\", desc \"Intel Gigabit Ethernet\"
+name \"virtio-net-pci\", bus PCI, alias \"virtio-net\"
+
+Misc devices:
+name \"pc-testdev\", bus ISA, desc \"PC Testdev, for tests\"
+name \"i8042\", bus ISA, no-user
+";
+
+    fn capabilities() -> Capabilities {
+        Capabilities {
+            accelerators: parse_accelerators(
+                "Accelerators supported in QEMU binary:\ntcg\nkvm\nqtest\n",
+            ),
+            machines: parse_machines(MACHINES),
+            cpus: parse_cpus(
+                "Available CPUs:\n\
+                 x86 486                   (alias configured by machine type)\n\
+                 x86 Broadwell             (alias of Broadwell-v1)\n\
+                 x86 host                  processor with all supported host features\n\
+                 \n\
+                 Recognized CPUID flags:\n\
+                 \x20 3dnow 3dnowext 3dnowprefetch abm ace2\n",
+            ),
+            devices: parse_devices(DEVICES),
+            log_items: parse_log_items(
+                "Log items (comma separated):\n\
+                 out_asm         show generated host assembly code for each compiled TB\n\
+                 guest_errors    log when the guest OS does something invalid\n\
+                 trace:PATTERN   enable trace events\n\
+                 \n\
+                 Use \"-d trace:help\" to get a list of trace events.\n",
+            ),
+            chardevs: parse_chardevs(
+                "Available chardev backend types: \n  null\n  socket\n  stdio\n",
+            ),
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let capabilities = capabilities();
+        assert_eq!(
+            vec![Accelerator::Tcg, Accelerator::Kvm],
+            capabilities.accelerators
+        );
+        assert_eq!(
+            MachineInfo {
+                name: "pc".to_string(),
+                description: "Standard PC (i440FX + PIIX, 1996)".to_string(),
+                alias_of: Some("pc-i440fx-8.2".to_string()),
+                is_default: false,
+                deprecated: false,
+            },
+            capabilities.machines[1]
+        );
+        assert!(capabilities.machines[2].is_default);
+        assert!(capabilities.machines[3].deprecated);
+        assert_eq!(7, capabilities.machines.len());
+        assert_eq!(vec!["486", "Broadwell", "host"], capabilities.cpus);
+        assert_eq!(
+            DeviceInfo {
+                name: "e1000".to_string(),
+                category: "Network".to_string(),
+                bus: Some("PCI".to_string()),
+                alias: Some("e1000-82540em".to_string()),
+                description: Some("Intel Gigabit Ethernet".to_string()),
+                no_user: false,
+            },
+            capabilities.devices[1]
+        );
+        assert_eq!(
+            Some("PC Testdev, for tests"),
+            capabilities.devices[3].description.as_deref()
+        );
+        assert!(capabilities.devices[4].no_user);
+        assert_eq!(
+            vec![LogItem::OutAsm, LogItem::GuestErrors],
+            capabilities.log_items
+        );
+        assert_eq!(vec!["null", "socket", "stdio"], capabilities.chardevs);
+    }
+
+    #[test]
+    fn test_supports() {
+        let capabilities = capabilities();
+        assert!(capabilities.supports_accelerator(Accelerator::Kvm));
+        assert!(!capabilities.supports_accelerator(Accelerator::Hvf));
+        assert!(capabilities.supports_machine("q35"));
+        assert!(!capabilities.supports_machine("virt"));
+        assert!(capabilities.supports_cpu("host"));
+        assert!(capabilities.supports_device("virtio-net"));
+        assert!(!capabilities.supports_device("i8042"));
+        assert!(capabilities.supports_log_item(&LogItem::Trace("kvm_*".to_string())));
+        assert!(!capabilities.supports_log_item(&LogItem::Int));
+        assert!(capabilities.supports_chardev("socket"));
+
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.accel(Accel {
+            accelerator: Accelerator::Kvm,
+            ..Default::default()
+        })
+        .machine(Machine {
+            kind: Some("q35".to_string()),
+            ..Default::default()
+        })
+        .device(Device::new("virtio-net-pci"))
+        .log_items([LogItem::GuestErrors]);
+        assert_eq!(
+            Vec::<Diagnostic>::new(),
+            qemu.check_capabilities(&capabilities)
+        );
+
+        qemu.accel(Accel {
+            accelerator: Accelerator::Hvf,
+            ..Default::default()
+        })
+        .device(Device::new("virtio-gpu-pci"))
+        .log_items([LogItem::Int]);
+        assert_eq!(
+            vec![
+                Diagnostic::Unsupported {
+                    option: QemuOption::Accel,
+                    value: "hvf".to_string(),
+                },
+                Diagnostic::Unsupported {
+                    option: QemuOption::Device,
+                    value: "virtio-gpu-pci".to_string(),
+                },
+                Diagnostic::Unsupported {
+                    option: QemuOption::LogItems,
+                    value: "int".to_string(),
+                },
+            ],
+            qemu.check_capabilities(&capabilities)
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_probe_is_cached() {
+        use std::fs;
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tmp::unique_path("probe");
+        fs::create_dir(&dir).unwrap();
+        let calls = dir.join("calls");
+        let binary = dir.join("qemu-generic");
+        fs::write(
+            &binary,
+            format!(
+                "#!/bin/sh\n\
+                 echo \"$1\" >> '{}'\n\
+                 case \"$1\" in\n\
+                 -accel) echo tcg ;;\n\
+                 -machine) echo 'none                 empty machine' ;;\n\
+                 -device) echo 'name \"e1000\", bus PCI' ;;\n\
+                 esac\n",
+                calls.display()
+            ),
+        )
+        .unwrap();
+        fs::set_permissions(&binary, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.binary(&binary);
+        let first = qemu.capabilities(Duration::from_secs(10)).unwrap();
+        let second = qemu.capabilities(Duration::from_secs(10)).unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(vec![Accelerator::Tcg], first.accelerators);
+        assert_eq!(6, fs::read_to_string(&calls).unwrap().lines().count());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_probe_unexpected_output() {
+        let err = Capabilities::probe(Path::new("true"), Duration::from_secs(10)).unwrap_err();
+        assert!(matches!(err, Error::Protocol(_)));
+    }
+}
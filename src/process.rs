@@ -0,0 +1,180 @@
+//! Helpers to run QEMU and related binaries with the failure modes of
+//! [`Error`].
+
+use crate::Error;
+use std::env;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Output, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn binary(cmd: &Command) -> PathBuf {
+    PathBuf::from(cmd.get_program())
+}
+
+/// Spawns the command, and reports a missing binary as such.
+pub(crate) fn spawn(cmd: &mut Command) -> Result<Child, Error> {
+    cmd.spawn().map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => Error::MissingBinary {
+            binary: binary(cmd),
+            source: e,
+        },
+        _ => Error::Io(e),
+    })
+}
+
+/// Runs the command to completion with captured output, and fails if
+/// it doesn't finish in time or exits with a failure status.
+pub(crate) fn run(cmd: &mut Command, timeout: Duration) -> Result<Output, Error> {
+    cmd.stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    let mut child = spawn(cmd)?;
+    // Read the pipes in the background, so the child can't block on them.
+    let stdout = read_in_background(child.stdout.take());
+    let stderr = read_in_background(child.stderr.take());
+
+    let start = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if start.elapsed() > timeout {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(Error::Timeout {
+                binary: binary(cmd),
+                timeout,
+            });
+        }
+        thread::sleep(Duration::from_millis(10));
+    };
+
+    let output = Output {
+        status,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    };
+    if !output.status.success() {
+        return Err(Error::Startup {
+            binary: binary(cmd),
+            status: output.status,
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        });
+    }
+    Ok(output)
+}
+
+/// Returns the binary of `command` that is selected without looking at
+/// `PATH`, see [`crate::Qemu::resolve_binary`].
+pub(crate) fn locate(
+    command: &str,
+    binary: Option<&Path>,
+    search_dirs: &[PathBuf],
+) -> Option<PathBuf> {
+    if let Some(binary) = binary {
+        return Some(binary.to_path_buf());
+    }
+    if let Some(dir) = env::var_os("QEMU_DIR") {
+        return Some(PathBuf::from(dir).join(command));
+    }
+    search_dirs
+        .iter()
+        .map(|dir| dir.join(command))
+        .find(|binary| binary.is_file())
+}
+
+/// Like [`locate`], but falls back to `PATH` and checks that the binary
+/// is executable.
+pub(crate) fn resolve(
+    command: &str,
+    binary: Option<&Path>,
+    search_dirs: &[PathBuf],
+) -> Result<PathBuf, Error> {
+    let binary = match locate(command, binary, search_dirs) {
+        Some(binary) => binary,
+        None => find_in_path(command).ok_or_else(|| Error::MissingBinary {
+            binary: command.into(),
+            source: io::Error::new(io::ErrorKind::NotFound, "not found in PATH"),
+        })?,
+    };
+    match check_executable(&binary) {
+        Ok(()) => Ok(binary),
+        Err(source) => Err(Error::MissingBinary { binary, source }),
+    }
+}
+
+/// Looks up a command in the directories of `PATH`.
+fn find_in_path(command: &str) -> Option<PathBuf> {
+    env::split_paths(&env::var_os("PATH")?)
+        .map(|dir| dir.join(command))
+        .find(|binary| check_executable(binary).is_ok())
+}
+
+/// Fails if `path` isn't an executable file.
+fn check_executable(path: &Path) -> io::Result<()> {
+    let metadata = path.metadata()?;
+    if !metadata.is_file() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "not a file"));
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        if metadata.permissions().mode() & 0o111 == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "not executable",
+            ));
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn read_in_background<R>(pipe: Option<R>) -> thread::JoinHandle<Vec<u8>>
+where
+    R: Read + Send + 'static,
+{
+    thread::spawn(move || {
+        let mut buf = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut buf);
+        }
+        buf
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_binary() {
+        let err = run(
+            &mut Command::new("/nonexistent/qemu"),
+            Duration::from_secs(1),
+        )
+        .unwrap_err();
+        assert!(matches!(err, Error::MissingBinary { .. }));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_failure_and_timeout() {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "echo broken >&2; exit 3"]);
+        match run(&mut cmd, Duration::from_secs(10)).unwrap_err() {
+            Error::Startup { status, stderr, .. } => {
+                assert_eq!(Some(3), status.code());
+                assert_eq!("broken\n", stderr);
+            }
+            err => panic!("unexpected error {:?}", err),
+        }
+
+        let mut cmd = Command::new("sleep");
+        cmd.arg("10");
+        let err = run(&mut cmd, Duration::from_millis(50)).unwrap_err();
+        assert!(matches!(err, Error::Timeout { .. }));
+    }
+}
@@ -0,0 +1,251 @@
+//! VM profiles, which are [`Qemu`] configurations stored as TOML or
+//! JSON files.
+//!
+//! A profile has one entry per option, named like its setter in
+//! kebab-case. Options that don't take a value are set with `true`,
+//! and arguments that aren't modeled are given as `others`:
+//!
+//! ```toml
+//! no-reboot = true
+//! others = ["-m", "1G"]
+//!
+//! [accel]
+//! accelerator = "kvm"
+//!
+//! [[drives]]
+//! file = "disk.img"
+//! format = "raw"
+//! ```
+//!
+//! Options are emitted in the order of the `order` list, which is written
+//! when a configuration is serialized. Options that are missing from the
+//! list, as in the example, are emitted after the others in a fixed order.
+//!
+//! A profile can also be an overlay for [`Qemu::merge`], which lists the
+//! options it removes from the base configuration, such as
+//! `removed = ["drive"]`.
+
+use crate::{Qemu, QemuSystem};
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+
+#[derive(Debug)]
+pub enum ProfileError {
+    /// The profile file can't be read.
+    Io(PathBuf, io::Error),
+    /// The profile file has an extension other than `.toml` or `.json`.
+    UnknownFormat(PathBuf),
+    Toml(toml::de::Error),
+    Json(serde_json::Error),
+    /// The configuration can't be written as TOML, for example because
+    /// a path isn't valid UTF-8.
+    SerializeToml(toml::ser::Error),
+}
+
+impl Display for ProfileError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(path, e) => write!(f, "failed to read {}: {}", path.display(), e),
+            Self::UnknownFormat(path) => {
+                write!(f, "unknown profile format of {}", path.display())
+            }
+            Self::Toml(e) => write!(f, "invalid TOML profile: {}", e),
+            Self::Json(e) => write!(f, "invalid JSON profile: {}", e),
+            Self::SerializeToml(e) => write!(f, "failed to write TOML profile: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ProfileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(_, e) => Some(e),
+            Self::UnknownFormat(_) => None,
+            Self::Toml(e) => Some(e),
+            Self::Json(e) => Some(e),
+            Self::SerializeToml(e) => Some(e),
+        }
+    }
+}
+
+impl<S> Qemu<S>
+where
+    S: QemuSystem + Default,
+{
+    /// Loads a profile, whose format is chosen by the extension of the file.
+    pub fn load_profile(path: impl AsRef<Path>) -> Result<Self, ProfileError> {
+        let path = path.as_ref();
+        let read = || fs::read_to_string(path).map_err(|e| ProfileError::Io(path.into(), e));
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => Self::from_toml(&read()?),
+            Some("json") => Self::from_json(&read()?),
+            _ => Err(ProfileError::UnknownFormat(path.into())),
+        }
+    }
+
+    pub fn from_toml(toml: &str) -> Result<Self, ProfileError> {
+        toml::from_str(toml).map_err(ProfileError::Toml)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, ProfileError> {
+        serde_json::from_str(json).map_err(ProfileError::Json)
+    }
+}
+
+impl<S> Qemu<S>
+where
+    S: QemuSystem,
+{
+    pub fn to_toml(&self) -> Result<String, ProfileError> {
+        toml::to_string(self).map_err(ProfileError::SerializeToml)
+    }
+
+    pub fn to_json(&self) -> Result<String, ProfileError> {
+        serde_json::to_string_pretty(self).map_err(ProfileError::Json)
+    }
+}
+
+/// (De)serializes options that don't take a value as a boolean.
+pub(crate) mod flag {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<T, S>(value: &Option<T>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_bool(value.is_some())
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+    where
+        T: Default,
+        D: Deserializer<'de>,
+    {
+        Ok(bool::deserialize(deserializer)?.then(T::default))
+    }
+}
+
+/// (De)serializes arguments as strings, which fails for arguments that
+/// aren't valid UTF-8.
+pub(crate) mod os_strings {
+    use serde::ser::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::ffi::OsString;
+
+    pub fn serialize<S>(args: &[OsString], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_seq(
+            args.iter()
+                .map(|arg| {
+                    arg.to_str()
+                        .ok_or_else(|| S::Error::custom(format!("non-UTF-8 argument {:?}", arg)))
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+        )
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<OsString>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let args = Vec::<String>::deserialize(deserializer)?;
+        Ok(args.into_iter().map(OsString::from).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::args::{Accel, Accelerator, Device, Drive, Format, LogItem, Machine, PropValue};
+    use crate::chardev::QemuCharDevice;
+    use crate::Generic;
+
+    const PROFILE: &str = r#"
+no-reboot = true
+others = ["-m", "1G"]
+log-items = ["guest_errors", { trace = "virtio_*" }]
+serial = "stdio"
+
+[accel]
+accelerator = "kvm"
+kernel-irqchip = "split"
+
+[machine]
+kind = "q35"
+smm = true
+
+[[drives]]
+file = "disk.img"
+format = "raw"
+
+[[devices]]
+driver = "virtio-net-pci"
+netdev = "net0"
+mq = true
+vectors = 10
+"#;
+
+    #[test]
+    fn test_load_toml() {
+        let qemu = Qemu::<Generic>::from_toml(PROFILE).unwrap();
+        let args = qemu.into_args().unwrap();
+        assert_eq!(
+            "--no-reboot -accel accel=kvm,idg-passthru=off,kernel-irqchip=split \
+             -drive file=disk.img,format=raw -serial stdio -d 'guest_errors,trace:virtio_*' \
+             -machine q35,smm=on -device virtio-net-pci,netdev=net0,mq=on,vectors=10 -m 1G",
+            args.to_string()
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.other("-m")
+            .other("1G")
+            .freeze_on_startup()
+            .accel(Accel {
+                accelerator: Accelerator::Tcg,
+                ..Default::default()
+            })
+            .drive(Drive {
+                file: PathBuf::from("disk.img"),
+                format: Some(Format::Raw),
+                ..Default::default()
+            })
+            .serial(QemuCharDevice::Id("chr0".to_string()))
+            .log_items([LogItem::Int, LogItem::Trace("kvm_*".to_string())])
+            .machine(Machine {
+                kind: Some("virt".to_string()),
+                ..Default::default()
+            })
+            .device(
+                Device::new("virtio-blk-pci")
+                    .prop("drive", "disk0")
+                    .prop("bootindex", PropValue::Int(1)),
+            );
+
+        let toml = qemu.to_toml().unwrap();
+        assert_eq!(qemu, Qemu::<Generic>::from_toml(&toml).unwrap());
+        let json = qemu.to_json().unwrap();
+        assert_eq!(qemu, Qemu::<Generic>::from_json(&json).unwrap());
+    }
+
+    #[test]
+    fn test_load_profile_errors() {
+        assert!(matches!(
+            Qemu::<Generic>::load_profile("vm.yaml"),
+            Err(ProfileError::UnknownFormat(_))
+        ));
+        assert!(matches!(
+            Qemu::<Generic>::load_profile("/nonexistent/vm.toml"),
+            Err(ProfileError::Io(..))
+        ));
+        assert!(matches!(
+            Qemu::<Generic>::from_toml("[accel]\naccelerator = \"qemu\""),
+            Err(ProfileError::Toml(_))
+        ));
+    }
+}
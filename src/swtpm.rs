@@ -0,0 +1,227 @@
+use crate::args::{os_arg, InvalidValue, Tpm, TpmModel};
+use crate::{process, tmp, Error};
+use std::ffi::{OsStr, OsString};
+use std::fs::{self, DirBuilder};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Appends `path` to `prefix` without a lossy conversion.
+///
+/// swtpm splits its options at ',' and has no escape for it, so paths
+/// that contain one are rejected.
+fn path_option(prefix: &str, path: &Path) -> Result<OsString, InvalidValue> {
+    if path.as_os_str().as_encoded_bytes().contains(&b',') {
+        return Err(InvalidValue::new(path, "swtpm paths must not contain ','"));
+    }
+    let mut option = OsString::from(prefix);
+    option.push(os_arg(path)?);
+    Ok(option)
+}
+
+/// A running `swtpm` TPM 2.0 emulator with a temporary state directory.
+///
+/// The process is killed and the state directory is removed when
+/// this is dropped, so it must outlive the QEMU process that uses it.
+#[derive(Debug)]
+pub struct Swtpm {
+    binary: OsString,
+    child: Child,
+    /// Drains stderr, so that the emulator never blocks on a full pipe.
+    stderr: Option<JoinHandle<Vec<u8>>>,
+    state_dir: PathBuf,
+    socket: PathBuf,
+}
+
+impl Swtpm {
+    /// Spawns `swtpm` from `PATH`.
+    pub fn spawn() -> Result<Self, Error> {
+        Self::spawn_from("swtpm")
+    }
+
+    /// Spawns the given `swtpm` binary and waits until its control
+    /// socket is available.
+    ///
+    /// The state directory is only accessible by the current user, and
+    /// spawning fails if something already exists at its path. Fails
+    /// with [`Error::InvalidConfig`] if the path of the state directory
+    /// contains a ',', which swtpm can't parse.
+    pub fn spawn_from(binary: impl AsRef<OsStr>) -> Result<Self, Error> {
+        let binary = binary.as_ref().to_os_string();
+        let state_dir = tmp::unique_path("swtpm");
+        let socket = state_dir.join("swtpm-sock");
+        let tpmstate = path_option("dir=", &state_dir)?;
+        let ctrl = path_option("type=unixio,path=", &socket)?;
+        let mut builder = DirBuilder::new();
+        #[cfg(unix)]
+        std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+        builder.create(&state_dir)?;
+
+        let child = process::spawn(
+            Command::new(&binary)
+                .arg("socket")
+                .arg("--tpm2")
+                .arg("--tpmstate")
+                .arg(tpmstate)
+                .arg("--ctrl")
+                .arg(ctrl)
+                .stdin(Stdio::null())
+                .stderr(Stdio::piped()),
+        );
+        let mut child = match child {
+            Ok(child) => child,
+            Err(e) => {
+                let _ = fs::remove_dir_all(&state_dir);
+                return Err(e);
+            }
+        };
+
+        let stderr = Some(process::read_in_background(child.stderr.take()));
+        let mut swtpm = Self {
+            binary,
+            child,
+            stderr,
+            state_dir,
+            socket,
+        };
+        swtpm.wait_for_socket()?;
+        Ok(swtpm)
+    }
+
+    fn wait_for_socket(&mut self) -> Result<(), Error> {
+        let start = Instant::now();
+        while !self.socket.exists() {
+            if let Some(status) = self.child.try_wait()? {
+                let stderr = match self.stderr.take() {
+                    Some(stderr) => stderr.join().unwrap_or_default(),
+                    None => Vec::new(),
+                };
+                return Err(Error::Startup {
+                    binary: PathBuf::from(&self.binary),
+                    status,
+                    stderr: String::from_utf8_lossy(&stderr).into_owned(),
+                });
+            }
+            if start.elapsed() > STARTUP_TIMEOUT {
+                return Err(Error::Timeout {
+                    binary: PathBuf::from(&self.binary),
+                    timeout: STARTUP_TIMEOUT,
+                });
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        Ok(())
+    }
+
+    /// The control socket that QEMU should connect to.
+    pub fn socket(&self) -> &Path {
+        &self.socket
+    }
+
+    /// The directory in which the emulator keeps its state.
+    pub fn state_dir(&self) -> &Path {
+        &self.state_dir
+    }
+
+    /// Creates a [`Tpm`] argument that connects to this emulator.
+    pub fn tpm(&self, model: TpmModel) -> Tpm {
+        Tpm::new(self.socket.clone(), model)
+    }
+}
+
+impl Drop for Swtpm {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        let _ = fs::remove_dir_all(&self.state_dir);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_path_option() {
+        assert_eq!(
+            OsString::from("dir=/tmp/swtpm-1"),
+            path_option("dir=", Path::new("/tmp/swtpm-1")).unwrap()
+        );
+        let err = path_option("dir=", Path::new("/tmp/a,b/swtpm-1")).unwrap_err();
+        assert_eq!(
+            InvalidValue::new("/tmp/a,b/swtpm-1", "swtpm paths must not contain ','"),
+            err
+        );
+    }
+
+    #[test]
+    fn test_spawn_missing_binary() {
+        let res = Swtpm::spawn_from("/nonexistent/swtpm");
+        assert!(matches!(res, Err(Error::MissingBinary { .. })));
+    }
+
+    /// Writes a stub `swtpm` that runs `script` after looking up the
+    /// control socket path.
+    #[cfg(unix)]
+    fn stub(dir: &Path, script: &str) -> PathBuf {
+        use std::os::unix::fs::PermissionsExt;
+
+        let binary = dir.join("swtpm");
+        fs::write(
+            &binary,
+            format!(
+                "#!/bin/sh\n\
+                 for arg; do case \"$arg\" in type=unixio,path=*) socket=\"${{arg#*path=}}\";; esac; done\n\
+                 {}\n",
+                script
+            ),
+        )
+        .unwrap();
+        fs::set_permissions(&binary, fs::Permissions::from_mode(0o755)).unwrap();
+        binary
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_spawn_stub() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tmp::unique_path("swtpm-test");
+        fs::create_dir(&dir).unwrap();
+        // The output exceeds the capacity of a pipe, so the stub only
+        // creates the socket if stderr is drained.
+        let binary = stub(
+            &dir,
+            "head -c 1000000 /dev/zero >&2\ntouch \"$socket\"\nexec sleep 60",
+        );
+
+        let swtpm = Swtpm::spawn_from(&binary).unwrap();
+        let state_dir = swtpm.state_dir().to_path_buf();
+        assert_eq!(state_dir.join("swtpm-sock"), swtpm.socket());
+        let mode = fs::metadata(&state_dir).unwrap().permissions().mode();
+        assert_eq!(0o700, mode & 0o777);
+
+        drop(swtpm);
+        assert!(!state_dir.exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_spawn_stub_failure() {
+        let dir = tmp::unique_path("swtpm-test");
+        fs::create_dir(&dir).unwrap();
+        let binary = stub(&dir, "echo 'swtpm: no tpm2 support' >&2\nexit 1");
+
+        match Swtpm::spawn_from(&binary) {
+            Err(Error::Startup { stderr, .. }) => {
+                assert_eq!("swtpm: no tpm2 support\n", stderr)
+            }
+            res => panic!("unexpected result {:?}", res),
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
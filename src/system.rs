@@ -0,0 +1,304 @@
+//! The systems that QEMU emulates, one type per `qemu-system-*` binary.
+//!
+//! [`AnySystem`] selects a system at runtime, for example from the target
+//! triple of a cross-compiled kernel.
+
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+pub trait QemuSystem {
+    fn command(&self) -> &'static str;
+
+    /// The architecture name that QEMU uses for this system, for example
+    /// in firmware descriptors.
+    fn architecture(&self) -> &'static str {
+        self.command().trim_start_matches("qemu-system-")
+    }
+
+    /// The machine type to use for a general purpose VM. This isn't
+    /// always QEMU's own default, which is an old board for some systems
+    /// or missing entirely.
+    fn default_machine(&self) -> &'static str;
+
+    /// The guest device name of the serial console of the default
+    /// machine, as used in a `console=` kernel parameter.
+    fn console_device(&self) -> &'static str;
+
+    /// The firmware that the default machine boots if neither `-bios`,
+    /// `-kernel` nor pflash is given.
+    fn default_firmware(&self) -> DefaultFirmware;
+}
+
+/// The firmware that a machine boots without explicit configuration.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum DefaultFirmware {
+    /// QEMU loads firmware that it ships with, such as SeaBIOS.
+    Bundled(&'static str),
+    /// There is no default firmware, so a kernel or firmware has to be
+    /// given.
+    Required,
+}
+
+/// A system that can boot UEFI firmware from a pair of pflash devices.
+pub trait UefiSystem: QemuSystem {
+    /// The machine type that is used if none is configured.
+    fn uefi_machine(&self) -> &'static str;
+
+    /// Whether secure boot firmware requires System Management Mode.
+    fn uefi_secure_boot_requires_smm(&self) -> bool;
+}
+
+macro_rules! systems {
+    ($($(#[$attr:meta])* $name:ident => $command:literal, $machine:literal, $console:literal, $firmware:expr;)*) => {$(
+        $(#[$attr])*
+        #[derive(Default, Debug, Copy, Clone, Eq, PartialEq, Hash)]
+        pub struct $name;
+
+        $(#[$attr])*
+        impl QemuSystem for $name {
+            fn command(&self) -> &'static str {
+                $command
+            }
+
+            fn default_machine(&self) -> &'static str {
+                $machine
+            }
+
+            fn console_device(&self) -> &'static str {
+                $console
+            }
+
+            fn default_firmware(&self) -> DefaultFirmware {
+                $firmware
+            }
+        }
+    )*};
+}
+
+macro_rules! uefi_system {
+    ($name:ident, $machine:literal, $smm:literal) => {
+        impl UefiSystem for $name {
+            fn uefi_machine(&self) -> &'static str {
+                $machine
+            }
+
+            fn uefi_secure_boot_requires_smm(&self) -> bool {
+                $smm
+            }
+        }
+    };
+}
+
+use DefaultFirmware::{Bundled, Required};
+
+systems! {
+    #[cfg(test)]
+    Generic => "qemu-generic", "none", "ttyS0", Required;
+    X86_64 => "qemu-system-x86_64", "q35", "ttyS0", Bundled("SeaBIOS");
+    I386 => "qemu-system-i386", "q35", "ttyS0", Bundled("SeaBIOS");
+    Aarch64 => "qemu-system-aarch64", "virt", "ttyAMA0", Required;
+    Arm => "qemu-system-arm", "virt", "ttyAMA0", Required;
+    Riscv32 => "qemu-system-riscv32", "virt", "ttyS0", Bundled("OpenSBI");
+    Riscv64 => "qemu-system-riscv64", "virt", "ttyS0", Bundled("OpenSBI");
+    Ppc => "qemu-system-ppc", "mac99", "ttyPZ0", Bundled("OpenBIOS");
+    Ppc64 => "qemu-system-ppc64", "pseries", "hvc0", Bundled("SLOF");
+    S390x => "qemu-system-s390x", "s390-ccw-virtio", "ttysclp0", Bundled("s390-ccw");
+    Mips => "qemu-system-mips", "malta", "ttyS0", Required;
+    Mipsel => "qemu-system-mipsel", "malta", "ttyS0", Required;
+    Mips64el => "qemu-system-mips64el", "malta", "ttyS0", Required;
+    Loongarch64 => "qemu-system-loongarch64", "virt", "ttyS0", Required;
+    Sparc64 => "qemu-system-sparc64", "sun4u", "ttyS0", Bundled("OpenBIOS");
+    M68k => "qemu-system-m68k", "virt", "ttyGF0", Required;
+}
+
+uefi_system!(X86_64, "q35", true);
+uefi_system!(I386, "q35", true);
+uefi_system!(Aarch64, "virt", false);
+uefi_system!(Arm, "virt", false);
+uefi_system!(Riscv64, "virt", false);
+uefi_system!(Loongarch64, "virt", false);
+
+macro_rules! any_system {
+    ($($name:ident),*) => {
+        /// A system that is selected at runtime, for use as `Qemu<AnySystem>`.
+        ///
+        /// This doesn't implement [`UefiSystem`], since only some of the
+        /// systems boot UEFI firmware.
+        #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+        #[cfg_attr(
+            feature = "serde",
+            derive(serde::Serialize, serde::Deserialize),
+            serde(rename_all = "lowercase")
+        )]
+        pub enum AnySystem {
+            $($name,)*
+        }
+
+        impl AnySystem {
+            pub const ALL: &'static [Self] = &[$(Self::$name,)*];
+
+            fn system(&self) -> &'static dyn QemuSystem {
+                match self {
+                    $(Self::$name => &$name,)*
+                }
+            }
+        }
+
+        $(
+            impl From<$name> for AnySystem {
+                fn from(_: $name) -> Self {
+                    Self::$name
+                }
+            }
+        )*
+    };
+}
+
+any_system!(
+    X86_64,
+    I386,
+    Aarch64,
+    Arm,
+    Riscv32,
+    Riscv64,
+    Ppc,
+    Ppc64,
+    S390x,
+    Mips,
+    Mipsel,
+    Mips64el,
+    Loongarch64,
+    Sparc64,
+    M68k
+);
+
+impl AnySystem {
+    /// Selects the system that runs code built for a Rust target triple,
+    /// such as `x86_64-unknown-none` or `riscv64gc-unknown-none-elf`.
+    pub fn from_target(triple: &str) -> Result<Self, UnknownSystem> {
+        let arch = triple.split('-').next().unwrap_or_default();
+        let system = match arch {
+            "x86_64" => Self::X86_64,
+            "i386" | "i486" | "i586" | "i686" => Self::I386,
+            "aarch64" | "aarch64_be" | "arm64" | "arm64e" => Self::Aarch64,
+            "powerpc" => Self::Ppc,
+            "powerpc64" | "powerpc64le" => Self::Ppc64,
+            "s390x" => Self::S390x,
+            "mips" => Self::Mips,
+            "mipsel" => Self::Mipsel,
+            "mips64el" | "mipsisa64r6el" => Self::Mips64el,
+            "loongarch64" => Self::Loongarch64,
+            "sparc64" | "sparcv9" => Self::Sparc64,
+            "m68k" => Self::M68k,
+            _ if arch.starts_with("riscv32") => Self::Riscv32,
+            _ if arch.starts_with("riscv64") => Self::Riscv64,
+            _ if arch.starts_with("arm") || arch.starts_with("thumb") => Self::Arm,
+            _ => return Err(UnknownSystem(triple.to_string())),
+        };
+        Ok(system)
+    }
+}
+
+impl QemuSystem for AnySystem {
+    fn command(&self) -> &'static str {
+        self.system().command()
+    }
+
+    fn architecture(&self) -> &'static str {
+        self.system().architecture()
+    }
+
+    fn default_machine(&self) -> &'static str {
+        self.system().default_machine()
+    }
+
+    fn console_device(&self) -> &'static str {
+        self.system().console_device()
+    }
+
+    fn default_firmware(&self) -> DefaultFirmware {
+        self.system().default_firmware()
+    }
+}
+
+impl Display for AnySystem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.architecture())
+    }
+}
+
+/// Parses the architecture name of a system, such as `riscv64`.
+impl FromStr for AnySystem {
+    type Err = UnknownSystem;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .find(|system| system.architecture() == s)
+            .copied()
+            .ok_or_else(|| UnknownSystem(s.to_string()))
+    }
+}
+
+/// An architecture name or target triple without a matching system.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct UnknownSystem(pub String);
+
+impl Display for UnknownSystem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "no QEMU system for {}", self.0)
+    }
+}
+
+impl std::error::Error for UnknownSystem {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_architecture() {
+        assert_eq!("x86_64", X86_64.architecture());
+        assert_eq!("i386", I386.architecture());
+        assert_eq!("riscv64", Riscv64.architecture());
+        assert_eq!("mips64el", Mips64el.architecture());
+        assert_eq!("loongarch64", Loongarch64.architecture());
+    }
+
+    #[test]
+    fn test_defaults() {
+        assert_eq!("pseries", Ppc64.default_machine());
+        assert_eq!("hvc0", Ppc64.console_device());
+        assert_eq!(Bundled("SLOF"), Ppc64.default_firmware());
+        assert_eq!(Required, Aarch64.default_firmware());
+        assert_eq!("virt", Riscv64.uefi_machine());
+    }
+
+    #[test]
+    fn test_any_system() {
+        for (triple, system) in [
+            ("x86_64-unknown-none", AnySystem::X86_64),
+            ("i686-unknown-linux-gnu", AnySystem::I386),
+            ("aarch64-unknown-none-softfloat", AnySystem::Aarch64),
+            ("thumbv7em-none-eabihf", AnySystem::Arm),
+            ("armv7a-none-eabi", AnySystem::Arm),
+            ("riscv32imac-unknown-none-elf", AnySystem::Riscv32),
+            ("riscv64gc-unknown-none-elf", AnySystem::Riscv64),
+            ("powerpc-unknown-linux-gnu", AnySystem::Ppc),
+            ("powerpc64le-unknown-linux-gnu", AnySystem::Ppc64),
+            ("loongarch64-unknown-none", AnySystem::Loongarch64),
+        ] {
+            assert_eq!(Ok(system), AnySystem::from_target(triple), "{}", triple);
+        }
+        assert_eq!(
+            Err(UnknownSystem("wasm32-unknown-unknown".to_string())),
+            AnySystem::from_target("wasm32-unknown-unknown")
+        );
+
+        for system in AnySystem::ALL {
+            assert_eq!(Ok(*system), system.to_string().parse());
+        }
+        assert_eq!("qemu-system-ppc64", AnySystem::from(Ppc64).command());
+        assert_eq!("ttyAMA0", AnySystem::Aarch64.console_device());
+    }
+}
@@ -0,0 +1,74 @@
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns a path in the system's temporary directory that is unique
+/// for this process. Nothing is created at that path.
+pub(crate) fn unique_path(prefix: &str) -> PathBuf {
+    let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+    std::env::temp_dir().join(format!("qemu_api-{}-{}-{}", prefix, process::id(), n))
+}
+
+/// A file that is removed when this is dropped.
+#[derive(Debug, Eq, PartialEq, Hash)]
+pub(crate) struct TempFile {
+    path: PathBuf,
+}
+
+impl TempFile {
+    /// Copies `source` into a new temporary file, which is only accessible
+    /// by the current user, regardless of the permissions of `source`.
+    ///
+    /// Fails if something already exists at the temporary path, so that
+    /// a planted file or symlink is never written to.
+    pub(crate) fn copy_from(source: &Path, prefix: &str) -> io::Result<Self> {
+        let mut source = File::open(source)?;
+        let path = unique_path(prefix);
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut target = options.open(&path)?;
+        // Removes the partial copy if copying fails
+        let file = Self { path };
+        io::copy(&mut source, &mut target)?;
+        Ok(file)
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(unix)]
+    fn test_copy_from_readonly() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let source = unique_path("tmp-test");
+        fs::write(&source, b"template").unwrap();
+        fs::set_permissions(&source, fs::Permissions::from_mode(0o444)).unwrap();
+
+        let copy = TempFile::copy_from(&source, "tmp-test").unwrap();
+        assert_eq!(b"template".as_slice(), fs::read(copy.path()).unwrap());
+        let mode = fs::metadata(copy.path()).unwrap().permissions().mode();
+        assert_eq!(0o600, mode & 0o777);
+        fs::write(copy.path(), b"written").unwrap();
+
+        fs::remove_file(&source).unwrap();
+    }
+}
@@ -0,0 +1,309 @@
+//! User-mode emulation, which runs a single Linux program of another
+//! architecture with `qemu-<arch>`, such as `qemu-riscv64`.
+
+use crate::args::{os_arg, InvalidValue, LogItem, LogItems, Plugin, QemuArgs, QemuArgument};
+use crate::{process, Error, QemuSystem};
+use std::ffi::{OsStr, OsString};
+use std::path::PathBuf;
+use std::process::{Child, Command, Output};
+use std::time::Duration;
+
+/// A program that runs under user-mode emulation for the architecture
+/// of the system `S`.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct QemuUser<S> {
+    program: PathBuf,
+    program_args: Vec<OsString>,
+    sysroot: Option<PathBuf>,
+    /// Variables to set, or to unset if the value is `None`, in order.
+    env: Vec<(OsString, Option<OsString>)>,
+    cpu: Option<String>,
+    gdb_port: Option<u16>,
+    strace: bool,
+    log_items: Option<LogItems>,
+    log_file: Option<PathBuf>,
+    plugins: Vec<Plugin>,
+    binary: Option<PathBuf>,
+    search_dirs: Vec<PathBuf>,
+    system: S,
+}
+
+impl<S> QemuUser<S>
+where
+    S: QemuSystem + Default,
+{
+    pub fn new(program: impl Into<PathBuf>) -> Self {
+        Self::with_system(S::default(), program)
+    }
+}
+
+impl<S> QemuUser<S>
+where
+    S: QemuSystem,
+{
+    /// Creates a launcher for a system that is selected at runtime, such
+    /// as an [`AnySystem`](crate::AnySystem).
+    pub fn with_system(system: S, program: impl Into<PathBuf>) -> Self {
+        Self {
+            program: program.into(),
+            program_args: Vec::new(),
+            sysroot: None,
+            env: Vec::new(),
+            cpu: None,
+            gdb_port: None,
+            strace: false,
+            log_items: None,
+            log_file: None,
+            plugins: Vec::new(),
+            binary: None,
+            search_dirs: Vec::new(),
+            system,
+        }
+    }
+
+    /// The name of the user-mode binary, such as `qemu-riscv64`.
+    pub fn command(&self) -> String {
+        format!("qemu-{}", self.system.architecture())
+    }
+
+    /// Adds an argument of the program.
+    pub fn arg(&mut self, arg: impl AsRef<OsStr>) -> &mut Self {
+        self.program_args.push(arg.as_ref().to_os_string());
+        self
+    }
+
+    /// Adds arguments of the program.
+    pub fn args<I>(&mut self, args: I) -> &mut Self
+    where
+        I: IntoIterator,
+        I::Item: AsRef<OsStr>,
+    {
+        for arg in args {
+            self.arg(arg);
+        }
+        self
+    }
+
+    /// Sets the directory in which the dynamic linker and shared libraries
+    /// of the program are looked up, via the `-L` option.
+    pub fn sysroot(&mut self, dir: impl Into<PathBuf>) -> &mut Self {
+        self.sysroot = Some(dir.into());
+        self
+    }
+
+    /// Sets an environment variable of the program via the `-E` option.
+    pub fn env(&mut self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) -> &mut Self {
+        self.env.push((
+            key.as_ref().to_os_string(),
+            Some(value.as_ref().to_os_string()),
+        ));
+        self
+    }
+
+    /// Unsets an environment variable of the program via the `-U` option.
+    pub fn env_remove(&mut self, key: impl AsRef<OsStr>) -> &mut Self {
+        self.env.push((key.as_ref().to_os_string(), None));
+        self
+    }
+
+    /// Selects the CPU model via the `-cpu` option.
+    pub fn cpu(&mut self, cpu: impl ToString) -> &mut Self {
+        self.cpu = Some(cpu.to_string());
+        self
+    }
+
+    /// Waits for a gdb connection on the given port via the `-g` option,
+    /// before the program starts.
+    pub fn gdb(&mut self, port: u16) -> &mut Self {
+        self.gdb_port = Some(port);
+        self
+    }
+
+    /// Logs the system calls of the program via the `-strace` option.
+    pub fn strace(&mut self) -> &mut Self {
+        self.strace = true;
+        self
+    }
+
+    /// Specifies log items via the `-d` option.
+    pub fn log_items<I>(&mut self, log_items: I) -> &mut Self
+    where
+        I: IntoIterator<Item = LogItem>,
+    {
+        self.log_items = Some(LogItems::from(log_items));
+        self
+    }
+
+    /// Writes the log to a file instead of stderr, via the `-D` option.
+    pub fn log_file(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+        self.log_file = Some(path.into());
+        self
+    }
+
+    /// Loads a TCG plugin via the `-plugin` option. This option is
+    /// repeatable.
+    pub fn plugin(&mut self, plugin: Plugin) -> &mut Self {
+        self.plugins.push(plugin);
+        self
+    }
+
+    /// Runs the given binary instead of looking up [`Self::command`].
+    pub fn binary(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+        self.binary = Some(path.into());
+        self
+    }
+
+    /// Adds a directory in which [`Self::command`] is looked up before
+    /// `PATH`.
+    pub fn search_dir(&mut self, dir: impl Into<PathBuf>) -> &mut Self {
+        self.search_dirs.push(dir.into());
+        self
+    }
+
+    /// Returns the arguments of QEMU, followed by the program and its
+    /// arguments.
+    pub fn to_args(&self) -> Result<QemuArgs, InvalidValue> {
+        let mut args: Vec<OsString> = Vec::new();
+        if let Some(sysroot) = &self.sysroot {
+            args.extend(["-L".into(), os_arg(sysroot)?]);
+        }
+        for (key, value) in &self.env {
+            match value {
+                Some(value) => {
+                    let mut var = env_part(key, true)?;
+                    var.push("=");
+                    var.push(env_part(value, false)?);
+                    args.extend(["-E".into(), var]);
+                }
+                None => args.extend(["-U".into(), env_part(key, true)?]),
+            }
+        }
+        if let Some(cpu) = &self.cpu {
+            args.extend(["-cpu".into(), os_arg(cpu)?]);
+        }
+        if let Some(port) = self.gdb_port {
+            args.extend(["-g".into(), port.to_string().into()]);
+        }
+        if self.strace {
+            args.push("-strace".into());
+        }
+        if let Some(log_items) = &self.log_items {
+            args.extend(log_items.format()?);
+        }
+        if let Some(log_file) = &self.log_file {
+            args.extend(["-D".into(), os_arg(log_file)?]);
+        }
+        for plugin in &self.plugins {
+            args.extend(plugin.format()?);
+        }
+        args.push(os_arg(&self.program)?);
+        for arg in &self.program_args {
+            args.push(os_arg(arg)?);
+        }
+        Ok(QemuArgs::from(args))
+    }
+
+    /// Returns a command that runs the program. Like
+    /// [`Qemu::to_command`](crate::Qemu::to_command), this doesn't check
+    /// that the binary exists.
+    pub fn to_command(&self) -> Result<Command, InvalidValue> {
+        let command = self.command();
+        let binary = process::locate(&command, self.binary.as_deref(), &self.search_dirs)
+            .unwrap_or_else(|| command.into());
+        let mut cmd = Command::new(binary);
+        cmd.args(self.to_args()?);
+        Ok(cmd)
+    }
+
+    /// Returns the binary that is run, which is looked up like the one
+    /// of [`Qemu::resolve_binary`](crate::Qemu::resolve_binary).
+    pub fn resolve_binary(&self) -> Result<PathBuf, Error> {
+        process::resolve(&self.command(), self.binary.as_deref(), &self.search_dirs)
+    }
+
+    pub fn spawn(&self) -> Result<Child, Error> {
+        process::spawn(&mut self.resolved_command()?)
+    }
+
+    /// Runs the program to completion with captured output. Fails if it
+    /// doesn't exit within `timeout` or exits with a failure status.
+    pub fn run(&self, timeout: Duration) -> Result<Output, Error> {
+        process::run(&mut self.resolved_command()?, timeout)
+    }
+
+    fn resolved_command(&self) -> Result<Command, Error> {
+        let args = self.to_args()?;
+        let mut cmd = Command::new(self.resolve_binary()?);
+        cmd.args(args);
+        Ok(cmd)
+    }
+}
+
+/// Checks a name or value of an environment variable. QEMU splits the
+/// values of `-E` and `-U` at commas, without any escaping.
+fn env_part(part: &OsStr, is_name: bool) -> Result<OsString, InvalidValue> {
+    let bytes = part.as_encoded_bytes();
+    if bytes.contains(&b',') {
+        return Err(InvalidValue::new(
+            part,
+            "environment variables must not contain ','",
+        ));
+    }
+    if is_name && (bytes.is_empty() || bytes.contains(&b'=')) {
+        return Err(InvalidValue::new(
+            part,
+            "environment variable names must not be empty or contain '='",
+        ));
+    }
+    os_arg(part)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AnySystem, Riscv64};
+
+    #[test]
+    fn test_user_args() {
+        let mut qemu = QemuUser::<Riscv64>::new("target/riscv64gc-unknown-linux-gnu/debug/app");
+        qemu.sysroot("/usr/riscv64-linux-gnu")
+            .env("RUST_LOG", "debug")
+            .env_remove("LD_PRELOAD")
+            .cpu("rv64,v=true")
+            .gdb(1234)
+            .strace()
+            .log_items([
+                LogItem::GuestErrors,
+                LogItem::Trace("syscall_*".to_string()),
+            ])
+            .log_file("/tmp/qemu.log")
+            .plugin(Plugin::new("/usr/lib/qemu/libinsn.so").arg("inline", true))
+            .args(["--test-threads", "1"]);
+
+        assert_eq!("qemu-riscv64", qemu.command());
+        assert_eq!(
+            "-L /usr/riscv64-linux-gnu -E RUST_LOG=debug -U LD_PRELOAD -cpu rv64,v=true \
+             -g 1234 -strace -d 'guest_errors,trace:syscall_*' -D /tmp/qemu.log \
+             -plugin file=/usr/lib/qemu/libinsn.so,inline=on \
+             target/riscv64gc-unknown-linux-gnu/debug/app --test-threads 1",
+            qemu.to_args().unwrap().to_string()
+        );
+        assert_eq!("qemu-riscv64", qemu.to_command().unwrap().get_program());
+    }
+
+    #[test]
+    fn test_invalid_env() {
+        let mut qemu = QemuUser::with_system(AnySystem::Aarch64, "app");
+        assert_eq!("qemu-aarch64", qemu.command());
+        qemu.env("PATH", "/bin,/usr/bin");
+        assert!(qemu.to_args().is_err());
+
+        let mut qemu = QemuUser::with_system(AnySystem::Aarch64, "app");
+        qemu.env_remove("A=B");
+        assert!(qemu.to_args().is_err());
+        assert!(matches!(
+            qemu.run(Duration::from_secs(1)),
+            Err(Error::InvalidConfig(_))
+        ));
+    }
+}
@@ -0,0 +1,327 @@
+use crate::args::{Accelerator, InvalidValue, NumaError};
+use crate::{Qemu, QemuOption, QemuSystem};
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+
+/// A mistake in a configuration, which QEMU would reject or ignore.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum Diagnostic {
+    /// An accelerator property that has no effect with the selected
+    /// accelerator, such as `tb-size` with KVM.
+    IrrelevantAccelProperty {
+        accelerator: Accelerator,
+        property: &'static str,
+    },
+    /// `-help` or `-version` is combined with other options, which have
+    /// no effect since QEMU exits right away.
+    ExitsImmediately(QemuOption),
+    /// `-fullscreen` is combined with `-display none` or `-nographic`.
+    FullscreenWithoutDisplay,
+    /// A file that is used by an option doesn't exist.
+    MissingFile {
+        option: QemuOption,
+        path: PathBuf,
+    },
+    /// An id that is used by more than one value of an option.
+    DuplicateId {
+        option: QemuOption,
+        id: String,
+    },
+    Numa(NumaError),
+    /// A value that the QEMU binary doesn't support, as found by
+    /// [`Qemu::check_capabilities`].
+    Unsupported {
+        option: QemuOption,
+        value: String,
+    },
+    /// A value can't be represented on the command line.
+    InvalidValue(InvalidValue),
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::IrrelevantAccelProperty {
+                accelerator,
+                property,
+            } => write!(
+                f,
+                "accel property {} has no effect with {:?}",
+                property, accelerator
+            ),
+            Self::ExitsImmediately(option) => write!(
+                f,
+                "{:?} makes QEMU exit, the other options have no effect",
+                option
+            ),
+            Self::FullscreenWithoutDisplay => write!(f, "fullscreen requires a display"),
+            Self::MissingFile { option, path } => {
+                write!(f, "file {} of {:?} doesn't exist", path.display(), option)
+            }
+            Self::DuplicateId { option, id } => {
+                write!(f, "id '{}' is used by more than one {:?}", id, option)
+            }
+            Self::Numa(e) => e.fmt(f),
+            Self::Unsupported { option, value } => {
+                write!(f, "{:?} {} isn't supported by QEMU", option, value)
+            }
+            Self::InvalidValue(e) => e.fmt(f),
+        }
+    }
+}
+
+impl<S> Qemu<S>
+where
+    S: QemuSystem,
+{
+    /// Checks the configuration for mistakes that would only show up as
+    /// QEMU errors, or not at all. An empty list means no mistakes were
+    /// found.
+    ///
+    /// This also checks that referenced files exist, so the result
+    /// depends on the file system.
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+
+        if let Some(accel) = &self.accel {
+            for property in accel.irrelevant_properties() {
+                diagnostics.push(Diagnostic::IrrelevantAccelProperty {
+                    accelerator: accel.accelerator,
+                    property,
+                });
+            }
+        }
+
+        for (option, set) in [
+            (QemuOption::Help, self.help.is_some()),
+            (QemuOption::Version, self.version.is_some()),
+        ] {
+            if set && self.full_order().iter().any(|&o| o != option) {
+                diagnostics.push(Diagnostic::ExitsImmediately(option));
+            }
+        }
+
+        if self.fullscreen.is_some() && self.has_no_display() {
+            diagnostics.push(Diagnostic::FullscreenWithoutDisplay);
+        }
+
+        let mut files: Vec<(QemuOption, &Path)> = Vec::new();
+        if let Some(bios) = &self.bios {
+            files.push((QemuOption::Bios, &bios.0));
+        }
+        if let Some(pflash) = &self.pflash {
+            files.push((QemuOption::Pflash, &pflash.code));
+            files.push((QemuOption::Pflash, &pflash.vars));
+        }
+        if let Some(mem_path) = &self.mem_path {
+            files.push((QemuOption::MemPath, &mem_path.0));
+        }
+        for drive in &self.drives {
+            files.push((QemuOption::Drive, &drive.file));
+        }
+        for (option, path) in files {
+            if !path.exists() {
+                diagnostics.push(Diagnostic::MissingFile {
+                    option,
+                    path: path.to_path_buf(),
+                });
+            }
+        }
+
+        let ids = [
+            (
+                QemuOption::Object,
+                self.objects.iter().map(|o| o.id()).collect::<Vec<_>>(),
+            ),
+            (
+                QemuOption::Device,
+                self.devices.iter().filter_map(|d| d.id()).collect(),
+            ),
+            (
+                QemuOption::Netdev,
+                self.netdevs.iter().map(|n| n.id.as_str()).collect(),
+            ),
+            (
+                QemuOption::Chardev,
+                self.chardevs.iter().map(|c| c.id.as_str()).collect(),
+            ),
+            (
+                QemuOption::Blockdev,
+                self.blockdevs
+                    .iter()
+                    .filter_map(|b| b.node_name.as_deref())
+                    .collect(),
+            ),
+        ];
+        for (option, ids) in ids {
+            let mut seen = HashSet::new();
+            for id in ids {
+                if !seen.insert(id) {
+                    diagnostics.push(Diagnostic::DuplicateId {
+                        option,
+                        id: id.to_string(),
+                    });
+                }
+            }
+        }
+
+        if let Err(e) = self.check_numa() {
+            diagnostics.push(Diagnostic::Numa(e));
+        }
+
+        if let Err(e) = self.args() {
+            diagnostics.push(Diagnostic::InvalidValue(e));
+        }
+
+        diagnostics
+    }
+
+    /// Whether the display is disabled by other arguments.
+    fn has_no_display(&self) -> bool {
+        self.others.iter().enumerate().any(|(i, arg)| {
+            let arg = arg.to_str().map(|arg| arg.replacen("--", "-", 1));
+            match arg.as_deref() {
+                Some("-nographic") => true,
+                Some("-display") => self.others.get(i + 1).is_some_and(|display| {
+                    display
+                        .to_str()
+                        .is_some_and(|display| display.split(',').next() == Some("none"))
+                }),
+                _ => false,
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::args::{Accel, Device, Drive, Netdev, NetdevKind, ObjectId};
+    use crate::Generic;
+
+    #[test]
+    fn test_valid() {
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.accel(Accel {
+            accelerator: Accelerator::Kvm,
+            kvm_shadow_mem: Some(1 << 20),
+            ..Default::default()
+        })
+        .fullscreen()
+        .device(Device::new("virtio-net-pci").prop("id", "nic0"))
+        .device(Device::new("virtio-net-pci").prop("id", "nic1"));
+
+        assert_eq!(Vec::<Diagnostic>::new(), qemu.validate());
+    }
+
+    #[test]
+    fn test_irrelevant_accel_properties() {
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.accel(Accel {
+            accelerator: Accelerator::Tcg,
+            kvm_shadow_mem: Some(1 << 20),
+            dirty_ring_size: Some(4096),
+            tb_size: Some(256),
+            ..Default::default()
+        });
+
+        assert_eq!(
+            vec![
+                Diagnostic::IrrelevantAccelProperty {
+                    accelerator: Accelerator::Tcg,
+                    property: "kvm-shadow-mem",
+                },
+                Diagnostic::IrrelevantAccelProperty {
+                    accelerator: Accelerator::Tcg,
+                    property: "dirty-ring-size",
+                },
+            ],
+            qemu.validate()
+        );
+
+        qemu.accel(Accel {
+            accelerator: Accelerator::Kvm,
+            tb_size: Some(256),
+            ..Default::default()
+        });
+        assert_eq!(
+            vec![Diagnostic::IrrelevantAccelProperty {
+                accelerator: Accelerator::Kvm,
+                property: "tb-size",
+            }],
+            qemu.validate()
+        );
+    }
+
+    #[test]
+    fn test_conflicting_switches() {
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.version();
+        assert!(qemu.validate().is_empty());
+
+        qemu.other("-m")
+            .other("1G")
+            .fullscreen()
+            .other("-nographic");
+        assert_eq!(
+            vec![
+                Diagnostic::ExitsImmediately(QemuOption::Version),
+                Diagnostic::FullscreenWithoutDisplay,
+            ],
+            qemu.validate()
+        );
+
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.fullscreen().other("--display").other("none");
+        assert_eq!(vec![Diagnostic::FullscreenWithoutDisplay], qemu.validate());
+    }
+
+    #[test]
+    fn test_missing_files_and_duplicate_ids() {
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.bios(PathBuf::from("/nonexistent/bios.bin"))
+            .drive(Drive {
+                file: PathBuf::from("/nonexistent/disk.img"),
+                ..Default::default()
+            })
+            .netdev(Netdev {
+                id: ObjectId::new("net0"),
+                kind: NetdevKind::User {
+                    net: None,
+                    restrict: None,
+                },
+            })
+            .netdev(Netdev {
+                id: ObjectId::new("net0"),
+                kind: NetdevKind::User {
+                    net: None,
+                    restrict: None,
+                },
+            })
+            .device(Device::new("e1000").prop("id", "nic0"))
+            .device(Device::new("e1000").prop("id", "nic0"));
+
+        assert_eq!(
+            vec![
+                Diagnostic::MissingFile {
+                    option: QemuOption::Bios,
+                    path: PathBuf::from("/nonexistent/bios.bin"),
+                },
+                Diagnostic::MissingFile {
+                    option: QemuOption::Drive,
+                    path: PathBuf::from("/nonexistent/disk.img"),
+                },
+                Diagnostic::DuplicateId {
+                    option: QemuOption::Device,
+                    id: "nic0".to_string(),
+                },
+                Diagnostic::DuplicateId {
+                    option: QemuOption::Netdev,
+                    id: "net0".to_string(),
+                },
+            ],
+            qemu.validate()
+        );
+    }
+}
@@ -0,0 +1,218 @@
+use crate::args::{QemuArgument, Version};
+use crate::user::QemuUser;
+use crate::{process, Error, Qemu, QemuSystem};
+use std::cmp::Ordering;
+use std::fmt::{Display, Formatter};
+use std::hash::{Hash, Hasher};
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::Duration;
+
+/// The version of a QEMU binary, as reported by `-version`.
+///
+/// Versions are compared by their numbers only, so that features can be
+/// gated with comparisons like `version >= QemuVersion::new(8, 2, 0)`,
+/// also for distribution builds with a package suffix.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct QemuVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub micro: u32,
+    /// The suffix of a distribution package or of a development build,
+    /// such as `Debian 1:8.2.2+ds-0ubuntu1` or `v9.0.0-1234-gabcdef`.
+    pub package: Option<String>,
+}
+
+impl QemuVersion {
+    pub fn new(major: u32, minor: u32, micro: u32) -> Self {
+        Self {
+            major,
+            minor,
+            micro,
+            package: None,
+        }
+    }
+
+    /// Parses the output of `-version`, which starts with a line like
+    /// `QEMU emulator version 8.2.2 (Debian 1:8.2.2+ds-0ubuntu1)`.
+    pub fn parse(output: &str) -> Option<Self> {
+        let line = output.lines().find(|line| line.contains(" version "))?;
+        let (_, rest) = line.split_once(" version ")?;
+        let (number, package) = match rest.trim().split_once(char::is_whitespace) {
+            Some((number, package)) => (number, Some(package.trim())),
+            None => (rest.trim(), None),
+        };
+
+        let mut parts = number.split('.').map(str::parse::<u32>);
+        let major = parts.next()?.ok()?;
+        let minor = parts.next()?.ok()?;
+        let micro = match parts.next() {
+            Some(micro) => micro.ok()?,
+            None => 0,
+        };
+        if parts.next().is_some() {
+            return None;
+        }
+
+        let package = package
+            .map(|package| {
+                package
+                    .strip_prefix('(')
+                    .and_then(|package| package.strip_suffix(')'))
+                    .unwrap_or(package)
+            })
+            .filter(|package| !package.is_empty())
+            .map(str::to_string);
+        Some(Self {
+            major,
+            minor,
+            micro,
+            package,
+        })
+    }
+
+    fn numbers(&self) -> (u32, u32, u32) {
+        (self.major, self.minor, self.micro)
+    }
+
+    /// Runs `binary -version` and parses its output.
+    fn query(binary: PathBuf, timeout: Duration) -> Result<Self, Error> {
+        let mut cmd = Command::new(binary);
+        cmd.args(Version.format()?);
+        let output = process::run(&mut cmd, timeout)?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        Self::parse(&stdout).ok_or_else(|| {
+            Error::Protocol(format!("unexpected version output {:?}", stdout.trim()))
+        })
+    }
+}
+
+impl PartialEq for QemuVersion {
+    fn eq(&self, other: &Self) -> bool {
+        self.numbers() == other.numbers()
+    }
+}
+
+impl Eq for QemuVersion {}
+
+impl PartialOrd for QemuVersion {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for QemuVersion {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.numbers().cmp(&other.numbers())
+    }
+}
+
+impl Hash for QemuVersion {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.numbers().hash(state);
+    }
+}
+
+impl Display for QemuVersion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.micro)?;
+        match &self.package {
+            Some(package) => write!(f, " ({})", package),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<S> Qemu<S>
+where
+    S: QemuSystem,
+{
+    /// Runs the binary of [`Self::resolve_binary`] with only `-version`,
+    /// and returns the reported version. The configured options aren't
+    /// passed.
+    pub fn query_version(&self, timeout: Duration) -> Result<QemuVersion, Error> {
+        QemuVersion::query(self.resolve_binary()?, timeout)
+    }
+}
+
+impl<S> QemuUser<S>
+where
+    S: QemuSystem,
+{
+    /// Like [`Qemu::query_version`], for the user-mode binary.
+    pub fn query_version(&self, timeout: Duration) -> Result<QemuVersion, Error> {
+        QemuVersion::query(self.resolve_binary()?, timeout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{tmp, Generic};
+
+    #[test]
+    fn test_parse() {
+        let version = QemuVersion::parse(
+            "QEMU emulator version 8.2.2 (Debian 1:8.2.2+ds-0ubuntu1.4)\n\
+             Copyright (c) 2003-2023 Fabrice Bellard and the QEMU Project developers\n",
+        )
+        .unwrap();
+        assert_eq!(QemuVersion::new(8, 2, 2), version);
+        assert_eq!(
+            Some("Debian 1:8.2.2+ds-0ubuntu1.4"),
+            version.package.as_deref()
+        );
+        assert_eq!("8.2.2 (Debian 1:8.2.2+ds-0ubuntu1.4)", version.to_string());
+        assert!(version >= QemuVersion::new(8, 2, 0));
+        assert!(version < QemuVersion::new(9, 0, 0));
+        assert!(version <= QemuVersion::new(8, 2, 2));
+        assert!(version >= QemuVersion::new(8, 2, 2));
+        assert!(version > QemuVersion::new(8, 2, 1));
+
+        assert_eq!(
+            Some(QemuVersion::new(9, 1, 0)),
+            QemuVersion::parse("qemu-riscv64 version 9.1.0\n")
+        );
+        assert_eq!(
+            Some("v9.0.0-1234-gabcdef".to_string()),
+            QemuVersion::parse("QEMU emulator version 9.0.50 (v9.0.0-1234-gabcdef)")
+                .unwrap()
+                .package
+        );
+        assert_eq!(None, QemuVersion::parse("QEMU emulator version unknown"));
+        assert_eq!(None, QemuVersion::parse(""));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_query_version() {
+        use std::fs;
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tmp::unique_path("version");
+        fs::create_dir(&dir).unwrap();
+        let binary = dir.join("qemu-generic");
+        fs::write(
+            &binary,
+            "#!/bin/sh\n[ \"$*\" = --version ] && echo 'QEMU emulator version 7.2.0'\n",
+        )
+        .unwrap();
+        fs::set_permissions(&binary, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let mut qemu = Qemu::<Generic>::new();
+        qemu.no_reboot().binary(&binary);
+        assert_eq!(
+            QemuVersion::new(7, 2, 0),
+            qemu.query_version(Duration::from_secs(10)).unwrap()
+        );
+
+        fs::write(&binary, "#!/bin/sh\necho garbage\n").unwrap();
+        assert!(matches!(
+            qemu.query_version(Duration::from_secs(10)),
+            Err(Error::Protocol(_))
+        ));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}